@@ -0,0 +1,75 @@
+//! Times `median_filter` against the textbook median that sorts every pixel's window.
+//!
+//! Run with `cargo run --release --example median_bench [size]`. Both versions split rows across the
+//! global Rayon pool, so the numbers compare the algorithms rather than the threading.
+
+use image::{Rgb, RgbImage};
+use kernel_rs::{add_gaussian_noise, generate_test_image, median_filter, TestPattern, DEFAULT_SEED};
+use rayon::prelude::*;
+use std::env;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 3;
+
+fn main() {
+    let size = env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(512);
+    let image = add_gaussian_noise(&generate_test_image(size, size, TestPattern::Gradient), 40.0, DEFAULT_SEED);
+    println!("{}x{} noisy gradient, best of {} runs", size, size, RUNS);
+    println!("{:>6} {:>12} {:>12} {:>8}", "radius", "histogram", "sort", "speedup");
+
+    for radius in [1, 2, 3, 5, 8] {
+        let (histogram, fast) = best_of(|| median_filter(&image, radius));
+        let (sort, slow) = best_of(|| sorted_median(&image, radius));
+        assert_eq!(fast, slow, "radius {} outputs differ", radius);
+        println!(
+            "{:>6} {:>10.1}ms {:>10.1}ms {:>7.1}x",
+            radius,
+            histogram.as_secs_f64() * 1000.0,
+            sort.as_secs_f64() * 1000.0,
+            sort.as_secs_f64() / histogram.as_secs_f64()
+        );
+    }
+}
+
+/// Runs `filter` `RUNS` times and returns the fastest time with its output.
+fn best_of(filter: impl Fn() -> RgbImage) -> (Duration, RgbImage) {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let output = filter();
+            (start.elapsed(), output)
+        })
+        .min_by_key(|(elapsed, _)| *elapsed)
+        .expect("at least one run")
+}
+
+/// The textbook median: collect and sort the clamped window of every pixel and channel.
+fn sorted_median(image: &RgbImage, radius: usize) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let radius = radius as i32;
+    let rows: Vec<Vec<Rgb<u8>>> = (0..height)
+        .into_par_iter()
+        .map(|y| {
+            let mut window = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+            (0..width)
+                .map(|x| {
+                    let mut pixel = [0u8; 3];
+                    for (channel, value) in pixel.iter_mut().enumerate() {
+                        window.clear();
+                        for dy in -radius..=radius {
+                            for dx in -radius..=radius {
+                                let sx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                                let sy = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                                window.push(image.get_pixel(sx, sy)[channel]);
+                            }
+                        }
+                        window.sort_unstable();
+                        *value = window[window.len() / 2];
+                    }
+                    Rgb(pixel)
+                })
+                .collect()
+        })
+        .collect();
+    RgbImage::from_fn(width, height, |x, y| rows[y as usize][x as usize])
+}
@@ -0,0 +1,100 @@
+use std::io::Cursor;
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::{AnimationDecoder, Frame, RgbaImage};
+use rayon::prelude::*;
+
+use crate::error::Error;
+
+/// Whether `path` names a GIF, the one format that is read and written frame by frame.
+pub fn is_gif_path(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+}
+
+/// Decodes every frame of the GIF in `bytes`. Each frame is already composited onto the full canvas
+/// (earlier frames show through transparent parts as the file intends), so it can be filtered on its own.
+pub fn decode_gif_frames(bytes: &[u8]) -> Result<Vec<Frame>, Error> {
+    Ok(GifDecoder::new(Cursor::new(bytes))?.into_frames().collect_frames()?)
+}
+
+/// How often the GIF in `bytes` repeats, read from its NETSCAPE2.0 looping extension (0 there means forever),
+/// or `None` if it has no such extension and plays once. `GifDecoder` doesn't report this, so the header is scanned.
+pub fn gif_repeat(bytes: &[u8]) -> Option<Repeat> {
+    // Extension introducer, application label, block size 11, the identifier, then the sub-block [3, 1, lo, hi]
+    const LOOP_EXTENSION: &[u8] = b"\x21\xff\x0bNETSCAPE2.0\x03\x01";
+    let start = bytes.windows(LOOP_EXTENSION.len()).position(|window| window == LOOP_EXTENSION)? + LOOP_EXTENSION.len();
+    match bytes.get(start..start + 2)? {
+        [0, 0] => Some(Repeat::Infinite),
+        &[low, high] => Some(Repeat::Finite(u16::from_le_bytes([low, high]))),
+        _ => None,
+    }
+}
+
+/// Runs `process` on every frame of the GIF in `bytes`, in parallel, and encodes the results as a new GIF
+/// with the same frame delays and loop count. Returns the encoded file, or the first error of any frame.
+pub fn filter_gif_frames<F>(bytes: &[u8], process: F) -> Result<Vec<u8>, Error>
+where
+    F: Fn(RgbaImage) -> Result<RgbaImage, Error> + Sync,
+{
+    let frames = decode_gif_frames(bytes)?;
+    let frames = frames
+        .into_par_iter()
+        .map(|frame| {
+            let (left, top, delay) = (frame.left(), frame.top(), frame.delay());
+            Ok(Frame::from_parts(process(frame.into_buffer())?, left, top, delay))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut encoded = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut encoded);
+        if let Some(repeat) = gif_repeat(bytes) {
+            encoder.set_repeat(repeat)?;
+        }
+        encoder.encode_frames(frames)?;
+    }
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Delay, Rgba};
+
+    /// A 3-frame 6x4 GIF of solid frames, shown for 100, 200 and 50 ms and repeated 3 times.
+    fn three_frame_gif() -> Vec<u8> {
+        let frames = [([200, 0, 0], 100), ([0, 200, 0], 200), ([0, 0, 200], 50)].map(|([r, g, b], millis)| {
+            Frame::from_parts(RgbaImage::from_pixel(6, 4, Rgba([r, g, b, 255])), 0, 0, Delay::from_numer_denom_ms(millis, 1))
+        });
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut bytes);
+            encoder.set_repeat(Repeat::Finite(3)).unwrap();
+            encoder.encode_frames(frames).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn filtered_gif_keeps_its_frames_delays_and_loop_count() {
+        let original = three_frame_gif();
+        let filtered = filter_gif_frames(&original, |mut frame| {
+            frame.pixels_mut().for_each(|pixel| pixel.0 = [255 - pixel[0], 255 - pixel[1], 255 - pixel[2], pixel[3]]);
+            Ok(frame)
+        })
+        .unwrap();
+
+        let (before, after) = (decode_gif_frames(&original).unwrap(), decode_gif_frames(&filtered).unwrap());
+        assert_eq!(after.len(), 3);
+        let delays = |frames: &[Frame]| frames.iter().map(|frame| frame.delay().numer_denom_ms()).collect::<Vec<_>>();
+        assert_eq!(delays(&after), delays(&before));
+        assert!(matches!(gif_repeat(&filtered), Some(Repeat::Finite(3))));
+
+        // Every frame was processed: the red frame is now cyan
+        let first = after[0].buffer().get_pixel(0, 0);
+        assert!(first[0] < 100 && first[1] > 150 && first[2] > 150, "{:?}", first);
+    }
+}
@@ -0,0 +1,405 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::color::{from_linear, linear_to_srgb, luminance, rgb_to_ycbcr, srgb_to_linear, to_linear, ycbcr_to_rgb};
+use crate::convolution::{convolve_float, to_f32, to_u8, BorderMode, RgbImageF32};
+use crate::kernel::{clamp_kernel_size, generate_gaussian_kernel};
+
+/// Box blur over a `(2 * radius + 1)` square window in constant time per pixel using a summed-area table.
+/// Edges are clamped like `apply_convolution`, so the result matches the naive box blur to within rounding (±1).
+pub fn box_blur_sat(image: &RgbImage, radius: usize) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let table = SummedAreaTable::new(image, radius);
+    let window = (2 * radius + 1) as u64;
+    let area = window * window;
+
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                // The window of output (x, y) starts at padded (x, y) and spans `window` pixels each way
+                let sums = table.window_sum(x as usize, y as usize, window as usize);
+                for (channel, sum) in pixel.0.iter_mut().zip(sums) {
+                    // Integer division rounded to nearest
+                    *channel = ((sum + area / 2) / area) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+/// Approximates a Gaussian blur with three successive box blurs (Kovesi's method), each in constant time per pixel.
+/// Much cheaper than a large Gaussian kernel and visually very close. A non-positive sigma returns the image unchanged.
+pub fn fast_gaussian(image: &RgbImage, sigma: f32) -> RgbImage {
+    if sigma <= 0.0 {
+        return image.clone();
+    }
+
+    box_sizes_for_gaussian(sigma, 3)
+        .into_iter()
+        .fold(image.clone(), |blurred, size| box_blur_sat(&blurred, size / 2))
+}
+
+/// Odd box widths whose repeated application matches a Gaussian of the given sigma.
+/// Picks the two odd widths around the ideal one and how many passes of each keep the variance exact.
+fn box_sizes_for_gaussian(sigma: f32, passes: usize) -> Vec<usize> {
+    let n = passes as f32;
+    let ideal_width = (12.0 * sigma * sigma / n + 1.0).sqrt();
+
+    let mut lower = ideal_width.floor() as usize;
+    if lower.is_multiple_of(2) {
+        lower = lower.saturating_sub(1).max(1);
+    }
+    let upper = lower + 2;
+
+    // Number of passes that use the lower width
+    let wl = lower as f32;
+    let ideal_lower_passes = (12.0 * sigma * sigma - n * wl * wl - 4.0 * n * wl - 3.0 * n) / (-4.0 * wl - 4.0);
+    let lower_passes = ideal_lower_passes.round().clamp(0.0, n) as usize;
+
+    (0..passes).map(|pass| if pass < lower_passes { lower } else { upper }).collect()
+}
+
+/// Painterly edge-preserving smoothing: the `(radius + 1)` square quadrants above-left, above-right, below-left and
+/// below-right of each pixel (all sharing the pixel itself) are compared by the variance of their luminance, and the
+/// pixel becomes the mean color of the calmest one. Near an edge that quadrant lies on one side of it, so edges stay
+/// crisp while regions flatten into brush-like patches. Constant time per pixel using summed-area tables.
+/// A radius of 0 returns the image unchanged.
+pub fn kuwahara_filter(image: &RgbImage, radius: usize) -> RgbImage {
+    if radius == 0 {
+        return image.clone();
+    }
+
+    let (width, height) = image.dimensions();
+    let colors = SummedAreaTable::new(image, radius);
+    // Whole-number luminance keeps the squares exact in u64
+    let luma = SummedAreaTable::from_fn(width, height, radius, |x, y| {
+        let value = luminance(image.get_pixel(x, y)).round() as u64;
+        [value, value * value]
+    });
+
+    let size = radius + 1;
+    let area = (size * size) as u64;
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                // Padded (x, y) is `radius` pixels above and left of the pixel, so that is where the top-left quadrant starts
+                let (x, y) = (x as usize, y as usize);
+                let quadrants = [(x, y), (x + radius, y), (x, y + radius), (x + radius, y + radius)];
+
+                // Variance times area², which compares the same as the variance without any division
+                let spread = |&(qx, qy): &(usize, usize)| {
+                    let [sum, squares] = luma.window_sum(qx, qy, size);
+                    area * squares - sum * sum
+                };
+                let &(qx, qy) = quadrants.iter().min_by_key(|quadrant| spread(quadrant)).expect("There are four quadrants");
+
+                let sums = colors.window_sum(qx, qy, size);
+                for (channel, sum) in pixel.0.iter_mut().zip(sums) {
+                    *channel = ((sum + area / 2) / area) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+/// Per-channel integral image of the input padded by `radius` clamped pixels on every side.
+/// Sums are u64 so even a 4K image of white pixels cannot overflow.
+pub(crate) struct SummedAreaTable<const N: usize = 3> {
+    /// Row stride of `sums`, i.e. padded width + 1 (the leading zero column).
+    stride: usize,
+    /// `sums[(y * stride + x)]` holds the totals of all padded pixels above and left of (x, y).
+    sums: Vec<[u64; N]>,
+}
+
+impl SummedAreaTable {
+    fn new(image: &RgbImage, radius: usize) -> Self {
+        let (width, height) = image.dimensions();
+        SummedAreaTable::from_fn(width, height, radius, |x, y| image.get_pixel(x, y).0.map(u64::from))
+    }
+}
+
+impl<const N: usize> SummedAreaTable<N> {
+    /// Table over the `width x height` values `value(x, y)`, e.g. channels or derived quantities such as squares.
+    pub(crate) fn from_fn(width: u32, height: u32, radius: usize, value: impl Fn(u32, u32) -> [u64; N] + Sync) -> Self {
+        let padded_width = width as usize + 2 * radius;
+        let padded_height = height as usize + 2 * radius;
+        let stride = padded_width + 1;
+
+        // Row 0 and column 0 stay zero so window sums need no bounds checks
+        let mut sums = vec![[0u64; N]; stride * (padded_height + 1)];
+
+        // Prefix-sum each padded row independently in parallel
+        sums.par_chunks_mut(stride)
+            .enumerate()
+            .skip(1)
+            .for_each(|(row_index, row)| {
+                let y = (row_index - 1) as i64 - radius as i64;
+                let sy = y.clamp(0, height as i64 - 1) as u32;
+                let mut running = [0u64; N];
+                for (px, cell) in row.iter_mut().enumerate().skip(1) {
+                    let x = (px - 1) as i64 - radius as i64;
+                    let sx = x.clamp(0, width as i64 - 1) as u32;
+                    for (total, channel) in running.iter_mut().zip(value(sx, sy)) {
+                        *total += channel;
+                    }
+                    *cell = running;
+                }
+            });
+
+        // Accumulate down the columns, one row after another
+        for row_index in 1..=padded_height {
+            let (above, current) = sums.split_at_mut(row_index * stride);
+            let above = &above[(row_index - 1) * stride..];
+            for (cell, above) in current[..stride].iter_mut().zip(above) {
+                for (total, above) in cell.iter_mut().zip(above) {
+                    *total += above;
+                }
+            }
+        }
+
+        SummedAreaTable { stride, sums }
+    }
+
+    /// Sum of the `size x size` padded window whose top-left corner is padded (x, y).
+    pub(crate) fn window_sum(&self, x: usize, y: usize, size: usize) -> [u64; N] {
+        let at = |x: usize, y: usize| self.sums[y * self.stride + x];
+        let (bottom_right, top_right) = (at(x + size, y + size), at(x + size, y));
+        let (bottom_left, top_left) = (at(x, y + size), at(x, y));
+
+        std::array::from_fn(|channel| {
+            bottom_right[channel] + top_left[channel] - top_right[channel] - bottom_left[channel]
+        })
+    }
+}
+
+/// Edge-preserving blur: each neighbor is weighted by its spatial distance (Gaussian of `spatial_sigma`)
+/// and by how close its color is to the center pixel (Gaussian of `range_sigma`, on the 0-255 scale).
+/// Flat regions are smoothed while strong edges stay sharp. Significantly slower than a Gaussian blur,
+/// since the weights depend on the pixel values and cannot be separated or precomputed.
+/// A non-positive sigma returns the image unchanged.
+pub fn bilateral_filter(image: &RgbImage, spatial_sigma: f32, range_sigma: f32) -> RgbImage {
+    if spatial_sigma <= 0.0 || range_sigma <= 0.0 {
+        return image.clone();
+    }
+
+    let (width, height) = image.dimensions();
+    let radius = (3.0 * spatial_sigma).ceil() as i32;
+    let spatial_denominator = 2.0 * spatial_sigma * spatial_sigma;
+    let range_denominator = 2.0 * range_sigma * range_sigma;
+
+    // The spatial weights are the same for every pixel, so compute them once
+    let spatial_weights: Vec<f32> = (-radius..=radius)
+        .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| (-((dx * dx + dy * dy) as f32) / spatial_denominator).exp())
+        .collect();
+
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let center = image.get_pixel(x, y).0.map(f32::from);
+                let mut sums = [0.0f32; 3];
+                let mut total_weight = 0.0;
+
+                let offsets = (-radius..=radius).flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)));
+                for ((dx, dy), spatial_weight) in offsets.zip(&spatial_weights) {
+                    let neighbor = BorderMode::Clamp.sample(image, x as i32 + dx, y as i32 + dy).0.map(f32::from);
+
+                    // Squared color distance between the neighbor and the center pixel
+                    let distance: f32 = neighbor.iter().zip(&center).map(|(n, c)| (n - c) * (n - c)).sum();
+                    let weight = spatial_weight * (-distance / range_denominator).exp();
+
+                    for (sum, value) in sums.iter_mut().zip(neighbor) {
+                        *sum += weight * value;
+                    }
+                    total_weight += weight;
+                }
+
+                // The center pixel always has weight 1, so total_weight is never zero
+                for (channel, sum) in pixel.0.iter_mut().zip(sums) {
+                    *channel = (sum / total_weight).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+/// Blurs in linear light: decodes sRGB, convolves, then re-encodes before clamping.
+/// Averaging encoded values darkens the mix of bright and dark areas (a black/white checkerboard
+/// blurs to ~128 instead of the ~188 that emits the same light), so edges and highlights keep their brightness.
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn linear_light_blur(image: &RgbImage, kernel: &[Vec<f32>]) -> RgbImage {
+    from_linear(&convolve_float(&to_linear(image), kernel))
+}
+
+/// Same as `linear_light_blur` on the f32 working format, using the exact curves instead of lookup tables.
+/// Panics on an invalid kernel, like `linear_light_blur`.
+pub fn linear_light_blur_f32(image: &RgbImageF32, kernel: &[Vec<f32>]) -> RgbImageF32 {
+    let mut linear = image.clone();
+    linear.iter_mut().for_each(|channel| *channel = srgb_to_linear((*channel / 255.0).clamp(0.0, 1.0)) * 255.0);
+
+    let mut output = convolve_float(&linear, kernel);
+    output.iter_mut().for_each(|channel| *channel = linear_to_srgb((*channel / 255.0).max(0.0)) * 255.0);
+    output
+}
+
+/// Softens color noise the way chroma subsampling does: the chroma (YCbCr Cb and Cr) gets a Gaussian blur of
+/// `chroma_size` and the luminance only one of `luma_size` (1 leaves it alone). Detail mostly lives in luminance,
+/// so the image stays sharp while blotchy color noise and fringes are smoothed away. Sizes are odd kernel widths.
+pub fn chroma_blur(image: &RgbImage, luma_size: usize, chroma_size: usize) -> RgbImage {
+    to_u8(&chroma_blur_f32(&to_f32(image), luma_size, chroma_size))
+}
+
+/// Same as `chroma_blur` on the f32 working format.
+pub fn chroma_blur_f32(image: &RgbImageF32, luma_size: usize, chroma_size: usize) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+    let mut ycbcr = image.clone();
+    ycbcr.par_chunks_mut(3).for_each(|pixel| pixel.copy_from_slice(&rgb_to_ycbcr([pixel[0], pixel[1], pixel[2]])));
+
+    // Gaussians spanning ±3σ over the requested size, like `BlurKind::Gaussian` without a sigma
+    let blur = |size: usize| {
+        (size > 1).then(|| convolve_float(&ycbcr, &generate_gaussian_kernel(clamp_kernel_size(size, width, height), size as f32 / 6.0)))
+    };
+    let (luma, chroma) = rayon::join(|| blur(luma_size), || blur(chroma_size));
+    let (luma, chroma) = (luma.as_ref().unwrap_or(&ycbcr), chroma.as_ref().unwrap_or(&ycbcr));
+
+    let mut output = RgbImageF32::new(width, height);
+    output
+        .par_chunks_mut(3)
+        .zip(luma.par_chunks(3).zip(chroma.par_chunks(3)))
+        .for_each(|(pixel, (luma, chroma))| pixel.copy_from_slice(&ycbcr_to_rgb([luma[0], chroma[1], chroma[2]])));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convolution::apply_convolution;
+    use crate::kernel::generate_box_blur_kernel;
+    use crate::pattern::{generate_test_image, TestPattern};
+
+    /// A 30x20 image with detail in every channel: a gradient with a checkerboard laid over its blue channel.
+    fn detailed_image() -> RgbImage {
+        let checker = generate_test_image(30, 20, TestPattern::Checkerboard { cell: 2 });
+        let mut image = generate_test_image(30, 20, TestPattern::Gradient);
+        for (pixel, checker) in image.pixels_mut().zip(checker.pixels()) {
+            pixel[2] = checker[0] / 2 + 50;
+        }
+        image
+    }
+
+    #[test]
+    fn summed_area_box_blur_matches_the_naive_box_blur() {
+        let image = detailed_image();
+        for radius in [1, 2, 4] {
+            let naive = apply_convolution(&image, &generate_box_blur_kernel(2 * radius + 1), BorderMode::Clamp, 0.0);
+            let fast = box_blur_sat(&image, radius);
+            for (a, b) in fast.as_raw().iter().zip(naive.as_raw()) {
+                assert!(a.abs_diff(*b) <= 1, "radius {}: {} vs {}", radius, a, b);
+            }
+        }
+    }
+
+    /// Mean of every channel value in the image.
+    fn mean(image: &RgbImage) -> f64 {
+        image.as_raw().iter().map(|&value| value as f64).sum::<f64>() / image.as_raw().len() as f64
+    }
+
+    #[test]
+    fn fast_gaussian_keeps_the_mean_brightness() {
+        let image = detailed_image();
+        for sigma in [1.0, 2.5, 6.0] {
+            let blurred = fast_gaussian(&image, sigma);
+            assert!((mean(&blurred) - mean(&image)).abs() < 1.0, "sigma {}", sigma);
+            assert_ne!(blurred, image);
+        }
+    }
+
+    /// Mean and standard deviation of the red channel over columns `columns` of every row.
+    fn column_stats(image: &RgbImage, columns: std::ops::Range<u32>) -> (f32, f32) {
+        let values: Vec<f32> = image.enumerate_pixels().filter(|(x, _, _)| columns.contains(x)).map(|(_, _, pixel)| pixel[0] as f32).collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance = values.iter().map(|value| (value - mean) * (value - mean)).sum::<f32>() / values.len() as f32;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn bilateral_smooths_noise_but_keeps_a_step_edge() {
+        let step = RgbImage::from_fn(20, 16, |x, _| Rgb([if x < 10 { 50 } else { 200 }; 3]));
+        let noisy = crate::noise::add_gaussian_noise(&step, 8.0, 3);
+        let filtered = bilateral_filter(&noisy, 2.0, 30.0);
+
+        // Noise on each side, away from the edge, is reduced
+        for side in [0..7, 13..20] {
+            let (_, before) = column_stats(&noisy, side.clone());
+            let (_, after) = column_stats(&filtered, side);
+            assert!(after < before * 0.6, "{} vs {}", after, before);
+        }
+
+        // The columns either side of the edge keep nearly the full step between them
+        let (left, _) = column_stats(&filtered, 9..10);
+        let (right, _) = column_stats(&filtered, 10..11);
+        assert!(right - left > 140.0, "{} vs {}", left, right);
+    }
+
+    #[test]
+    fn linear_light_blur_of_a_checkerboard_is_brighter() {
+        let checker = generate_test_image(16, 16, TestPattern::Checkerboard { cell: 1 });
+        let kernel = generate_gaussian_kernel(5, 1.5);
+        let naive = apply_convolution(&checker, &kernel, BorderMode::Clamp, 0.0);
+        let linear = linear_light_blur(&checker, &kernel);
+
+        // Averaging encoded values gives ~128; the same light encodes to ~188
+        assert!((mean(&naive) - 128.0).abs() < 8.0, "{}", mean(&naive));
+        assert!(mean(&linear) > 175.0, "{}", mean(&linear));
+    }
+
+    /// Variance of the Y, Cb and Cr planes of the image.
+    fn ycbcr_variances(image: &RgbImage) -> [f32; 3] {
+        let ycbcr: Vec<[f32; 3]> = image.pixels().map(|pixel| rgb_to_ycbcr(pixel.0.map(|channel| channel as f32))).collect();
+        std::array::from_fn(|plane| {
+            let mean = ycbcr.iter().map(|pixel| pixel[plane]).sum::<f32>() / ycbcr.len() as f32;
+            ycbcr.iter().map(|pixel| (pixel[plane] - mean).powi(2)).sum::<f32>() / ycbcr.len() as f32
+        })
+    }
+
+    #[test]
+    fn chroma_blur_removes_more_color_noise_than_luma_noise() {
+        let noisy = crate::noise::add_gaussian_noise(&RgbImage::from_pixel(40, 30, Rgb([128, 110, 90])), 20.0, 3);
+        let [luma_before, cb_before, cr_before] = ycbcr_variances(&noisy);
+        let [luma_after, cb_after, cr_after] = ycbcr_variances(&chroma_blur(&noisy, 3, 9));
+
+        let luma_drop = luma_after / luma_before;
+        for (plane, drop) in [("Cb", cb_after / cb_before), ("Cr", cr_after / cr_before)] {
+            assert!(drop < luma_drop / 2.0, "{} kept {:.2} of its variance, luma {:.2}", plane, drop, luma_drop);
+        }
+    }
+
+    #[test]
+    fn kuwahara_keeps_a_step_edge_crisp() {
+        // Two lightly textured halves meeting at column 8
+        let image = RgbImage::from_fn(16, 10, |x, y| Rgb([if x < 8 { 40 } else { 200 } + ((x * 3 + y) % 4) as u8; 3]));
+        let painted = kuwahara_filter(&image, 2);
+
+        for (x, y, pixel) in painted.enumerate_pixels() {
+            let side = if x < 8 { 40..=43 } else { 200..=203 };
+            assert!(side.contains(&pixel[0]), "pixel ({}, {}) is {}", x, y, pixel[0]);
+        }
+        // A box blur of the same reach smears the edge into in-between values
+        let blurred = apply_convolution(&image, &generate_box_blur_kernel(5), BorderMode::Clamp, 0.0);
+        assert!((50..190).contains(&blurred.get_pixel(8, 5)[0]));
+        assert_eq!(kuwahara_filter(&image, 0), image);
+    }
+}
@@ -0,0 +1,861 @@
+use std::str::FromStr;
+
+use kernel_rs::{ensure_odd_size, kernel_size_for_sigma, unsharp_amount, warn, BlurKind, ChannelMask, ColorSpace, EdgeOperator, Filter, Flip, Noise, Pipeline, ResampleMode, Rotation, Schedule, TestPattern, DEFAULT_CHECKER_CELL, MAX_UNSHARP_PERCENT};
+
+use crate::job::Resize;
+use crate::preset::{Config, CONFIG_FILE};
+
+const USAGE_HEADER: &str = "\
+Usage: kernel-rs [--input <file>] [--output <file>] --filter <name> [options]
+       kernel-rs [--input <file>] [--output <file>] --pipeline <spec>
+       kernel-rs [--input <file>] [--output <file>] --preset <name>
+       kernel-rs [--input <file>] [--output <file>] --kernel <file> [--normalize]
+       kernel-rs [--input <file>] [--output <dir>] --split-channels
+       kernel-rs --merge-channels <red> <green> <blue> [--output <file>]
+       kernel-rs --generate <pattern>[:WxH] [--output <file> | --stdout]
+       kernel-rs --compare <a> <b>
+       kernel-rs --batch (--filter <name> [options] | --pipeline <spec> | --preset <name> | --kernel <file>)
+       kernel-rs                (interactive menu)
+
+";
+
+const USAGE_FOOTER: &str = "
+Pipelines:
+  --pipeline \"blur:5,sharpen,grayscale\" chains filters in one run. Each stage is name[:param[:param]]
+  with parameters in the order listed above, e.g. gaussian:7:1.5, sharpen:0.5, motion:9:45, unsharp:1.0:1.5,
+  bilateral:3:25, edges:prewitt:gray, equalize:channels. Blurs take a trailing :linear, e.g. gaussian:7:linear,
+  and sharpen a trailing :conservative, e.g. sharpen:2:conservative. Unsharp takes sigma:factor
+  (unsharp:1.0:1.5 is --sigma 1 --amount 150), and guided radius:epsilon:factor (guided:2:0.01:1.5).
+
+Presets:
+  --preset portrait runs the pipeline saved under that name in kernel-rs.json (in the current directory):
+  { \"presets\": { \"portrait\": [\"bilateral:3:20\", \"sharpen:0.3\"] } }. Each stage is a pipeline stage spec.
+
+Custom kernels:
+  --kernel kernel.txt applies a kernel read from a text file: one row per line, values separated by
+  spaces or commas, odd width and height. Lines starting with # are ignored. Add --normalize to scale
+  the weights so they sum to 1 (kernels summing to 0, like edge detectors, are left alone).
+  --show-kernel saves the kernel of --kernel or of a convolution filter (blur, gaussian, sharpen, motion,
+  laplacian, emboss, unsharp, dog) as a grayscale picture, smallest weight black and largest white,
+  instead of filtering, e.g. --filter gaussian --sigma 2 --show-kernel writes images/gaussian_13_kernel.png.
+
+Channels:
+  --split-channels writes <stem>_r, <stem>_g and <stem>_b: gray images of each color channel.
+  --merge-channels cat_r.png cat_g.png cat_b.png recombines them (default output images/cat_merged.png);
+  all three must have the same size.
+
+Test images:
+  --generate checkerboard:512x512 writes a synthetic image (default images/checkerboard_512x512.png) to try
+  filters on: solid[:WxH[:r,g,b]], checkerboard[:WxH[:square size]], gradient[:WxH] or impulse[:WxH]
+  (one white pixel at the center, so a filter's output shows its kernel). The size defaults to 512x512.
+
+Comparing:
+  --compare cat.jpg images/cat_blurred_5.jpg prints the PSNR (in dB; inf when identical) and the mean SSIM
+  (1 when identical) of two images of the same size, e.g. to measure how much a filter changed an image.
+
+Options:
+  --input <file>    Image to process, or - to read it from stdin (defaults to the first image in images/)
+                    A quoted pattern such as \"photos/*.jpg\" processes every match like --batch
+  --output <path>   Where to save the result (defaults to images/<stem>_<filter>.<input extension>); missing folders
+                    are created. A directory (existing, or ending in /) keeps the default file name inside it
+  --output-suffix <template>
+                    Name derived outputs from a template of {stem}, {filter}, {param} and {ext} (with its dot),
+                    e.g. \"{stem}-{filter}{ext}\" names cat.png blurred with strength 5 cat-blurred.png
+  --in-place        Replace the input file with the result (written to a temporary file first, so a failure
+                    leaves the original intact); also works with --batch and wildcard inputs
+  --crop <x,y,w,h>  Only process this region (the output is the cropped region)
+  --flip <h|v>      Mirror the image horizontally or vertically before filtering (after --crop)
+  --rotate <deg>    Turn the image 90, 180 or 270 degrees clockwise before filtering (after --flip)
+  --pixelate <n>    Add an n-pixel mosaic after the filter, or on its own without --filter
+  --vignette <s>    Add a vignette of this strength after the filter, or on its own without --filter
+  --posterize <n>   Add an n-level posterize after the filter, or on its own
+  --gamma <g>       Add a gamma curve (e.g. 2.2 brightens midtones) after the filter, or on its own
+  --add-noise <n>   Add noise before filtering: gaussian[:sigma] (default 10) or salt[:probability] (default 0.05)
+  --seed <n>        Seed for randomized steps (--add-noise); the same seed, input and filter give identical output (default fixed)
+  --resize <WxH>    Resize the result, e.g. 320x240; 320x or x240 keeps the aspect ratio
+  --resample <mode> Resampling for --resize: nearest, bilinear or box (area average, default)
+  --max-dimension <n>
+                    Downscale images with a side longer than n pixels to fit before filtering, with a warning
+  --channels <rgb>  Only change these color channels, e.g. rb (the others are copied from the input)
+  --border-color <r,g,b>
+                    Read pixels past the image edges as this color (e.g. the background) instead of repeating the edge
+  --soft-clip       Roll results off smoothly near 0 and 255 instead of cutting them off, so strong sharpening
+                    keeps some highlight and shadow detail (255 itself comes out around 247)
+  --iterations <n>  Apply the filter, pipeline or kernel n times, each pass on the previous result (default 1);
+                    outputs are named e.g. cat_blurred_3_x4.jpg
+  --luma-only       Only keep changes to brightness (YCbCr luminance); colors come from the input
+  --mask <file>     Grayscale image choosing where the result applies: white fully, black keeps the input,
+                    grays blend (stretched to the image size if it differs)
+  --color-space <s> Run the filters in rgb (default) or lab (CIE L*a*b*, D65), where blurs mix colors
+                    perceptually; outputs are named e.g. cat_blurred_5_lab.jpg
+  --quality <1-100> JPEG quality (default 90; other formats ignore it)
+  --format <ext>    Encoder to write, whatever the --output extension: jpg, jpeg, png, bmp or tiff
+                    (also the extension of default output names; JPEG refuses images with transparency)
+  --stdout          Write the result to stdout as PNG (or --format) instead of saving a file, e.g. for '| other-tool'
+  --preview         Also print the result to the terminal as colored blocks (plain ASCII art when piped or NO_COLOR is set)
+  --preview-width <n>
+                    Columns for --preview (default 80)
+  --time            Print how long the filtering took (excluding load/save), MP/s, kernel size and threads
+  --quiet           Don't draw progress bars (they only appear when stderr is a terminal)
+  -v, --verbose     Log image sizes, kernels, thread counts and per-stage timings on stderr (same as KERNEL_RS_LOG=debug)
+  --threads <n>     Worker threads (1 = single-threaded, 0 = all cores; default $KERNEL_RS_THREADS or all cores)
+  --single-thread   Run on one thread with sequential row loops, for debugging and reproducible benchmarks
+  --tile-height <n> Rows per strip for blurs, bounding memory on huge images (default 256; 0 = whole image)
+  --schedule <mode> How the 2D convolution splits work between threads: rows, tiles (pixel runs that keep
+                    many cores busy on small images) or auto (tiles below 512x512, the default)
+  --batch           Process every .jpg/.jpeg/.png/.bmp/.tif/.tiff/.webp in images/ with the default output names
+                    (files that look like earlier outputs, e.g. cat_blurred_5.jpg, are not used as inputs)
+  --recursive       Also look for images in subfolders of images/; outputs go to the matching subfolder
+  --skip-existing   With --batch, leave out inputs whose output file already exists
+  --resume          With --batch, skip the inputs an interrupted run of the same job already finished
+                    (batch runs record finished files in kernel-rs-batch.json)
+  --dry-run         Print the steps, inputs and output paths without reading or writing any image
+  --list-filters    Print the filters with their options and exit
+  -h, --help        Show this message
+";
+
+/// A filter selectable with `--filter`, for the help text and `--list-filters`.
+pub struct FilterInfo {
+    pub name: &'static str,
+    /// One-line summary, e.g. "Box blur".
+    pub description: &'static str,
+    /// Options with their defaults, as shown in the help text.
+    pub options: &'static str,
+    /// Flags that configure this filter; any other filter option is rejected.
+    pub flags: &'static [&'static str],
+}
+
+/// Every filter selectable with `--filter`. The help text, `--list-filters` and the check for options
+/// that don't apply to the chosen filter all read this, so adding a filter here is enough to document it.
+pub const FILTERS: &[FilterInfo] = &[
+    FilterInfo { name: "blur", description: "Box blur", options: "--strength <size> (default 5), --linear (blur in linear light)", flags: &["--strength", "--linear"] },
+    FilterInfo { name: "gaussian", description: "Gaussian blur", options: "--strength <size> (default 5) or --sigma <s> (sizes the kernel to ±3σ), --linear", flags: &["--strength", "--sigma", "--linear"] },
+    FilterInfo { name: "sharpen", description: "Sharpen", options: "--strength <s> (default 1.0; 0 leaves the image unchanged), --conservative (no halos)", flags: &["--strength", "--conservative"] },
+    FilterInfo { name: "motion", description: "Motion blur", options: "--strength <length> (default 9), --angle <degrees> (default 0)", flags: &["--strength", "--angle"] },
+    FilterInfo { name: "edges", description: "Gradient edges", options: "--edge sobel|prewitt|scharr|laplacian (default sobel), --gray (luminance only; faster, grayscale)", flags: &["--edge", "--gray"] },
+    FilterInfo { name: "laplacian", description: "Laplacian edges", options: "", flags: &[] },
+    FilterInfo { name: "dog", description: "Band-pass edges", options: "--dog <s1,s2> (Difference of Gaussians sigmas, default 1.0,2.0; mid-gray is no response)", flags: &["--dog"] },
+    FilterInfo { name: "stddev", description: "Texture map", options: "--radius <r> (window radius, default 2; flat areas are black, busy ones bright)", flags: &["--radius"] },
+    FilterInfo { name: "emboss", description: "Emboss", options: "", flags: &[] },
+    FilterInfo { name: "grayscale", description: "Grayscale (luminance)", options: "", flags: &[] },
+    FilterInfo { name: "saturate", description: "HSV saturation", options: "--saturation <factor> (default 1.5; 0 is grayscale)", flags: &["--saturation"] },
+    FilterInfo { name: "equalize", description: "Histogram equalize", options: "--per-channel (equalize R, G and B separately; may shift colors)", flags: &["--per-channel"] },
+    FilterInfo { name: "invert", description: "Color negative", options: "", flags: &[] },
+    FilterInfo { name: "sepia", description: "Sepia tone", options: "", flags: &[] },
+    FilterInfo { name: "pixelate", description: "Mosaic blocks", options: "--strength <block size> (default 16)", flags: &["--strength"] },
+    FilterInfo { name: "vignette", description: "Darken the corners", options: "--strength <s> (default 0.5; 0 is none, 1 is black corners)", flags: &["--strength"] },
+    FilterInfo { name: "posterize", description: "Poster color bands", options: "--strength <levels> (default 4 per channel, up to 256)", flags: &["--strength"] },
+    FilterInfo { name: "dither", description: "Error-diffusion dither", options: "--strength <levels> (default 2 per channel, up to 256)", flags: &["--strength"] },
+    FilterInfo { name: "cartoon", description: "Cartoon look", options: "--strength <levels> (default 8 per channel), --level <edge threshold 0-255> (default 64; lower draws more lines)", flags: &["--strength", "--level"] },
+    FilterInfo { name: "gamma", description: "Gamma tone curve", options: "--strength <gamma> (default 2.2; above 1 brightens midtones)", flags: &["--strength"] },
+    FilterInfo { name: "unsharp", description: "Unsharp mask", options: "--radius <px> or --sigma <s> (default 1), --amount <percent> (default 100)", flags: &["--sigma", "--radius", "--amount"] },
+    FilterInfo { name: "guided", description: "Halo-free sharpen", options: "--radius <r> (default 2), --epsilon <e> (edge threshold 0-1, default 0.01), --amount <percent> (default 100)", flags: &["--radius", "--epsilon", "--amount"] },
+    FilterInfo { name: "chroma", description: "Chroma denoise", options: "--chroma-blur <luma,chroma> (blur sizes for brightness and color, default 1,9; 1 is no blur)", flags: &["--chroma-blur"] },
+    FilterInfo { name: "median", description: "Median denoise", options: "--radius <r> (default 1)", flags: &["--radius"] },
+    FilterInfo { name: "dilate", description: "Dilate (local max)", options: "--radius <r> (default 1)", flags: &["--radius"] },
+    FilterInfo { name: "erode", description: "Erode (local min)", options: "--radius <r> (default 1)", flags: &["--radius"] },
+    FilterInfo { name: "boxmax", description: "Box maximum (same as dilate)", options: "--radius <r> (default 1)", flags: &["--radius"] },
+    FilterInfo { name: "boxmin", description: "Box minimum (same as erode)", options: "--radius <r> (default 1)", flags: &["--radius"] },
+    FilterInfo { name: "boxrange", description: "Box max minus min (edges)", options: "--radius <r> (default 1)", flags: &["--radius"] },
+    FilterInfo { name: "threshold", description: "Black and white", options: "--level <0-255> (default 128)", flags: &["--level"] },
+    FilterInfo { name: "adaptive", description: "Adaptive threshold", options: "--strength <block size> (default 15), --offset <c> (default 5.0)", flags: &["--strength", "--offset"] },
+    FilterInfo { name: "adjust", description: "Brightness/contrast", options: "--brightness <offset> (default 0), --contrast <factor> (default 1.0)", flags: &["--brightness", "--contrast"] },
+    FilterInfo { name: "kuwahara", description: "Painterly smooth", options: "--radius <r> (default 2; larger gives broader strokes)", flags: &["--radius"] },
+    FilterInfo { name: "bilateral", description: "Bilateral blur", options: "--sigma <s> (default 3.0), --range <r> (color sigma, default 25.0)", flags: &["--sigma", "--range"] },
+];
+
+/// The full `--help` text, with the filter list generated from `FILTERS`.
+pub fn usage() -> String {
+    format!("{}Filters:\n{}{}", USAGE_HEADER, filter_list(), USAGE_FOOTER)
+}
+
+/// One aligned line per entry of `FILTERS`: name, description and options.
+pub fn filter_list() -> String {
+    FILTERS.iter()
+        .map(|info| format!("  {:<10} {:<19} {}", info.name, info.description, info.options).trim_end().to_string() + "\n")
+        .collect()
+}
+
+/// Placeholders an `--output-suffix` template may use.
+const OUTPUT_PLACEHOLDERS: &[&str] = &["stem", "filter", "param", "ext"];
+
+/// Options collected from the command line.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub input: Option<String>,
+    pub output: Option<String>,
+    pub filter: Option<String>,
+    pub format: Option<String>,
+    pub output_suffix: Option<String>,
+    pub pipeline: Option<String>,
+    pub preset: Option<String>,
+    pub edge: Option<String>,
+    pub kernel: Option<String>,
+    pub channels: Option<ChannelMask>,
+    pub merge_channels: Option<[String; 3]>,
+    pub generate: Option<(TestPattern, u32, u32)>,
+    pub compare: Option<[String; 2]>,
+    pub crop: Option<(u32, u32, u32, u32)>,
+    pub flip: Option<Flip>,
+    pub rotation: Option<Rotation>,
+    pub pixelate: Option<u32>,
+    pub vignette: Option<f32>,
+    pub gamma: Option<f32>,
+    pub posterize: Option<u16>,
+    pub noise: Option<Noise>,
+    pub seed: Option<u64>,
+    pub resize: Option<(Option<u32>, Option<u32>)>,
+    pub max_dimension: Option<u32>,
+    pub resample: Option<ResampleMode>,
+    pub strength: Option<f32>,
+    pub sigma: Option<f32>,
+    pub angle: Option<f32>,
+    pub amount: Option<f32>,
+    pub radius: Option<usize>,
+    pub range: Option<f32>,
+    pub epsilon: Option<f32>,
+    pub level: Option<u8>,
+    pub offset: Option<f32>,
+    pub brightness: Option<i32>,
+    pub contrast: Option<f32>,
+    pub saturation: Option<f32>,
+    pub dog: Option<(f32, f32)>,
+    pub chroma_blur: Option<(usize, usize)>,
+    pub border_color: Option<[u8; 3]>,
+    pub color_space: Option<ColorSpace>,
+    pub threads: Option<usize>,
+    pub tile_height: Option<usize>,
+    pub schedule: Option<Schedule>,
+    pub preview_width: Option<u32>,
+    pub quality: Option<u8>,
+    pub gray: bool,
+    pub normalize: bool,
+    pub show_kernel: bool,
+    pub per_channel: bool,
+    pub linear: bool,
+    pub conservative: bool,
+    pub soft_clip: bool,
+    pub iterations: Option<usize>,
+    pub luma_only: bool,
+    pub mask: Option<String>,
+    pub split_channels: bool,
+    pub batch: bool,
+    pub skip_existing: bool,
+    pub resume: bool,
+    pub in_place: bool,
+    pub recursive: bool,
+    pub dry_run: bool,
+    pub stdout: bool,
+    pub preview: bool,
+    pub quiet: bool,
+    pub verbose: bool,
+    pub time: bool,
+    pub single_thread: bool,
+    pub list_filters: bool,
+    pub help: bool,
+}
+
+/// Parses `--flag value` style arguments (program name already stripped).
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => parsed.help = true,
+            "--list-filters" => parsed.list_filters = true,
+            "--batch" => parsed.batch = true,
+            "--split-channels" => parsed.split_channels = true,
+            "--merge-channels" => {
+                parsed.merge_channels = Some([value(&arg, args.next())?, value(&arg, args.next())?, value(&arg, args.next())?]);
+            }
+            "--compare" => parsed.compare = Some([value(&arg, args.next())?, value(&arg, args.next())?]),
+            "--generate" => parsed.generate = Some(test_pattern(&arg, args.next())?),
+            "--skip-existing" => parsed.skip_existing = true,
+            "--resume" => parsed.resume = true,
+            "--in-place" => parsed.in_place = true,
+            "--recursive" => parsed.recursive = true,
+            "--dry-run" => parsed.dry_run = true,
+            "--stdout" => parsed.stdout = true,
+            "--preview" => parsed.preview = true,
+            "--quiet" => parsed.quiet = true,
+            "-v" | "--verbose" => parsed.verbose = true,
+            "--time" => parsed.time = true,
+            "--single-thread" => parsed.single_thread = true,
+            "--gray" => parsed.gray = true,
+            "--normalize" => parsed.normalize = true,
+            "--show-kernel" => parsed.show_kernel = true,
+            "--per-channel" => parsed.per_channel = true,
+            "--linear" => parsed.linear = true,
+            "--conservative" => parsed.conservative = true,
+            "--soft-clip" => parsed.soft_clip = true,
+            "--iterations" => parsed.iterations = Some(positive(&arg, args.next())? as usize),
+            "--luma-only" => parsed.luma_only = true,
+            "--mask" => parsed.mask = Some(value(&arg, args.next())?),
+            "--threads" => parsed.threads = Some(number(&arg, args.next())?),
+            "--tile-height" => parsed.tile_height = Some(number(&arg, args.next())?),
+            "--schedule" => parsed.schedule = Some(schedule(&arg, args.next())?),
+            "--preview-width" => parsed.preview_width = Some(positive(&arg, args.next())?),
+            "--quality" => parsed.quality = Some(quality(&arg, args.next())?),
+            "--input" => parsed.input = Some(value(&arg, args.next())?),
+            "--output" => parsed.output = Some(value(&arg, args.next())?),
+            "--filter" => parsed.filter = Some(value(&arg, args.next())?),
+            "--format" => parsed.format = Some(format(&arg, args.next())?),
+            "--output-suffix" => parsed.output_suffix = Some(output_template(&arg, args.next())?),
+            "--pipeline" => parsed.pipeline = Some(value(&arg, args.next())?),
+            "--preset" => parsed.preset = Some(value(&arg, args.next())?),
+            "--edge" => parsed.edge = Some(edge(&arg, args.next())?),
+            "--kernel" => parsed.kernel = Some(value(&arg, args.next())?),
+            "--channels" => parsed.channels = Some(channels(&arg, args.next())?),
+            "--border-color" => parsed.border_color = Some(color(&arg, args.next())?),
+            "--color-space" => parsed.color_space = Some(color_space(&arg, args.next())?),
+            "--crop" => parsed.crop = Some(rectangle(&arg, args.next())?),
+            "--flip" => parsed.flip = Some(flip(&arg, args.next())?),
+            "--rotate" => parsed.rotation = Some(rotation(&arg, args.next())?),
+            "--pixelate" => parsed.pixelate = Some(positive(&arg, args.next())?),
+            "--vignette" => parsed.vignette = Some(number(&arg, args.next())?),
+            "--gamma" => parsed.gamma = Some(number(&arg, args.next())?),
+            "--posterize" => parsed.posterize = Some(number(&arg, args.next())?),
+            "--add-noise" => parsed.noise = Some(noise(&arg, args.next())?),
+            "--seed" => parsed.seed = Some(number(&arg, args.next())?),
+            "--resize" => parsed.resize = Some(dimensions(&arg, args.next())?),
+            "--max-dimension" => parsed.max_dimension = Some(positive(&arg, args.next())?),
+            "--resample" => parsed.resample = Some(resample(&arg, args.next())?),
+            "--strength" => parsed.strength = Some(number(&arg, args.next())?),
+            "--sigma" => parsed.sigma = Some(number(&arg, args.next())?),
+            "--angle" => parsed.angle = Some(number(&arg, args.next())?),
+            "--amount" => parsed.amount = Some(number(&arg, args.next())?),
+            "--radius" => parsed.radius = Some(number(&arg, args.next())?),
+            "--range" => parsed.range = Some(number(&arg, args.next())?),
+            "--epsilon" => parsed.epsilon = Some(number(&arg, args.next())?),
+            "--level" => parsed.level = Some(number(&arg, args.next())?),
+            "--offset" => parsed.offset = Some(number(&arg, args.next())?),
+            "--brightness" => parsed.brightness = Some(number(&arg, args.next())?),
+            "--contrast" => parsed.contrast = Some(number(&arg, args.next())?),
+            "--saturation" => parsed.saturation = Some(number(&arg, args.next())?),
+            "--dog" => parsed.dog = Some(sigmas(&arg, args.next())?),
+            "--chroma-blur" => parsed.chroma_blur = Some(sizes(&arg, args.next())?),
+            other => return Err(format!("Unknown argument '{}'", other)),
+        }
+    }
+
+    Ok(parsed)
+}
+
+impl Args {
+    /// The `--kernel` file to apply instead of a filter, if one was given.
+    pub fn kernel(&self) -> Result<Option<&str>, String> {
+        let Some(path) = &self.kernel else {
+            if self.normalize {
+                return Err("'--normalize' only applies to '--kernel'".to_string());
+            }
+            return Ok(None);
+        };
+
+        if self.filter.is_some() || self.pipeline.is_some() || self.preset.is_some() || self.has_filter_options() || self.has_effects() {
+            return Err("'--kernel' cannot be combined with '--filter', '--pipeline', '--preset', '--pixelate', '--vignette', '--gamma', '--posterize' or filter options".to_string());
+        }
+
+        Ok(Some(path))
+    }
+
+    /// The `--resize` target with its `--resample` mode, if a resize was requested.
+    pub fn resize(&self) -> Result<Option<Resize>, String> {
+        match (self.resize, self.resample) {
+            (Some((width, height)), mode) => Ok(Some(Resize { width, height, mode: mode.unwrap_or_default() })),
+            (None, Some(_)) => Err("'--resample' only applies to '--resize'".to_string()),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Builds the stages to run: the `--pipeline` spec, a `--preset` from `kernel-rs.json`, or the single `--filter` with its options,
+    /// followed by `--gamma`, `--posterize`, `--pixelate` and `--vignette` stages if requested (which may also be used on their own).
+    pub fn pipeline(&self) -> Result<Pipeline, String> {
+        let mut pipeline = match (&self.pipeline, &self.preset) {
+            (Some(_), Some(_)) => return Err("'--pipeline' and '--preset' are mutually exclusive".to_string()),
+            (Some(_), None) | (None, Some(_)) if self.filter.is_some() || self.has_filter_options() => {
+                return Err("'--pipeline' and '--preset' cannot be combined with '--filter' or filter options; put parameters in the spec".to_string());
+            }
+            (Some(spec), None) => spec.parse().map_err(|err| format!("Invalid pipeline: {}", err))?,
+            (None, Some(name)) => Config::load(CONFIG_FILE)?.preset(name)?.clone(),
+            (None, None) if self.filter.is_none() && self.has_effects() && !self.has_filter_options() => Pipeline::default(),
+            (None, None) => Pipeline::from(self.filter()?),
+        };
+
+        if let Some(gamma) = self.gamma {
+            pipeline.stages.push(Filter::Gamma { gamma });
+        }
+        if let Some(levels) = self.posterize {
+            pipeline.stages.push(Filter::Posterize { levels });
+        }
+        if let Some(block) = self.pixelate {
+            pipeline.stages.push(Filter::Pixelate { block });
+        }
+        if let Some(strength) = self.vignette {
+            pipeline.stages.push(Filter::Vignette { strength });
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Builds the selected filter, rejecting options that don't apply to it.
+    pub fn filter(&self) -> Result<Filter, String> {
+        // `--dog s1,s2` or `--chroma-blur luma,chroma` on its own picks the filter it configures
+        let name = match (&self.filter, self.dog, self.chroma_blur) {
+            (Some(name), ..) => name.as_str(),
+            (None, Some(_), None) => "dog",
+            (None, None, Some(_)) => "chroma",
+            (None, Some(_), Some(_)) => return Err("'--dog' and '--chroma-blur' configure different filters; pick one".to_string()),
+            (None, None, None) => return Err("Missing '--filter'".to_string()),
+        };
+        let info = FILTERS.iter()
+            .find(|info| info.name == name)
+            .ok_or_else(|| format!("Unknown filter '{}' (see '--list-filters')", name))?;
+
+        let filter = match name {
+            "blur" => Filter::Blur { size: self.size(5, "Blur size")?, kind: BlurKind::Box, linear: self.linear },
+            "gaussian" => match (self.strength, self.sigma) {
+                (Some(_), Some(_)) => {
+                    return Err("'--strength' and '--sigma' are mutually exclusive for the gaussian filter; pick the size or the sigma".to_string());
+                }
+                // Size the kernel to cover ±3σ
+                (None, Some(sigma)) => Filter::Blur {
+                    size: kernel_size_for_sigma(sigma),
+                    kind: BlurKind::Gaussian { sigma },
+                    linear: self.linear,
+                },
+                _ => Filter::Blur { size: self.size(5, "Blur size")?, kind: BlurKind::Gaussian { sigma: 0.0 }, linear: self.linear },
+            },
+            "sharpen" => Filter::Sharpen { strength: self.strength.unwrap_or(1.0), conservative: self.conservative },
+            "motion" => Filter::MotionBlur { length: self.size(9, "Motion blur length")?, angle: self.angle.unwrap_or(0.0) },
+            "edges" => match self.edge.as_deref() {
+                Some("prewitt") => Filter::Edges { operator: EdgeOperator::Prewitt, luma: self.gray },
+                Some("scharr") => Filter::Edges { operator: EdgeOperator::Scharr, luma: self.gray },
+                Some("laplacian") if self.gray => return Err("'--gray' does not apply to Laplacian edges".to_string()),
+                Some("laplacian") => Filter::Laplacian,
+                _ => Filter::Edges { operator: EdgeOperator::Sobel, luma: self.gray },
+            },
+            "laplacian" => Filter::Laplacian,
+            "dog" => {
+                let (sigma1, sigma2) = self.dog.unwrap_or((1.0, 2.0));
+                Filter::DifferenceOfGaussians { sigma1, sigma2 }
+            }
+            "emboss" => Filter::Emboss,
+            "grayscale" => Filter::Grayscale,
+            "saturate" => Filter::Saturation { factor: self.saturation.unwrap_or(1.5) },
+            "equalize" => Filter::Equalize { per_channel: self.per_channel },
+            "invert" => Filter::Invert,
+            "sepia" => Filter::Sepia,
+            "pixelate" => Filter::Pixelate { block: self.block(16)? },
+            "vignette" => Filter::Vignette { strength: self.strength.unwrap_or(0.5) },
+            "gamma" => Filter::Gamma { gamma: self.strength.unwrap_or(2.2) },
+            "posterize" => Filter::Posterize { levels: self.levels(4)? },
+            "dither" => Filter::Dither { levels: self.levels(2)? },
+            "cartoon" => Filter::Cartoon { levels: self.levels(8)?, edge_threshold: self.level.unwrap_or(64) },
+            "chroma" => {
+                let (luma_size, chroma_size) = self.chroma_blur.unwrap_or((1, 9));
+                Filter::ChromaBlur { luma_size: ensure_odd_size(luma_size, "Luma blur size"), chroma_size: ensure_odd_size(chroma_size, "Chroma blur size") }
+            }
+            "median" => Filter::Median { radius: self.radius.unwrap_or(1) },
+            "stddev" => Filter::LocalStddev { radius: self.radius.unwrap_or(2) },
+            "kuwahara" => Filter::Kuwahara { radius: self.radius.unwrap_or(2) },
+            "dilate" | "boxmax" => Filter::Dilate { radius: self.radius.unwrap_or(1) },
+            "erode" | "boxmin" => Filter::Erode { radius: self.radius.unwrap_or(1) },
+            "boxrange" => Filter::BoxRange { radius: self.radius.unwrap_or(1) },
+            "bilateral" => Filter::Bilateral { spatial_sigma: self.sigma.unwrap_or(3.0), range_sigma: self.range.unwrap_or(25.0) },
+            "threshold" => Filter::Threshold { level: self.level.unwrap_or(128) },
+            "adaptive" => Filter::AdaptiveThreshold { block_size: self.size(15, "Block size")?, c: self.offset.unwrap_or(5.0) },
+            "adjust" => Filter::BrightnessContrast { brightness: self.brightness.unwrap_or(0), contrast: self.contrast.unwrap_or(1.0) },
+            "unsharp" => self.unsharp()?,
+            "guided" => Filter::GuidedSharpen {
+                radius: self.radius.unwrap_or(2),
+                epsilon: match self.epsilon {
+                    Some(epsilon) if epsilon < 0.0 => return Err(format!("Invalid epsilon '{}': expected at least 0", epsilon)),
+                    epsilon => epsilon.unwrap_or(0.01),
+                },
+                amount: unsharp_amount(self.amount_percent()?),
+            },
+            other => unreachable!("filter '{}' is listed in FILTERS but not built here", other),
+        };
+
+        if let Some(flag) = self.filter_options().into_iter().find(|flag| !info.flags.contains(flag)) {
+            return Err(format!("'{}' does not apply to the {} filter", flag, name));
+        }
+
+        Ok(filter)
+    }
+
+    /// Whether anything that filters the image (a filter, pipeline, preset, kernel or effect flag) was given.
+    pub fn has_operation(&self) -> bool {
+        self.filter.is_some() || self.pipeline.is_some() || self.preset.is_some() || self.kernel.is_some()
+            || self.has_filter_options() || self.has_effects()
+    }
+
+    /// Whether a stage-adding flag such as `--vignette` was given.
+    fn has_effects(&self) -> bool {
+        self.pixelate.is_some() || self.vignette.is_some() || self.gamma.is_some() || self.posterize.is_some()
+    }
+
+    /// Whether any option that configures a single `--filter` was given.
+    fn has_filter_options(&self) -> bool {
+        !self.filter_options().is_empty()
+    }
+
+    /// The filter-configuring flags that were given, as matched against `FilterInfo::flags`.
+    fn filter_options(&self) -> Vec<&'static str> {
+        let given = [
+            ("--strength", self.strength.is_some()),
+            ("--sigma", self.sigma.is_some()),
+            ("--angle", self.angle.is_some()),
+            ("--amount", self.amount.is_some()),
+            ("--radius", self.radius.is_some()),
+            ("--range", self.range.is_some()),
+            ("--epsilon", self.epsilon.is_some()),
+            ("--level", self.level.is_some()),
+            ("--offset", self.offset.is_some()),
+            ("--brightness", self.brightness.is_some()),
+            ("--contrast", self.contrast.is_some()),
+            ("--saturation", self.saturation.is_some()),
+            ("--edge", self.edge.is_some()),
+            ("--per-channel", self.per_channel),
+            ("--linear", self.linear),
+            ("--conservative", self.conservative),
+            ("--gray", self.gray),
+            ("--dog", self.dog.is_some()),
+            ("--chroma-blur", self.chroma_blur.is_some()),
+        ];
+        given.into_iter().filter(|(_, given)| *given).map(|(flag, _)| flag).collect()
+    }
+
+    /// Reads `--strength` as a whole-pixel block size of at least 1, without the odd-size adjustment.
+    fn block(&self, default: u32) -> Result<u32, String> {
+        match self.strength {
+            None => Ok(default),
+            Some(strength) if strength >= 1.0 && strength.fract() == 0.0 => Ok(strength as u32),
+            Some(strength) => Err(format!("Invalid strength '{}': expected a positive whole number", strength)),
+        }
+    }
+
+    /// Reads `--strength` as a posterize or dither level count, a whole number from 1 to 256.
+    fn levels(&self, default: u16) -> Result<u16, String> {
+        match self.strength {
+            None => Ok(default),
+            Some(strength) if (1.0..=256.0).contains(&strength) && strength.fract() == 0.0 => Ok(strength as u16),
+            Some(strength) => Err(format!("Invalid strength '{}': expected a whole number of levels from 1 to 256", strength)),
+        }
+    }
+
+    /// Builds the unsharp mask from photo-editor style options: `--radius` in whole pixels (or the Gaussian `--sigma`
+    /// it stands for) and `--amount` in percent, so 100 is a factor of 1.0.
+    fn unsharp(&self) -> Result<Filter, String> {
+        let sigma = match (self.radius, self.sigma) {
+            (Some(_), Some(_)) => return Err("'--radius' and '--sigma' are mutually exclusive for the unsharp filter".to_string()),
+            (Some(radius), None) => radius as f32,
+            (None, sigma) => sigma.unwrap_or(1.0),
+        };
+        Ok(Filter::UnsharpMask { sigma, amount: unsharp_amount(self.amount_percent()?) })
+    }
+
+    /// Reads `--amount` in percent (default 100) for the unsharp and guided sharpens, capping it at `MAX_UNSHARP_PERCENT`.
+    fn amount_percent(&self) -> Result<f32, String> {
+        match self.amount {
+            None => Ok(100.0),
+            Some(amount) if amount < 0.0 => Err(format!("Invalid amount '{}': expected a percentage of at least 0", amount)),
+            Some(amount) if amount > MAX_UNSHARP_PERCENT => {
+                warn!("Amount {}% is above the {}% maximum; using {}%.", amount, MAX_UNSHARP_PERCENT, MAX_UNSHARP_PERCENT);
+                Ok(MAX_UNSHARP_PERCENT)
+            }
+            Some(amount) => Ok(amount),
+        }
+    }
+
+    /// Reads `--strength` as a kernel size, which must be a positive whole number.
+    /// Even sizes are bumped to the next odd one with a warning naming `what`.
+    fn size(&self, default: usize, what: &str) -> Result<usize, String> {
+        match self.strength {
+            None => Ok(default),
+            Some(strength) if strength >= 1.0 && strength.fract() == 0.0 => Ok(ensure_odd_size(strength as usize, what)),
+            Some(strength) => Err(format!("Invalid strength '{}': expected a positive whole number", strength)),
+        }
+    }
+}
+
+/// Takes the value following a flag.
+fn value(flag: &str, next: Option<String>) -> Result<String, String> {
+    next.ok_or_else(|| format!("Missing value for '{}'", flag))
+}
+
+/// Takes and parses the numeric value following a flag.
+fn number<T: FromStr>(flag: &str, next: Option<String>) -> Result<T, String> {
+    let raw = value(flag, next)?;
+    raw.parse().map_err(|_| format!("Invalid value '{}' for '{}'", raw, flag))
+}
+
+/// Takes the value following `--channels`: a combination of r, g and b such as `rg`.
+fn channels(flag: &str, next: Option<String>) -> Result<ChannelMask, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    let mut selected = [false; 3];
+    for letter in raw.chars() {
+        match "rgb".find(letter) {
+            Some(index) => selected[index] = true,
+            None => return Err(format!("Invalid channels '{}': expected a combination of r, g and b", raw)),
+        }
+    }
+
+    if selected == [false; 3] {
+        return Err(format!("'{}' needs at least one of r, g or b", flag));
+    }
+    Ok(ChannelMask(selected))
+}
+
+/// Takes the value following `--flip`: `h` (or `horizontal`) or `v` (or `vertical`).
+fn flip(flag: &str, next: Option<String>) -> Result<Flip, String> {
+    let raw = value(flag, next)?;
+    match raw.to_ascii_lowercase().as_str() {
+        "h" | "horizontal" => Ok(Flip::Horizontal),
+        "v" | "vertical" => Ok(Flip::Vertical),
+        _ => Err(format!("Invalid value '{}' for '{}': expected h or v", raw, flag)),
+    }
+}
+
+/// Takes the value following `--rotate`: 90, 180 or 270 (degrees clockwise).
+fn rotation(flag: &str, next: Option<String>) -> Result<Rotation, String> {
+    let raw = value(flag, next)?;
+    raw.trim().parse().ok().and_then(Rotation::from_degrees)
+        .ok_or_else(|| format!("Invalid value '{}' for '{}': expected 90, 180 or 270", raw, flag))
+}
+
+/// Takes the value following `--border-color`: three 0–255 channel values such as `255,0,0`.
+fn color(flag: &str, next: Option<String>) -> Result<[u8; 3], String> {
+    let raw = value(flag, next)?;
+    let parts: Vec<u8> = raw.split(',').map(|part| part.trim().parse()).collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid color '{}' for '{}': expected r,g,b with values from 0 to 255", raw, flag))?;
+
+    match parts[..] {
+        [r, g, b] => Ok([r, g, b]),
+        _ => Err(format!("Invalid color '{}' for '{}': expected r,g,b with values from 0 to 255", raw, flag)),
+    }
+}
+
+/// Takes the value following `--crop`: `x,y,w,h` with a non-empty width and height.
+fn rectangle(flag: &str, next: Option<String>) -> Result<(u32, u32, u32, u32), String> {
+    let raw = value(flag, next)?;
+    let parts: Vec<u32> = raw.split(',').map(|part| part.trim().parse()).collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid region '{}' for '{}': expected x,y,w,h", raw, flag))?;
+
+    match parts[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Ok((x, y, width, height)),
+        _ => Err(format!("Invalid region '{}' for '{}': expected x,y,w,h with a non-zero width and height", raw, flag)),
+    }
+}
+
+/// Takes the value following `--add-noise`: `gaussian[:sigma]` or `salt[:probability]`.
+fn noise(flag: &str, next: Option<String>) -> Result<Noise, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    let (kind, amount) = match raw.split_once(':') {
+        Some((kind, amount)) => {
+            let amount: f32 = amount.parse().map_err(|_| format!("Invalid noise amount in '{}' for '{}'", raw, flag))?;
+            (kind, Some(amount))
+        }
+        None => (raw.as_str(), None),
+    };
+
+    match kind {
+        "gaussian" => Ok(Noise::Gaussian { sigma: amount.unwrap_or(10.0).max(0.0) }),
+        "salt" => Ok(Noise::SaltPepper { probability: amount.unwrap_or(0.05).clamp(0.0, 1.0) }),
+        _ => Err(format!("Unsupported noise '{}' for '{}': expected gaussian[:sigma] or salt[:probability]", raw, flag)),
+    }
+}
+
+/// Takes the value following `--resize`: `WxH`, or `Wx` / `xH` to keep the aspect ratio.
+fn dimensions(flag: &str, next: Option<String>) -> Result<(Option<u32>, Option<u32>), String> {
+    let raw = value(flag, next)?;
+    let invalid = || format!("Invalid size '{}' for '{}': expected WxH, Wx or xH with positive whole numbers", raw, flag);
+
+    let (width, height) = raw.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let parse = |part: &str| match part {
+        "" => Ok(None),
+        part => part.parse().ok().filter(|&size: &u32| size > 0).map(Some).ok_or_else(invalid),
+    };
+
+    match (parse(width)?, parse(height)?) {
+        (None, None) => Err(invalid()),
+        size => Ok(size),
+    }
+}
+
+/// Takes the value following `--dog`: two positive Gaussian sigmas such as `1,2` or `1.0,3.5`.
+fn sigmas(flag: &str, next: Option<String>) -> Result<(f32, f32), String> {
+    let raw = value(flag, next)?;
+    let invalid = || format!("Invalid sigmas '{}' for '{}': expected s1,s2 with positive numbers, e.g. 1,2", raw, flag);
+
+    let (first, second) = raw.split_once(',').ok_or_else(invalid)?;
+    let parse = |part: &str| part.trim().parse().ok().filter(|&sigma: &f32| sigma > 0.0).ok_or_else(invalid);
+    Ok((parse(first)?, parse(second)?))
+}
+
+/// Takes the value following `--generate`: `name[:WxH[:option]]`, e.g. `checkerboard:512x512:16` or `solid:64x64:255,0,0`.
+/// The size defaults to 512x512; the option is the square size for checkerboard and the color for solid (default mid-gray).
+fn test_pattern(flag: &str, next: Option<String>) -> Result<(TestPattern, u32, u32), String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    let mut parts = raw.splitn(3, ':');
+    let name = parts.next().unwrap_or_default();
+    let (width, height) = match parts.next() {
+        Some(size) => match dimensions(flag, Some(size.to_string()))? {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err(format!("Invalid size '{}' for '{}': expected WxH, e.g. 512x512", size, flag)),
+        },
+        None => (512, 512),
+    };
+    let option = parts.next();
+
+    let pattern = match (name, option) {
+        ("solid", color_option) => TestPattern::SolidColor(match color_option {
+            Some(rgb) => color(flag, Some(rgb.to_string()))?,
+            None => [128; 3],
+        }),
+        ("checkerboard", cell) => TestPattern::Checkerboard {
+            cell: match cell {
+                Some(cell) => positive(flag, Some(cell.to_string()))?,
+                None => DEFAULT_CHECKER_CELL,
+            },
+        },
+        ("gradient", None) => TestPattern::Gradient,
+        ("impulse", None) => TestPattern::Impulse,
+        ("gradient" | "impulse", Some(option)) => return Err(format!("'{}' takes no option, got '{}' for '{}'", name, option, flag)),
+        _ => return Err(format!("Unsupported pattern '{}' for '{}': expected solid, checkerboard, gradient or impulse", name, flag)),
+    };
+    Ok((pattern, width, height))
+}
+
+/// Takes the value following `--chroma-blur`: two positive whole kernel sizes such as `1,9` or `3,15`.
+fn sizes(flag: &str, next: Option<String>) -> Result<(usize, usize), String> {
+    let raw = value(flag, next)?;
+    let invalid = || format!("Invalid sizes '{}' for '{}': expected luma,chroma with positive whole numbers, e.g. 1,9", raw, flag);
+
+    let (first, second) = raw.split_once(',').ok_or_else(invalid)?;
+    let parse = |part: &str| part.trim().parse().ok().filter(|&size: &usize| size > 0).ok_or_else(invalid);
+    Ok((parse(first)?, parse(second)?))
+}
+
+/// Takes the value following `--resample`.
+fn resample(flag: &str, next: Option<String>) -> Result<ResampleMode, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    match raw.as_str() {
+        "nearest" => Ok(ResampleMode::Nearest),
+        "bilinear" => Ok(ResampleMode::Bilinear),
+        "box" => Ok(ResampleMode::Box),
+        _ => Err(format!("Unsupported resampling '{}': expected nearest, bilinear or box", raw)),
+    }
+}
+
+/// Takes the value following `--color-space`.
+fn color_space(flag: &str, next: Option<String>) -> Result<ColorSpace, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    match raw.as_str() {
+        "rgb" | "srgb" => Ok(ColorSpace::Rgb),
+        "lab" => Ok(ColorSpace::Lab),
+        _ => Err(format!("Unsupported color space '{}': expected rgb or lab", raw)),
+    }
+}
+
+/// Takes the value following `--schedule`.
+fn schedule(flag: &str, next: Option<String>) -> Result<Schedule, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    match raw.as_str() {
+        "auto" => Ok(Schedule::Auto),
+        "rows" => Ok(Schedule::Rows),
+        "tiles" => Ok(Schedule::Tiles),
+        _ => Err(format!("Unsupported schedule '{}': expected auto, rows or tiles", raw)),
+    }
+}
+
+/// Takes the value following `--edge`: the operator used by the edges filter.
+fn edge(flag: &str, next: Option<String>) -> Result<String, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    match raw.as_str() {
+        "sobel" | "prewitt" | "scharr" | "laplacian" => Ok(raw),
+        _ => Err(format!("Unsupported edge operator '{}': expected sobel, prewitt, scharr or laplacian", raw)),
+    }
+}
+
+/// Takes a whole-number value of at least 1 following `flag`, e.g. the `--pixelate` block size.
+fn positive(flag: &str, next: Option<String>) -> Result<u32, String> {
+    match number(flag, next)? {
+        0 => Err(format!("Invalid value '0' for '{}': expected at least 1", flag)),
+        value => Ok(value),
+    }
+}
+
+/// Takes the value following `--quality`, a JPEG quality from 1 to 100.
+fn quality(flag: &str, next: Option<String>) -> Result<u8, String> {
+    match number(flag, next)? {
+        quality @ 1..=100 => Ok(quality),
+        quality => Err(format!("Invalid value '{}' for '{}': expected 1 to 100", quality, flag)),
+    }
+}
+
+/// Takes the value following `--format`, which must name an extension we can encode.
+fn format(flag: &str, next: Option<String>) -> Result<String, String> {
+    let raw = value(flag, next)?.to_ascii_lowercase();
+    match raw.as_str() {
+        "jpg" | "jpeg" | "png" | "bmp" | "tif" | "tiff" => Ok(raw),
+        "webp" => Err("WebP images can be read but not written (the encoder isn't part of this build); expected jpg, jpeg, png, bmp or tiff".to_string()),
+        _ => Err(format!("Unsupported format '{}': expected jpg, jpeg, png, bmp or tiff", raw)),
+    }
+}
+
+/// Takes the value following `--output-suffix`: a file name template whose `{...}` placeholders are all known.
+fn output_template(flag: &str, next: Option<String>) -> Result<String, String> {
+    let raw = value(flag, next)?;
+    if raw.contains(['/', '\\']) {
+        return Err(format!("Invalid template '{}' for '{}': expected a file name, not a path; use '--output <folder>/' for the folder", raw, flag));
+    }
+    let mut rest = raw.as_str();
+    while let Some(start) = rest.find('{') {
+        let Some((placeholder, after)) = rest[start + 1..].split_once('}') else {
+            return Err(format!("Invalid template '{}' for '{}': unclosed '{{'", raw, flag));
+        };
+        if !OUTPUT_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!("Unknown placeholder '{{{}}}' in '{}': expected {{stem}}, {{filter}}, {{param}} or {{ext}}", placeholder, flag));
+        }
+        rest = after;
+    }
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `args` as they would follow the program name.
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn every_selectable_filter_is_listed() {
+        let list = filter_list();
+        for info in FILTERS {
+            assert!(list.lines().any(|line| line.split_whitespace().next() == Some(info.name)), "{} is not listed", info.name);
+            let args = parse(&["--filter", info.name]).unwrap();
+            assert!(args.filter().is_ok(), "{} is listed but can't be selected", info.name);
+        }
+        assert_eq!(list.lines().count(), FILTERS.len());
+
+        assert!(parse(&["--filter", "nonsense"]).unwrap().filter().is_err());
+        assert!(usage().contains(&list));
+    }
+
+    #[test]
+    fn unsharp_amount_is_a_percentage() {
+        let filter = |args: &[&str]| parse(args).unwrap().filter().unwrap();
+        assert_eq!(filter(&["--filter", "unsharp", "--amount", "100"]), Filter::UnsharpMask { sigma: 1.0, amount: 1.0 });
+        assert_eq!(filter(&["--filter", "unsharp", "--amount", "150", "--radius", "2"]), Filter::UnsharpMask { sigma: 2.0, amount: 1.5 });
+        assert_eq!(unsharp_amount(100.0), 1.0);
+
+        // Past the maximum is capped, negative is an error
+        assert_eq!(filter(&["--filter", "unsharp", "--amount", "5000"]), Filter::UnsharpMask { sigma: 1.0, amount: MAX_UNSHARP_PERCENT / 100.0 });
+        assert!(parse(&["--filter", "unsharp", "--amount", "-5"]).unwrap().filter().is_err());
+    }
+}
@@ -0,0 +1,712 @@
+use std::sync::LazyLock;
+
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::convolution::RgbImageF32;
+use crate::error::Error;
+
+/// Perceived brightness of a pixel using the Rec. 601 weights 0.299R + 0.587G + 0.114B.
+pub fn luminance(pixel: &Rgb<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+/// Converts an image to grayscale, writing the luminance to all three channels so it stays an RGB image.
+pub fn to_grayscale(image: &RgbImage) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let luma = luminance(image.get_pixel(x, y)).round().clamp(0.0, 255.0) as u8;
+                pixel.0 = [luma; 3];
+            }
+        });
+
+    output
+}
+
+/// Extracts the luminance as a single-channel image, for filters that only need brightness.
+pub fn to_luma8(image: &RgbImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Luma<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = [luminance(image.get_pixel(x, y)).round().clamp(0.0, 255.0) as u8];
+            }
+        });
+
+    output
+}
+
+/// Copies one color channel (0 = R, 1 = G, 2 = B) into a single-channel image.
+pub fn extract_channel(image: &RgbImage, channel: usize) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Luma<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = [image.get_pixel(x, y)[channel]];
+            }
+        });
+
+    output
+}
+
+/// Splits an image into its R, G and B channels, each written to all three channels of a gray image
+/// so it can be saved in any format. `merge_channels` puts them back together.
+pub fn split_channels(image: &RgbImage) -> [RgbImage; 3] {
+    [0, 1, 2].map(|channel| DynamicImage::ImageLuma8(extract_channel(image, channel)).into_rgb8())
+}
+
+/// Builds a color image from separate R, G and B planes, e.g. from `split_channels` after `to_luma8`.
+/// Fails with `Error::InvalidArgument` if the three planes don't all have the same dimensions.
+pub fn merge_channels(red: &GrayImage, green: &GrayImage, blue: &GrayImage) -> Result<RgbImage, Error> {
+    let dimensions = red.dimensions();
+    if green.dimensions() != dimensions || blue.dimensions() != dimensions {
+        return Err(Error::InvalidArgument(format!(
+            "channel images must have the same size, but they are {}x{}, {}x{} and {}x{}",
+            red.width(), red.height(), green.width(), green.height(), blue.width(), blue.height()
+        )));
+    }
+
+    let (width, height) = dimensions;
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = [red.get_pixel(x, y)[0], green.get_pixel(x, y)[0], blue.get_pixel(x, y)[0]];
+            }
+        });
+
+    Ok(output)
+}
+
+/// Applies `out = (in - 128) * contrast + 128 + brightness` to every channel, clamped to 0-255.
+/// Contrast scales around mid-gray, so brightness 0 and contrast 1.0 leave the image unchanged.
+pub fn adjust_brightness_contrast(image: &RgbImage, brightness: i32, contrast: f32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = image.get_pixel(x, y).0.map(|channel| {
+                    ((channel as f32 - 128.0) * contrast + 128.0 + brightness as f32).round().clamp(0.0, 255.0) as u8
+                });
+            }
+        });
+
+    output
+}
+
+/// Inverts every channel (`255 - value`), giving a photographic negative. Applying it twice restores the input.
+pub fn invert(image: &RgbImage) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = image.get_pixel(x, y).0.map(|channel| 255 - channel);
+            }
+        });
+
+    output
+}
+
+/// Maps every channel value through a 256-entry lookup table, e.g. one from `gamma_lut` or `levels_lut`.
+/// An identity table (`lut[v] == v`) returns the image unchanged.
+pub fn apply_lut(image: &RgbImage, lut: &[u8; 256]) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = image.get_pixel(x, y).0.map(|channel| lut[channel as usize]);
+            }
+        });
+
+    output
+}
+
+/// Table for a gamma correction `255 * (v / 255)^(1 / gamma)`: above 1 brightens the midtones, below 1 darkens them.
+/// Black and white stay put; a non-positive gamma gives the identity.
+pub fn gamma_lut(gamma: f32) -> [u8; 256] {
+    if gamma <= 0.0 {
+        return std::array::from_fn(|value| value as u8);
+    }
+    std::array::from_fn(|value| (255.0 * (value as f32 / 255.0).powf(1.0 / gamma)).round() as u8)
+}
+
+/// Table for a levels adjustment: `black` and below map to 0, `white` and above to 255, and the range between
+/// is stretched with the midtone `gamma` (as in `gamma_lut`). `black` must be less than `white`.
+pub fn levels_lut(black: u8, white: u8, gamma: f32) -> [u8; 256] {
+    let gamma = if gamma > 0.0 { gamma } else { 1.0 };
+    let range = (white as f32 - black as f32).max(1.0);
+    std::array::from_fn(|value| {
+        let position = ((value as f32 - black as f32) / range).clamp(0.0, 1.0);
+        (255.0 * position.powf(1.0 / gamma)).round() as u8
+    })
+}
+
+/// Table for `posterize`: `levels` evenly spaced values from 0 to 255, each input rounded to the nearest.
+pub fn posterize_lut(levels: u16) -> [u8; 256] {
+    // A single level has nothing to choose between; everything becomes mid-gray
+    if levels <= 1 {
+        return [128; 256];
+    }
+    let steps = (levels.min(256) - 1) as f32;
+    std::array::from_fn(|value| ((value as f32 * steps / 255.0).round() * 255.0 / steps).round() as u8)
+}
+
+/// Reduces every channel to `levels` evenly spaced values for a banded poster look.
+/// 2 levels is a per-channel threshold at 128 and 256 leaves the image unchanged.
+pub fn posterize(image: &RgbImage, levels: u16) -> RgbImage {
+    apply_lut(image, &posterize_lut(levels))
+}
+
+/// Reduces every channel to `levels` evenly spaced values like `posterize`, but pushes each pixel's rounding error
+/// onto its unvisited neighbors with the Floyd–Steinberg weights (7/16 right, 3/16 below-left, 5/16 below, 1/16
+/// below-right), so smooth gradients become a fine mix of levels instead of hard bands.
+/// Each pixel depends on the errors of the ones before it, so rows are processed in order on one thread.
+pub fn dither_floyd_steinberg(image: &RgbImage, levels: u16) -> RgbImage {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut output = image.clone();
+    if levels <= 1 {
+        // Nothing to choose between, as in `posterize_lut`
+        output.iter_mut().for_each(|value| *value = 128);
+        return output;
+    }
+    let steps = (levels.min(256) - 1) as f32;
+    let quantize = |value: f32| ((value * steps / 255.0).round().clamp(0.0, steps) * 255.0 / steps).round();
+
+    // Errors carried into the current and the next row, with a spare pixel on each side so the
+    // diagonal neighbors of the edge pixels need no bounds checks
+    let row_len = (width + 2) * 3;
+    let mut current = vec![0.0f32; row_len];
+    let mut next = vec![0.0f32; row_len];
+
+    for row in output.chunks_exact_mut(width * 3).take(height) {
+        for x in 0..width {
+            for c in 0..3 {
+                let wanted = row[x * 3 + c] as f32 + current[(x + 1) * 3 + c];
+                let chosen = quantize(wanted);
+                row[x * 3 + c] = chosen as u8;
+
+                let error = wanted - chosen;
+                current[(x + 2) * 3 + c] += error * 7.0 / 16.0;
+                next[x * 3 + c] += error * 3.0 / 16.0;
+                next[(x + 1) * 3 + c] += error * 5.0 / 16.0;
+                next[(x + 2) * 3 + c] += error / 16.0;
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.iter_mut().for_each(|error| *error = 0.0);
+    }
+
+    output
+}
+
+/// Gives an image the warm brown tone of old photographs using the standard sepia matrix.
+/// Bright colors overflow the matrix, so each output channel is clamped to 255.
+pub fn sepia(image: &RgbImage) -> RgbImage {
+    const SEPIA: [[f32; 3]; 3] = [
+        [0.393, 0.769, 0.189],
+        [0.349, 0.686, 0.168],
+        [0.272, 0.534, 0.131],
+    ];
+
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let [r, g, b] = image.get_pixel(x, y).0.map(f32::from);
+                pixel.0 = SEPIA.map(|[wr, wg, wb]| (wr * r + wg * g + wb * b).round().min(255.0) as u8);
+            }
+        });
+
+    output
+}
+
+/// Converts a pixel to hue (degrees, 0 up to 360), saturation and value (both 0-1).
+/// Grays have no hue, so they report hue 0 and saturation 0.
+pub fn rgb_to_hsv(pixel: &Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = pixel.0.map(|channel| channel as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return [0.0, 0.0, max];
+    }
+
+    // Which sixth of the color wheel we're in depends on the dominant channel
+    let hue = if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    [hue.rem_euclid(360.0), delta / max, max]
+}
+
+/// Converts hue (degrees, wrapped into 0-360), saturation and value (clamped to 0-1) back to a pixel.
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> Rgb<u8> {
+    let [hue, saturation, value] = hsv;
+    let hue = hue.rem_euclid(360.0);
+    let saturation = saturation.clamp(0.0, 1.0);
+    let value = value.clamp(0.0, 1.0);
+
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let m = value - chroma;
+    Rgb([r, g, b].map(|channel| ((channel + m) * 255.0).round().clamp(0.0, 255.0) as u8))
+}
+
+/// Scales saturation by `factor` in HSV space, leaving hue and value alone (0 gives grayscale, >1 is more vivid).
+pub fn adjust_saturation(image: &RgbImage, factor: f32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let [hue, saturation, value] = rgb_to_hsv(image.get_pixel(x, y));
+                *pixel = hsv_to_rgb([hue, saturation * factor, value]);
+            }
+        });
+
+    output
+}
+
+/// Entries in the linear→sRGB table; fine enough that neighboring entries never skip an 8-bit level.
+const LINEAR_LUT_SIZE: usize = 4096;
+
+/// sRGB-encoded 8-bit value → linear light on the 0–255 scale, one entry per level.
+static SRGB_TO_LINEAR: LazyLock<[f32; 256]> = LazyLock::new(|| {
+    let mut lut = [0.0; 256];
+    for (value, entry) in lut.iter_mut().enumerate() {
+        *entry = srgb_to_linear(value as f32 / 255.0) * 255.0;
+    }
+    lut
+});
+
+/// Linear light (quantized to `LINEAR_LUT_SIZE` steps) → sRGB-encoded 8-bit value.
+static LINEAR_TO_SRGB: LazyLock<Vec<u8>> = LazyLock::new(|| {
+    (0..LINEAR_LUT_SIZE)
+        .map(|step| (linear_to_srgb(step as f32 / (LINEAR_LUT_SIZE - 1) as f32) * 255.0).round() as u8)
+        .collect()
+});
+
+/// The sRGB decoding curve: an encoded value in 0–1 to linear light in 0–1.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+/// The sRGB encoding curve: linear light in 0–1 to an encoded value in 0–1.
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Decodes an 8-bit sRGB image to linear light in the f32 working format (still on the 0–255 scale).
+pub fn to_linear(image: &RgbImage) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_raw(width, height, image.iter().map(|&channel| SRGB_TO_LINEAR[channel as usize]).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// Encodes a linear-light f32 image back to 8-bit sRGB, clamping out-of-range values.
+pub fn from_linear(image: &RgbImageF32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let scale = (LINEAR_LUT_SIZE - 1) as f32 / 255.0;
+    ImageBuffer::from_raw(
+        width,
+        height,
+        image.iter().map(|&channel| LINEAR_TO_SRGB[(channel * scale).round().clamp(0.0, (LINEAR_LUT_SIZE - 1) as f32) as usize]).collect(),
+    )
+    .expect("Buffer size matches the image dimensions")
+}
+
+/// Full-range BT.601 (JPEG) RGB → YCbCr on the 0–255 scale. Chroma is centered on 128.
+pub fn rgb_to_ycbcr([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b,
+        128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b,
+    ]
+}
+
+/// Inverse of `rgb_to_ycbcr`. The result is not clamped: a changed Y with the old chroma can leave the RGB gamut.
+pub fn ycbcr_to_rgb([y, cb, cr]: [f32; 3]) -> [f32; 3] {
+    [
+        y + 1.402 * (cr - 128.0),
+        y - 0.344136 * (cb - 128.0) - 0.714136 * (cr - 128.0),
+        y + 1.772 * (cb - 128.0),
+    ]
+}
+
+/// Blends `filtered` over `original` with `mask` as the per-pixel weight: white takes the filtered pixel, black
+/// keeps the original and grays mix the two, e.g. to blur only the background. All three must have the same size.
+pub fn apply_masked(original: &RgbImage, filtered: &RgbImage, mask: &GrayImage) -> RgbImage {
+    assert_eq!(original.dimensions(), filtered.dimensions(), "Original and filtered images differ in size");
+    assert_eq!(filtered.dimensions(), mask.dimensions(), "Mask and image differ in size");
+
+    let mut output = filtered.clone();
+    output
+        .par_chunks_mut(3)
+        .zip(original.par_chunks(3))
+        .zip(mask.par_iter())
+        .for_each(|((pixel, original), &weight)| {
+            for (value, &original) in pixel.iter_mut().zip(original) {
+                // Rounded integer mix, so 0 and 255 give back exactly the original and the filtered value
+                *value = ((original as u32 * (255 - weight as u32) + *value as u32 * weight as u32 + 127) / 255) as u8;
+            }
+        });
+
+    output
+}
+
+/// Combines the luminance (Y) of `processed` with the chroma (Cb, Cr) of `original`, so a filter only
+/// changes brightness detail and can't introduce color fringes. Stays in unclamped f32 like the working format.
+pub fn merge_luma_f32(original: &RgbImageF32, processed: &RgbImageF32) -> RgbImageF32 {
+    let mut output = processed.clone();
+    output
+        .par_chunks_mut(3)
+        .zip(original.par_chunks(3))
+        .for_each(|(pixel, original)| {
+            let [luma, ..] = rgb_to_ycbcr([pixel[0], pixel[1], pixel[2]]);
+            let [_, cb, cr] = rgb_to_ycbcr([original[0], original[1], original[2]]);
+            pixel.copy_from_slice(&ycbcr_to_rgb([luma, cb, cr]));
+        });
+
+    output
+}
+
+/// Color space a pipeline runs its filters in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// The image's own sRGB values. The default.
+    #[default]
+    Rgb,
+    /// CIE L*a*b* (D65): filters see lightness and two opponent color axes, spaced so equal steps look about
+    /// equally different, e.g. blurs mix colors without the dark or muddy fringes sRGB averaging can give.
+    Lab,
+}
+
+/// D65 reference white in XYZ, with Y = 1.
+const D65_WHITE: [f32; 3] = [0.95047, 1.0, 1.08883];
+
+/// Linear sRGB → XYZ (D65), one row per X, Y and Z.
+const RGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.072175],
+    [0.0193339, 0.119192, 0.9503041],
+];
+
+/// XYZ (D65) → linear sRGB, the inverse of `RGB_TO_XYZ`.
+const XYZ_TO_RGB: [[f32; 3]; 3] = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.969266, 1.8760108, 0.041556],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+/// Where the Lab companding switches from the cube root to a line, 6/29.
+const LAB_DELTA: f32 = 6.0 / 29.0;
+
+fn multiply(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+/// sRGB on the 0–255 scale → CIE L*a*b* with the D65 white point: L* from 0 (black) to 100 (white), and a*
+/// (green to red) and b* (blue to yellow) roughly -128 to 127, 0 for grays.
+pub fn rgb_to_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let linear = rgb.map(|channel| srgb_to_linear((channel / 255.0).clamp(0.0, 1.0)));
+    let xyz = multiply(&RGB_TO_XYZ, linear);
+    let [fx, fy, fz] = std::array::from_fn(|axis| {
+        let t = xyz[axis] / D65_WHITE[axis];
+        if t > LAB_DELTA.powi(3) { t.cbrt() } else { t / (3.0 * LAB_DELTA * LAB_DELTA) + 4.0 / 29.0 }
+    });
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// Inverse of `rgb_to_lab`, back to sRGB on the 0–255 scale. Colors outside the sRGB gamut are clamped.
+pub fn lab_to_rgb([lightness, a, b]: [f32; 3]) -> [f32; 3] {
+    let fy = (lightness + 16.0) / 116.0;
+    let f = [fy + a / 500.0, fy, fy - b / 200.0];
+    let xyz: [f32; 3] = std::array::from_fn(|axis| {
+        let t = f[axis];
+        let ratio = if t > LAB_DELTA { t.powi(3) } else { 3.0 * LAB_DELTA * LAB_DELTA * (t - 4.0 / 29.0) };
+        ratio * D65_WHITE[axis]
+    });
+    multiply(&XYZ_TO_RGB, xyz).map(|channel| linear_to_srgb(channel.clamp(0.0, 1.0)) * 255.0)
+}
+
+/// sRGB on the 0–255 scale → cyan, magenta, yellow and black ink amounts from 0 to 1, with as much of the
+/// shared darkness as possible moved into black (no ICC profile, so this is the naive device conversion).
+pub fn rgb_to_cmyk(rgb: [f32; 3]) -> [f32; 4] {
+    let [r, g, b] = rgb.map(|channel| (channel / 255.0).clamp(0.0, 1.0));
+    let black = 1.0 - r.max(g).max(b);
+    if black >= 1.0 {
+        return [0.0, 0.0, 0.0, 1.0];
+    }
+    let ink = |channel: f32| (1.0 - channel - black) / (1.0 - black);
+    [ink(r), ink(g), ink(b), black]
+}
+
+/// Inverse of `rgb_to_cmyk`, back to sRGB on the 0–255 scale. Ink amounts are clamped to 0–1.
+pub fn cmyk_to_rgb(cmyk: [f32; 4]) -> [f32; 3] {
+    let [c, m, y, black] = cmyk.map(|ink| ink.clamp(0.0, 1.0));
+    [c, m, y].map(|ink| 255.0 * (1.0 - ink) * (1.0 - black))
+}
+
+/// Converts an 8-bit sRGB image to Lab in the f32 working format, scaled onto the 0–255 range the filters
+/// expect: L* × 2.55 and a*, b* + 128. Use `from_lab_f32` to get back to sRGB.
+pub fn to_lab_f32(image: &RgbImage) -> RgbImageF32 {
+    let mut output = RgbImageF32::new(image.width(), image.height());
+    output
+        .par_chunks_mut(3)
+        .zip(image.par_chunks(3))
+        .for_each(|(pixel, rgb)| {
+            let [lightness, a, b] = rgb_to_lab([rgb[0] as f32, rgb[1] as f32, rgb[2] as f32]);
+            pixel.copy_from_slice(&[lightness * 2.55, a + 128.0, b + 128.0]);
+        });
+
+    output
+}
+
+/// Inverse of `to_lab_f32`, rounding and clamping to 8-bit sRGB.
+pub fn from_lab_f32(image: &RgbImageF32) -> RgbImage {
+    let mut output = RgbImage::new(image.width(), image.height());
+    output
+        .par_chunks_mut(3)
+        .zip(image.par_chunks(3))
+        .for_each(|(pixel, lab)| {
+            let rgb = lab_to_rgb([lab[0] / 2.55, lab[1] - 128.0, lab[2] - 128.0]);
+            pixel.copy_from_slice(&rgb.map(|channel| channel.round().clamp(0.0, 255.0) as u8));
+        });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::{generate_test_image, TestPattern};
+
+    #[test]
+    fn grayscale_uses_the_luminance_weights() {
+        let primaries = RgbImage::from_fn(3, 1, |x, _| {
+            let mut color = [0; 3];
+            color[x as usize] = 255;
+            Rgb(color)
+        });
+
+        // 0.299, 0.587 and 0.114 of 255, rounded
+        let gray = to_grayscale(&primaries);
+        assert_eq!(gray.get_pixel(0, 0).0, [76; 3]);
+        assert_eq!(gray.get_pixel(1, 0).0, [150; 3]);
+        assert_eq!(gray.get_pixel(2, 0).0, [29; 3]);
+        assert_eq!(to_luma8(&primaries).into_raw(), vec![76, 150, 29]);
+    }
+
+    /// A 16x16 gradient covering most red/green combinations.
+    fn gradient() -> RgbImage {
+        generate_test_image(16, 16, TestPattern::Gradient)
+    }
+
+    #[test]
+    fn neutral_brightness_and_contrast_is_the_identity() {
+        let image = gradient();
+        assert_eq!(adjust_brightness_contrast(&image, 0, 1.0), image);
+        assert_eq!(adjust_brightness_contrast(&image, 20, 1.0).get_pixel(0, 0).0, [20, 20, 148]);
+        assert_eq!(adjust_brightness_contrast(&image, 0, 0.0), RgbImage::from_pixel(16, 16, Rgb([128; 3])));
+    }
+
+    #[test]
+    fn invert_is_its_own_inverse() {
+        let image = gradient();
+        assert_eq!(invert(&image).get_pixel(0, 0).0, [255, 255, 127]);
+        assert_eq!(invert(&invert(&image)), image);
+    }
+
+    #[test]
+    fn sepia_keeps_white_near_white() {
+        // The red and green rows of the matrix sum past 1 and clamp; blue lands at 0.937 of 255
+        let white = sepia(&RgbImage::from_pixel(2, 2, Rgb([255; 3])));
+        assert!(white.pixels().all(|pixel| pixel.0 == [255, 255, 239]));
+    }
+
+    #[test]
+    fn hsv_round_trip_recovers_the_pixel() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(15) {
+                    let pixel = Rgb([r as u8, g as u8, b as u8]);
+                    let back = hsv_to_rgb(rgb_to_hsv(&pixel));
+                    assert!(back.0.iter().zip(pixel.0).all(|(&a, b)| a.abs_diff(b) <= 1), "{:?} came back as {:?}", pixel, back);
+                }
+            }
+        }
+        assert_eq!(rgb_to_hsv(&Rgb([255, 0, 0])), [0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn saturation_boost_keeps_grays_and_factor_one() {
+        let image = gradient();
+        assert_eq!(adjust_saturation(&image, 1.0), image);
+        assert_eq!(adjust_saturation(&image, 0.0), to_grayscale(&adjust_saturation(&image, 0.0)));
+        let gray = RgbImage::from_pixel(3, 3, Rgb([77; 3]));
+        assert_eq!(adjust_saturation(&gray, 2.0), gray);
+    }
+
+    #[test]
+    fn identity_lut_keeps_the_image_and_gamma_brightens_midtones() {
+        let image = gradient();
+        assert_eq!(apply_lut(&image, &std::array::from_fn(|value| value as u8)), image);
+        assert_eq!(apply_lut(&image, &gamma_lut(1.0)), image);
+
+        let lut = gamma_lut(2.2);
+        assert_eq!((lut[0], lut[255]), (0, 255));
+        assert!(lut[128] > 128, "{}", lut[128]);
+        assert!(lut.windows(2).all(|pair| pair[0] <= pair[1]));
+        let brightened = apply_lut(&image, &lut);
+        assert!(brightened.pixels().zip(image.pixels()).all(|(brightened, original)| (0..3).all(|c| brightened[c] >= original[c])));
+
+        let levels = levels_lut(50, 200, 1.0);
+        assert_eq!((levels[50], levels[125], levels[200]), (0, 128, 255));
+    }
+
+    #[test]
+    fn split_then_merge_reproduces_the_original() {
+        let image = generate_test_image(16, 12, TestPattern::Gradient);
+        let channels = split_channels(&image);
+
+        // Each channel image is gray, with that channel in all three of its channels
+        for (channel, split) in channels.iter().enumerate() {
+            assert!(split.pixels().zip(image.pixels()).all(|(split, original)| split.0 == [original[channel]; 3]));
+        }
+
+        let [red, green, blue] = channels.map(|channel| to_luma8(&channel));
+        assert_eq!(merge_channels(&red, &green, &blue).unwrap(), image);
+    }
+
+    #[test]
+    fn posterize_keeps_256_levels_and_collapses_one() {
+        let image = gradient();
+        assert_eq!(posterize(&image, 256), image);
+        assert!(posterize(&image, 1).pixels().all(|pixel| pixel.0 == [128; 3]));
+
+        // Two levels threshold each channel at 128
+        let two = posterize(&image, 2);
+        for (posterized, original) in two.pixels().zip(image.pixels()) {
+            assert_eq!(posterized.0, original.0.map(|channel| if channel >= 128 { 255 } else { 0 }));
+        }
+        let mut levels: Vec<u8> = posterize(&image, 4).pixels().flat_map(|pixel| pixel.0).collect();
+        levels.sort();
+        levels.dedup();
+        assert_eq!(levels, vec![0, 85, 170, 255]);
+    }
+
+    #[test]
+    fn dithered_gradient_uses_only_the_levels_and_keeps_its_brightness() {
+        let ramp = RgbImage::from_fn(64, 16, |x, _| Rgb([(x * 4) as u8; 3]));
+        let dithered = dither_floyd_steinberg(&ramp, 4);
+        assert!(dithered.iter().all(|value| [0, 85, 170, 255].contains(value)));
+
+        // Each 8x8 block averages out to about the input's brightness there, which posterizing alone misses
+        let block_mean = |image: &RgbImage, bx: u32, by: u32| {
+            let sum: u32 = (0..8).flat_map(|y| (0..8).map(move |x| (x, y)))
+                .map(|(x, y)| image.get_pixel(bx * 8 + x, by * 8 + y)[0] as u32)
+                .sum();
+            sum as f32 / 64.0
+        };
+        for by in 0..2 {
+            for bx in 0..8 {
+                let error = block_mean(&dithered, bx, by) - block_mean(&ramp, bx, by);
+                assert!(error.abs() < 6.0, "block ({}, {}) is off by {}", bx, by, error);
+            }
+        }
+        let posterized = posterize(&ramp, 4);
+        assert!((block_mean(&posterized, 0, 0) - block_mean(&ramp, 0, 0)).abs() > 10.0);
+        let total = |image: &RgbImage| image.iter().map(|&value| value as f32).sum::<f32>();
+        assert!((total(&dithered) - total(&ramp)).abs() / (64.0 * 16.0 * 3.0) < 1.0);
+    }
+
+    #[test]
+    fn half_white_mask_leaves_the_black_half_untouched() {
+        let original = gradient();
+        let filtered = posterize(&original, 2);
+        let mask = GrayImage::from_fn(16, 16, |x, _| Luma([if x < 8 { 255 } else { 0 }]));
+
+        let masked = apply_masked(&original, &filtered, &mask);
+        for (x, y, pixel) in masked.enumerate_pixels() {
+            let expected = if x < 8 { filtered.get_pixel(x, y) } else { original.get_pixel(x, y) };
+            assert_eq!(pixel, expected, "pixel ({}, {})", x, y);
+        }
+
+        // Mid-gray mixes the two halfway
+        let black = RgbImage::new(1, 1);
+        let white = RgbImage::from_pixel(1, 1, Rgb([255; 3]));
+        assert_eq!(apply_masked(&black, &white, &GrayImage::from_pixel(1, 1, Luma([128]))).get_pixel(0, 0).0, [128; 3]);
+    }
+
+    /// Colors covering the corners of the RGB cube, grays and a few in between.
+    const SAMPLE_COLORS: [[f32; 3]; 10] = [
+        [0.0, 0.0, 0.0], [255.0, 255.0, 255.0], [128.0, 128.0, 128.0], [255.0, 0.0, 0.0], [0.0, 255.0, 0.0],
+        [0.0, 0.0, 255.0], [255.0, 255.0, 0.0], [12.0, 200.0, 180.0], [240.0, 128.0, 30.0], [70.0, 40.0, 90.0],
+    ];
+
+    #[test]
+    fn lab_and_cmyk_round_trip_sample_colors() {
+        for rgb in SAMPLE_COLORS {
+            let through_lab = lab_to_rgb(rgb_to_lab(rgb));
+            let through_cmyk = cmyk_to_rgb(rgb_to_cmyk(rgb));
+            for channel in 0..3 {
+                assert!((through_lab[channel] - rgb[channel]).abs() < 0.5, "{:?} via Lab gave {:?}", rgb, through_lab);
+                assert!((through_cmyk[channel] - rgb[channel]).abs() < 0.5, "{:?} via CMYK gave {:?}", rgb, through_cmyk);
+            }
+        }
+
+        // D65 white is L* 100 with no color, and black needs only black ink
+        let [lightness, a, b] = rgb_to_lab([255.0; 3]);
+        assert!((lightness - 100.0).abs() < 0.01 && a.abs() < 0.01 && b.abs() < 0.01, "{:?}", [lightness, a, b]);
+        assert_eq!(rgb_to_cmyk([0.0; 3]), [0.0, 0.0, 0.0, 1.0]);
+    }
+}
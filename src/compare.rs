@@ -0,0 +1,117 @@
+use image::RgbImage;
+use rayon::prelude::*;
+
+use crate::color::to_luma8;
+use crate::convolution::{apply_convolution_gray_f32, gray_to_f32, BorderMode, GrayImageF32};
+use crate::error::Error;
+use crate::kernel::generate_gaussian_kernel;
+
+/// Side of the Gaussian window SSIM compares over, with its sigma (the values from the original SSIM paper).
+const SSIM_WINDOW: usize = 11;
+const SSIM_SIGMA: f32 = 1.5;
+
+/// Peak signal-to-noise ratio between two images in decibels, over all R, G and B values.
+/// Higher is closer: around 30–50 dB is a mild change, and identical images give `f64::INFINITY`.
+/// Returns an error if the images differ in size.
+pub fn psnr(a: &RgbImage, b: &RgbImage) -> Result<f64, Error> {
+    check_same_size(a, b)?;
+
+    let squared_error: f64 = a
+        .as_raw()
+        .par_iter()
+        .zip(b.as_raw().par_iter())
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+
+    Ok(if mse == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mse).log10() })
+}
+
+/// Mean structural similarity (SSIM) of the two images' luminance, from -1 to 1 where 1 means identical.
+/// Means, variances and covariance are taken over an 11x11 Gaussian window (sigma 1.5) around each pixel, so
+/// unlike PSNR this tracks how similar local structure looks rather than raw pixel differences.
+/// Returns an error if the images differ in size.
+pub fn ssim(a: &RgbImage, b: &RgbImage) -> Result<f64, Error> {
+    check_same_size(a, b)?;
+    let (width, height) = a.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(1.0);
+    }
+
+    // Stabilizing constants for dark, flat windows: (0.01 * 255)² and (0.03 * 255)²
+    const C1: f32 = 6.5025;
+    const C2: f32 = 58.5225;
+
+    // Tiny images get a window no larger than they are (the size stays odd)
+    let size = SSIM_WINDOW.min(2 * width.max(height) as usize - 1);
+    let window = generate_gaussian_kernel(size, SSIM_SIGMA);
+    let local_mean = |image: &GrayImageF32| apply_convolution_gray_f32(image, &window, BorderMode::Clamp, 0.0);
+    let product = |x: &GrayImageF32, y: &GrayImageF32| {
+        GrayImageF32::from_raw(width, height, x.iter().zip(y.iter()).map(|(x, y)| x * y).collect())
+            .expect("Buffer size matches the image dimensions")
+    };
+
+    let x = gray_to_f32(&to_luma8(a));
+    let y = gray_to_f32(&to_luma8(b));
+    let (mean_x, mean_y) = (local_mean(&x), local_mean(&y));
+    let (mean_xx, mean_yy, mean_xy) = (local_mean(&product(&x, &x)), local_mean(&product(&y, &y)), local_mean(&product(&x, &y)));
+
+    let total: f64 = (0..mean_x.len())
+        .into_par_iter()
+        .map(|index| {
+            let (mx, my) = (mean_x.as_raw()[index], mean_y.as_raw()[index]);
+            let variance_x = mean_xx.as_raw()[index] - mx * mx;
+            let variance_y = mean_yy.as_raw()[index] - my * my;
+            let covariance = mean_xy.as_raw()[index] - mx * my;
+
+            let similarity = ((2.0 * mx * my + C1) * (2.0 * covariance + C2))
+                / ((mx * mx + my * my + C1) * (variance_x + variance_y + C2));
+            similarity as f64
+        })
+        .sum();
+
+    Ok(total / mean_x.len() as f64)
+}
+
+fn check_same_size(a: &RgbImage, b: &RgbImage) -> Result<(), Error> {
+    if a.dimensions() != b.dimensions() {
+        return Err(Error::InvalidArgument(format!(
+            "images must have the same size to be compared, but they are {}x{} and {}x{}",
+            a.width(), a.height(), b.width(), b.height()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::kernel::BlurKind;
+    use crate::pattern::{generate_test_image, TestPattern};
+
+    #[test]
+    fn identical_images_are_a_perfect_match() {
+        let image = generate_test_image(32, 24, TestPattern::Checkerboard { cell: 4 });
+        assert_eq!(psnr(&image, &image).unwrap(), f64::INFINITY);
+        assert!((ssim(&image, &image).unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_stronger_blur_scores_lower() {
+        let image = generate_test_image(32, 24, TestPattern::Checkerboard { cell: 4 });
+        let blur = |size| Filter::Blur { size, kind: BlurKind::Box, linear: false }.apply(&image);
+        let (mild, strong) = (blur(3), blur(7));
+
+        assert!(psnr(&image, &mild).unwrap() > psnr(&image, &strong).unwrap());
+        assert!(ssim(&image, &mild).unwrap() > ssim(&image, &strong).unwrap());
+        assert!(ssim(&image, &strong).unwrap() < 1.0);
+    }
+
+    #[test]
+    fn different_sizes_are_an_error() {
+        let (a, b) = (RgbImage::new(4, 4), RgbImage::new(4, 5));
+        assert!(matches!(psnr(&a, &b), Err(Error::InvalidArgument(_))));
+        assert!(matches!(ssim(&a, &b), Err(Error::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,1347 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+
+use image::buffer::EnumeratePixelsMut;
+use image::{GrayImage, RgbImage, RgbaImage, Rgb, Rgba, ImageBuffer, Luma, Pixel, Primitive};
+use rayon::prelude::*;
+
+use crate::color::{luminance, rgb_to_ycbcr, ycbcr_to_rgb};
+use crate::kernel::{separate_kernel, validate_kernel};
+use crate::progress::RowProgress;
+
+/// An RGB image with f32 channels on the usual 0–255 scale, but unclamped.
+/// Chaining filters through it avoids rounding and clipping between stages; only `to_u8` clamps.
+pub type RgbImageF32 = ImageBuffer<Rgb<f32>, Vec<f32>>;
+
+/// Single-channel f32 image, used to keep grayscale inputs on one channel through a pipeline.
+pub type GrayImageF32 = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+/// A 16-bit-per-channel RGB image (0–65535), e.g. decoded from a 16-bit PNG or TIFF.
+pub type RgbImage16 = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+/// Default for `set_tile_height`: a few MB of f32 intermediate per strip even for very wide images.
+pub const DEFAULT_TILE_HEIGHT: usize = 256;
+
+/// Rows per strip of the separable passes; 0 processes the whole image in one strip.
+static TILE_HEIGHT: AtomicUsize = AtomicUsize::new(DEFAULT_TILE_HEIGHT);
+
+/// Sets how many output rows the separable convolutions (box, Gaussian and straight motion blurs) produce
+/// per strip. Only a strip plus its halo of `half_k` rows is held in the f32 intermediate, which bounds
+/// peak memory on very large images; the result is the same for any height. 0 disables tiling.
+/// The direct 2D path (`apply_convolution`) reads the input in place and needs no intermediate.
+pub fn set_tile_height(rows: usize) {
+    TILE_HEIGHT.store(rows, Ordering::Relaxed);
+}
+
+/// Whether the separable vertical pass accumulates whole blocks of columns at once; see `set_blocked_vertical_pass`.
+static BLOCKED_VERTICAL: AtomicBool = AtomicBool::new(true);
+
+/// Pixels per column block of the blocked vertical pass: the block's sums (3–4 KB) stay in L1 cache.
+const COLUMN_BLOCK: usize = 256;
+
+/// Chooses how the separable convolutions run their vertical pass. Blocked (the default) walks each source row
+/// contiguously across a block of `COLUMN_BLOCK` columns, adding it into the block's sums, instead of striding down
+/// the column for every pixel, which is much kinder to the cache on large images. Both add the same terms in the
+/// same order, so the output is byte-identical; the per-pixel pass is kept for comparison.
+pub fn set_blocked_vertical_pass(enabled: bool) {
+    BLOCKED_VERTICAL.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether `apply_convolution` walks rows with a plain sequential iterator instead of Rayon.
+static SEQUENTIAL: AtomicBool = AtomicBool::new(false);
+
+/// Makes `apply_convolution` process rows one after another on the calling thread, for debugging and
+/// golden-image tests. Every row is computed independently either way, so the output is byte-identical.
+pub fn set_sequential(enabled: bool) {
+    SEQUENTIAL.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether the final clamp to 0–255 rolls off smoothly instead of cutting off; see `set_soft_clip`.
+static SOFT_CLIP: AtomicBool = AtomicBool::new(false);
+
+/// Distance from 0 and 255 over which `soft_clip` compresses values; everything in between is unchanged.
+pub const SOFT_CLIP_KNEE: f32 = 32.0;
+
+/// Makes the convolutions and `to_u8` pass results through `soft_clip` before rounding, so overshoots
+/// from aggressive sharpening are compressed into the top and bottom of the range instead of flattened.
+pub fn set_soft_clip(enabled: bool) {
+    SOFT_CLIP.store(enabled, Ordering::Relaxed);
+}
+
+/// Smooth rolloff towards 0 and 255: values within `SOFT_CLIP_KNEE` of either end (and beyond) follow a tanh
+/// curve with slope 1 at the knee, so 250 and 300 come out different instead of both 255. Mid-range values pass through.
+/// The price is that the ends themselves move in a little: 255 maps to about 247 and 0 to about 8.
+pub fn soft_clip(value: f32) -> f32 {
+    let (low, high) = (SOFT_CLIP_KNEE, 255.0 - SOFT_CLIP_KNEE);
+    if value > high {
+        high + SOFT_CLIP_KNEE * ((value - high) / SOFT_CLIP_KNEE).tanh()
+    } else if value < low {
+        low - SOFT_CLIP_KNEE * ((low - value) / SOFT_CLIP_KNEE).tanh()
+    } else {
+        value
+    }
+}
+
+/// The rounding every 8-bit result goes through: `soft_clip` first when `soft` (read once per image from
+/// `set_soft_clip`), then the usual clamp.
+fn clip_u8(value: f32, soft: bool) -> u8 {
+    let value = if soft { soft_clip(value) } else { value };
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// Same as `clip_u8` for values rescaled to 0–65535.
+fn clip_u16(value: f32, soft: bool) -> u16 {
+    let value = if soft { soft_clip(value / 257.0) * 257.0 } else { value };
+    value.round().clamp(0.0, 65535.0) as u16
+}
+
+/// How `apply_convolution` splits an image between worker threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Schedule {
+    /// Tiles for images below `SMALL_IMAGE_PIXELS`, rows otherwise. The default.
+    #[default]
+    Auto,
+    /// One task per output row. Large images have plenty of rows to keep every core busy.
+    Rows,
+    /// Equal runs of pixels over the raw buffer, several per worker thread. A small image may have fewer
+    /// rows than there are cores to share, and bridging rows into Rayon one by one costs more than they take.
+    Tiles,
+}
+
+/// Images with fewer pixels than this (512x512) use `Schedule::Tiles` under `Schedule::Auto`.
+pub const SMALL_IMAGE_PIXELS: u64 = 512 * 512;
+
+/// Tiles per worker thread, so threads that finish early can pick up the rest.
+const TILES_PER_THREAD: usize = 8;
+
+/// Smallest tile, keeping the per-task overhead negligible.
+const MIN_TILE_PIXELS: usize = 256;
+
+static SCHEDULE: AtomicU8 = AtomicU8::new(Schedule::Auto as u8);
+
+/// Overrides how `apply_convolution` splits the work; the output is byte-identical for every schedule.
+pub fn set_schedule(schedule: Schedule) {
+    SCHEDULE.store(schedule as u8, Ordering::Relaxed);
+}
+
+/// The schedule chosen with `set_schedule`.
+fn current_schedule() -> Schedule {
+    match SCHEDULE.load(Ordering::Relaxed) {
+        value if value == Schedule::Rows as u8 => Schedule::Rows,
+        value if value == Schedule::Tiles as u8 => Schedule::Tiles,
+        _ => Schedule::Auto,
+    }
+}
+
+/// What `schedule` comes down to for a `width x height` image: `Auto` picks tiles or rows by size.
+fn schedule_for(width: u32, height: u32, schedule: Schedule) -> Schedule {
+    match schedule {
+        Schedule::Auto if (width as u64 * height as u64) < SMALL_IMAGE_PIXELS => Schedule::Tiles,
+        Schedule::Auto => Schedule::Rows,
+        schedule => schedule,
+    }
+}
+
+/// Channel types the convolution routines can read.
+pub trait Channel: Primitive + Send + Sync {
+    /// The channel value as f32, in the channel's own range.
+    fn to_f32(self) -> f32;
+    /// Converts a `BorderMode::Constant` fill, given on the 8-bit scale, into this channel's range.
+    fn from_fill(value: u8) -> Self;
+}
+
+impl Channel for u8 {
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_fill(value: u8) -> Self {
+        value
+    }
+}
+
+impl Channel for u16 {
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_fill(value: u8) -> Self {
+        // 255 * 257 = 65535, so the 8-bit scale maps exactly onto the full 16-bit range
+        value as u16 * 257
+    }
+}
+
+impl Channel for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_fill(value: u8) -> Self {
+        value as f32
+    }
+}
+
+/// Converts an 8-bit image to the f32 working format without changing any values.
+pub fn to_f32(image: &RgbImage) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_raw(width, height, image.iter().map(|&channel| channel as f32).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// Rounds and clamps an f32 image back to 8 bits; this is the only place the working format is clipped.
+pub fn to_u8(image: &RgbImageF32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    ImageBuffer::from_raw(width, height, image.iter().map(|&channel| clip_u8(channel, soft)).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// Same as `to_f32` for a grayscale image.
+pub fn gray_to_f32(image: &GrayImage) -> GrayImageF32 {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_raw(width, height, image.iter().map(|&value| value as f32).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// Same as `to_u8` for a grayscale image.
+pub fn gray_to_u8(image: &GrayImageF32) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    ImageBuffer::from_raw(width, height, image.iter().map(|&value| clip_u8(value, soft)).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// Converts a 16-bit image to the f32 working format, rescaled to 0–255 but keeping the fractional precision.
+pub fn from_u16(image: &RgbImage16) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_raw(width, height, image.iter().map(|&channel| channel as f32 / 257.0).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// Rounds and clamps an f32 image to 16 bits, rescaling 0–255 to 0–65535.
+pub fn to_u16(image: &RgbImageF32) -> RgbImage16 {
+    let (width, height) = image.dimensions();
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    ImageBuffer::from_raw(width, height, image.iter().map(|&channel| clip_u16(channel * 257.0, soft)).collect())
+        .expect("Buffer size matches the image dimensions")
+}
+
+/// How neighbor lookups that fall outside the image are resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BorderMode {
+    /// Repeat the nearest edge pixel.
+    #[default]
+    Clamp,
+    /// Mirror pixels across the boundary without repeating the edge pixel (-1 maps to 1).
+    Reflect,
+    /// Treat the image as toroidal, so leaving one edge re-enters from the opposite one.
+    Wrap,
+    /// Use a fixed value for every channel outside the image.
+    Constant(u8),
+    /// Use a fixed color outside the image, e.g. the known background behind an object.
+    /// Alpha reads as opaque, and grayscale images read the color's luminance.
+    ConstantRgb([u8; 3]),
+}
+
+impl BorderMode {
+    /// Maps a possibly out-of-bounds coordinate onto `0..len`.
+    /// Returns `None` when the constant fill value should be used instead.
+    pub fn resolve(self, coord: i32, len: u32) -> Option<u32> {
+        let len = len as i32;
+        if (0..len).contains(&coord) {
+            return Some(coord as u32);
+        }
+
+        match self {
+            BorderMode::Clamp => Some(coord.clamp(0, len - 1) as u32),
+            BorderMode::Reflect => {
+                // Mirroring repeats with a period of 2 * (len - 1), which also covers kernels wider than the image
+                if len == 1 {
+                    return Some(0);
+                }
+                let period = 2 * (len - 1);
+                let folded = coord.rem_euclid(period);
+                Some(if folded < len { folded } else { period - folded } as u32)
+            }
+            BorderMode::Wrap => Some(coord.rem_euclid(len) as u32),
+            BorderMode::Constant(_) | BorderMode::ConstantRgb(_) => None,
+        }
+    }
+
+    /// Looks up the pixel at (x, y), applying the border rule when it falls outside the image.
+    /// A `Constant` fill applies to every channel, including alpha for RGBA images; a `ConstantRgb` fill is opaque.
+    pub fn sample<P>(self, image: &ImageBuffer<P, Vec<P::Subpixel>>, x: i32, y: i32) -> P
+    where
+        P: Pixel,
+        P::Subpixel: Channel,
+    {
+        let (width, height) = image.dimensions();
+        match (self.resolve(x, width), self.resolve(y, height)) {
+            (Some(nx), Some(ny)) => *image.get_pixel(nx, ny),
+            _ => {
+                let channels = P::CHANNEL_COUNT as usize;
+                *P::from_slice(&self.fill_value(channels).map(P::Subpixel::from_fill)[..channels])
+            }
+        }
+    }
+
+    /// The channel values used for unresolved coordinates (only the constant modes produce them)
+    /// in a pixel with `channels` channels: one or two for gray (with alpha), three or four for color.
+    fn fill_value(self, channels: usize) -> [u8; 4] {
+        match self {
+            BorderMode::Constant(value) => [value; 4],
+            BorderMode::ConstantRgb(color) if channels <= 2 => [luminance(&Rgb(color)).round() as u8, 255, 0, 0],
+            BorderMode::ConstantRgb([r, g, b]) => [r, g, b, 255],
+            _ => [0; 4],
+        }
+    }
+}
+
+/// Applies an (m x n) convolution kernel to an RGB image using multi-threading.
+/// Each color channel (R, G, B) is processed independently, and `border` decides how pixels past the edges are read.
+/// `bias` is added to every weighted sum before clamping (pass 0.0 for plain convolution).
+/// Rows are spread over the threads, or tiles of pixels for small images (see `Schedule`).
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution(image: &RgbImage, kernel: &[Vec<f32>], border: BorderMode, bias: f32) -> RgbImage {
+    apply_convolution_with(image, kernel, border, bias, SEQUENTIAL.load(Ordering::Relaxed), current_schedule())
+}
+
+/// `apply_convolution` on the calling thread alone when `sequential`, otherwise spread over Rayon by `schedule`.
+fn apply_convolution_with(image: &RgbImage, kernel: &[Vec<f32>], border: BorderMode, bias: f32, sequential: bool, schedule: Schedule) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let (half_kx, half_ky) = kernel_center(kernel);
+
+    // Create an empty output image with the same dimensions
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    if width == 0 || height == 0 {
+        return output;
+    }
+    let progress = RowProgress::start(height);
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+
+    let convolve_pixel = |x: u32, y: u32, pixel: &mut [u8]| {
+        let [sum_r, sum_g, sum_b] = weighted_sum(image, kernel, border, x, y, half_kx, half_ky);
+
+        // Offset, clamp values and assign them to the output pixel
+        pixel[0] = clip_u8(sum_r + bias, soft);
+        pixel[1] = clip_u8(sum_g + bias, soft);
+        pixel[2] = clip_u8(sum_b + bias, soft);
+    };
+    let process_row = |(_y, row): (u32, EnumeratePixelsMut<'_, Rgb<u8>>)| {
+        for (x, y, pixel) in row {
+            convolve_pixel(x, y, &mut pixel.0);
+        }
+
+        // One relaxed atomic add per row, so the hot loop above is unaffected
+        progress.row_done();
+    };
+
+    if sequential {
+        output.enumerate_rows_mut().for_each(process_row);
+    } else if schedule_for(width, height, schedule) == Schedule::Tiles {
+        // Indexed chunks split evenly across the pool; a tile may start and end mid-row
+        let pixels = width as usize * height as usize;
+        let tile_pixels = pixels.div_ceil(rayon::current_num_threads() * TILES_PER_THREAD).max(MIN_TILE_PIXELS);
+        output
+            .par_chunks_mut(tile_pixels * 3)
+            .enumerate()
+            .for_each(|(tile, chunk)| {
+                let start = tile * tile_pixels;
+                for (offset, pixel) in chunk.chunks_exact_mut(3).enumerate() {
+                    let index = start + offset;
+                    convolve_pixel((index % width as usize) as u32, (index / width as usize) as u32, pixel);
+                }
+                progress.inc(rows_ending_in(start..start + chunk.len() / 3, width as usize));
+            });
+    } else {
+        // Uses Rayon to parallelize row processing
+        output
+            .enumerate_rows_mut()
+            .par_bridge() // Convert to parallel iterator
+            .for_each(process_row);
+    }
+
+    progress.finish();
+    output
+}
+
+/// Same as `apply_convolution` on the f32 working format: sums (plus `bias`) are stored unrounded and unclamped.
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution_f32(image: &RgbImageF32, kernel: &[Vec<f32>], border: BorderMode, bias: f32) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+    let (half_kx, half_ky) = kernel_center(kernel);
+
+    let mut output: RgbImageF32 = ImageBuffer::new(width, height);
+    let progress = RowProgress::start(height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let sums: [f32; 3] = weighted_sum(image, kernel, border, x, y, half_kx, half_ky);
+                pixel.0 = sums.map(|sum| sum + bias);
+            }
+            progress.row_done();
+        });
+
+    progress.finish();
+    output
+}
+
+/// Same as `apply_convolution` on a 16-bit image, clamping to 0–65535 so detail finer than 8 bits survives.
+/// `bias` is on the 16-bit scale. Sums are accumulated in f32, which holds every u16 value exactly.
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution_u16(image: &RgbImage16, kernel: &[Vec<f32>], border: BorderMode, bias: f32) -> RgbImage16 {
+    let (width, height) = image.dimensions();
+    let (half_kx, half_ky) = kernel_center(kernel);
+
+    let mut output: RgbImage16 = ImageBuffer::new(width, height);
+    let progress = RowProgress::start(height);
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let sums: [f32; 3] = weighted_sum(image, kernel, border, x, y, half_kx, half_ky);
+                pixel.0 = sums.map(|sum| clip_u16(sum + bias, soft));
+            }
+            progress.row_done();
+        });
+
+    progress.finish();
+    output
+}
+
+/// Which of the R, G and B channels an operation may change; unselected channels are copied from the input.
+/// Alpha is never affected by the mask.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelMask(pub [bool; 3]);
+
+impl ChannelMask {
+    /// Every color channel selected, i.e. no masking.
+    pub const ALL: ChannelMask = ChannelMask([true; 3]);
+
+    /// Takes the selected channels from `processed` and the rest from `original`.
+    /// Both images must have the same dimensions.
+    pub fn merge<P>(self, original: &ImageBuffer<P, Vec<P::Subpixel>>, processed: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P: Pixel + Send + Sync,
+        P::Subpixel: Send + Sync,
+    {
+        let mut output = processed.clone();
+        if self == ChannelMask::ALL {
+            return output;
+        }
+
+        output
+            .enumerate_rows_mut()
+            .par_bridge()
+            .for_each(|(_y, row)| {
+                for (x, y, pixel) in row {
+                    let original_pixel = original.get_pixel(x, y);
+                    for ((channel, &selected), &value) in pixel.channels_mut().iter_mut().zip(&self.0).zip(original_pixel.channels()) {
+                        if !selected {
+                            *channel = value;
+                        }
+                    }
+                }
+            });
+
+        output
+    }
+}
+
+impl Default for ChannelMask {
+    fn default() -> Self {
+        ChannelMask::ALL
+    }
+}
+
+/// Same as `apply_convolution`, but only the channels selected by `channels` are convolved;
+/// the others are copied through untouched (e.g. blur just red and blue to soften chroma noise).
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution_masked(image: &RgbImage, kernel: &[Vec<f32>], border: BorderMode, bias: f32, channels: ChannelMask) -> RgbImage {
+    channels.merge(image, &apply_convolution(image, kernel, border, bias))
+}
+
+/// How the alpha channel is treated by `apply_convolution_rgba`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlphaMode {
+    /// Copy alpha from the input unchanged; only R, G and B are convolved.
+    #[default]
+    Preserve,
+    /// Convolve alpha as a fourth channel, so blurs also soften transparent edges.
+    Convolve,
+    /// Convolve alpha too, but weight every pixel's color by its alpha first (premultiplied alpha) and divide
+    /// it back out afterwards. Fully transparent pixels then add nothing to their neighbors' color, so the
+    /// garbage RGB hiding under them (often black) can't bleed dark or colored halos into opaque edges.
+    Premultiplied,
+}
+
+/// Applies an (m x n) convolution kernel to an RGBA image using multi-threading.
+/// Color channels are treated exactly like `apply_convolution`; `alpha` chooses whether transparency is kept or convolved.
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution_rgba(image: &RgbaImage, kernel: &[Vec<f32>], border: BorderMode, alpha: AlphaMode) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let (half_kx, half_ky) = kernel_center(kernel);
+
+    let mut output: RgbaImage = ImageBuffer::new(width, height);
+    let progress = RowProgress::start(height);
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    let premultiplied = (alpha == AlphaMode::Premultiplied).then(|| premultiply(image));
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let sums: [f32; 4] = match &premultiplied {
+                    Some(premultiplied) => unpremultiply(weighted_sum(premultiplied, kernel, border, x, y, half_kx, half_ky)),
+                    None => weighted_sum(image, kernel, border, x, y, half_kx, half_ky),
+                };
+                // Alpha is coverage, not color, so it is never soft clipped
+                for (index, (channel, sum)) in pixel.0.iter_mut().zip(sums).enumerate() {
+                    *channel = clip_u8(sum, soft && index < 3);
+                }
+
+                if alpha == AlphaMode::Preserve {
+                    pixel.0[3] = image.get_pixel(x, y)[3];
+                }
+            }
+            progress.row_done();
+        });
+
+    progress.finish();
+    output
+}
+
+/// Color channels multiplied by alpha (on the 0–255 scale), e.g. (200, 100, 0) at alpha 51 becomes (40, 20, 0).
+fn premultiply(image: &RgbaImage) -> ImageBuffer<Rgba<f32>, Vec<f32>> {
+    let (width, height) = image.dimensions();
+    let mut output = ImageBuffer::new(width, height);
+    output.par_chunks_mut(4).zip(image.par_chunks(4)).for_each(|(output, pixel): (&mut [f32], &[u8])| {
+        let alpha = pixel[3] as f32;
+        for (output, &channel) in output[..3].iter_mut().zip(&pixel[..3]) {
+            *output = channel as f32 * alpha / 255.0;
+        }
+        output[3] = alpha;
+    });
+    output
+}
+
+/// Undoes `premultiply` on convolved sums; colors where nothing is left visible come out black.
+fn unpremultiply([r, g, b, alpha]: [f32; 4]) -> [f32; 4] {
+    if alpha <= 0.0 {
+        return [0.0; 4];
+    }
+    let scale = 255.0 / alpha;
+    [r * scale, g * scale, b * scale, alpha]
+}
+
+/// Runs `process` on the luminance (YCbCr Y) of `image` and converts back with the original Cb and Cr, e.g. to
+/// sharpen without color fringes at colored edges, at a third of the work of filtering R, G and B.
+/// Y stays unclamped f32 throughout, so however many filters `process` chains, the only clamp is the final one
+/// to RGB; pixels the new Y pushes out of gamut are clamped per channel there, like `apply_convolution`.
+pub fn apply_to_luma(image: &RgbImage, process: impl FnOnce(GrayImageF32) -> GrayImageF32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let ycbcr: Vec<[f32; 3]> = image.pixels().map(|pixel| rgb_to_ycbcr(pixel.0.map(|channel| channel as f32))).collect();
+    let luma: GrayImageF32 = ImageBuffer::from_raw(width, height, ycbcr.iter().map(|pixel| pixel[0]).collect())
+        .expect("Buffer size matches the image dimensions");
+
+    let filtered = process(luma);
+
+    let mut output: RgbImage = ImageBuffer::new(width, height);
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    output
+        .par_chunks_mut(3)
+        .zip(filtered.par_iter().zip(ycbcr.par_iter()))
+        .for_each(|(pixel, (luma, [_, cb, cr]))| {
+            let rgb = ycbcr_to_rgb([*luma, *cb, *cr]);
+            for (channel, value) in pixel.iter_mut().zip(rgb) {
+                *channel = clip_u8(value, soft);
+            }
+        });
+
+    output
+}
+
+/// Convolves a single-channel grayscale image, doing a third of the work of the same kernel on RGB.
+/// `bias` is added before clamping, as in `apply_convolution`.
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution_luma8(image: &GrayImage, kernel: &[Vec<f32>], border: BorderMode, bias: f32) -> GrayImage {
+    gray_to_u8(&apply_convolution_gray_f32(&gray_to_f32(image), kernel, border, bias))
+}
+
+/// Same as `apply_convolution_luma8` in the f32 working format, without clamping.
+/// Works in strips of `set_tile_height` output rows like the separable RGB path: each strip is padded according to
+/// `border` (with the rows its kernel reaches above and below), so the inner loops run over plain row slices and
+/// only one strip's padded rows and intermediate are held at a time. Separable kernels take two 1D passes
+/// (see `apply_separable_convolution`).
+///
+/// Panics if `validate_kernel` rejects the kernel (empty, ragged rows or an even dimension).
+pub fn apply_convolution_gray_f32(image: &GrayImageF32, kernel: &[Vec<f32>], border: BorderMode, bias: f32) -> GrayImageF32 {
+    gray_in_strips(image, kernel, border, bias, TILE_HEIGHT.load(Ordering::Relaxed))
+}
+
+/// `apply_convolution_gray_f32` in strips of `tile_height` output rows (0 for a single strip).
+fn gray_in_strips(image: &GrayImageF32, kernel: &[Vec<f32>], border: BorderMode, bias: f32, tile_height: usize) -> GrayImageF32 {
+    let (half_kx, half_ky) = kernel_center(kernel);
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return image.clone();
+    }
+    let (half_kx, half_ky) = (half_kx as usize, half_ky as usize);
+    let (width, height) = (width as usize, height as usize);
+    let padded_width = width + 2 * half_kx;
+    let separable = separate_kernel(kernel);
+    let strip_rows = match tile_height {
+        0 => height,
+        rows => rows.min(height),
+    };
+
+    let mut output = vec![bias; width * height];
+    for (strip, strip_output) in output.chunks_mut(width * strip_rows).enumerate() {
+        let first_row = strip * strip_rows;
+        let padded = pad_gray(image, half_kx, half_ky, border, first_row..first_row + strip_output.len() / width);
+
+        match &separable {
+            Some((horizontal, vertical)) => {
+                // Horizontal pass over every padded row of the strip, so the vertical pass still has its border rows
+                let mut intermediate = vec![0.0f32; padded.len() / padded_width * width];
+                intermediate
+                    .par_chunks_mut(width)
+                    .zip(padded.par_chunks(padded_width))
+                    .for_each(|(row, source)| accumulate_row(row, source, horizontal));
+
+                strip_output.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+                    for (k, &weight) in vertical.iter().enumerate() {
+                        let source = &intermediate[(y + k) * width..][..width];
+                        row.iter_mut().zip(source).for_each(|(sum, &value)| *sum += value * weight);
+                    }
+                });
+            }
+            None => {
+                strip_output.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+                    for (ky, weights) in kernel.iter().enumerate() {
+                        accumulate_row(row, &padded[(y + ky) * padded_width..][..padded_width], weights);
+                    }
+                });
+            }
+        }
+    }
+
+    ImageBuffer::from_raw(width as u32, height as u32, output).expect("Buffer size matches the image dimensions")
+}
+
+/// Adds `weights[k] * source[x + k]` to each `row[x]`; `source` is the row padded by half the kernel on each side.
+fn accumulate_row(row: &mut [f32], source: &[f32], weights: &[f32]) {
+    for (k, &weight) in weights.iter().enumerate() {
+        row.iter_mut().zip(&source[k..]).for_each(|(sum, &value)| *sum += value * weight);
+    }
+}
+
+/// Copies output rows `rows` of a grayscale plane, plus `pad_y` rows above and below, into a buffer with `pad_x`
+/// columns of border on each side; rows and columns past the image are read according to `border`.
+fn pad_gray(image: &GrayImageF32, pad_x: usize, pad_y: usize, border: BorderMode, rows: Range<usize>) -> Vec<f32> {
+    let (width, height) = image.dimensions();
+    let fill = border.fill_value(1)[0] as f32;
+    let padded_width = width as usize + 2 * pad_x;
+    let columns: Vec<Option<u32>> = (0..padded_width).map(|x| border.resolve(x as i32 - pad_x as i32, width)).collect();
+
+    let mut padded = vec![fill; padded_width * (rows.len() + 2 * pad_y)];
+    padded.par_chunks_mut(padded_width).enumerate().for_each(|(y, row)| {
+        let Some(source_y) = border.resolve((rows.start + y) as i32 - pad_y as i32, height) else {
+            return;
+        };
+        let source = &image.as_raw()[source_y as usize * width as usize..][..width as usize];
+        for (value, column) in row.iter_mut().zip(&columns) {
+            if let Some(x) = column {
+                *value = source[*x as usize];
+            }
+        }
+    });
+    padded
+}
+
+/// Same as `apply_convolution` but keeps the raw f32 sums, so negative responses (e.g. gradients) survive.
+/// Works on any pixel type; the result holds one `[f32; N]` per pixel in row-major order.
+pub(crate) fn convolve_raw<P, const N: usize>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    kernel: &[Vec<f32>],
+    border: BorderMode,
+) -> Vec<[f32; N]>
+where
+    P: Pixel + Sync,
+    P::Subpixel: Channel,
+{
+    let (width, height) = image.dimensions();
+    let (half_kx, half_ky) = kernel_center(kernel);
+
+    let mut output = vec![[0.0; N]; (width * height) as usize];
+    output
+        .par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, sums) in row.iter_mut().enumerate() {
+                *sums = weighted_sum(image, kernel, border, x as u32, y as u32, half_kx, half_ky);
+            }
+        });
+
+    output
+}
+
+/// How many rows of a `width`-pixel image have their last pixel among the row-major pixel indices `pixels`,
+/// so a tile that stops mid-row only counts the rows it finished. Over all tiles this adds up to the height.
+fn rows_ending_in(pixels: Range<usize>, width: usize) -> usize {
+    pixels.end / width - pixels.start / width
+}
+
+/// Validates the kernel shape and returns its horizontal and vertical half sizes.
+/// Rows are the vertical extent and columns the horizontal one, so 1 x n and n x 1 kernels work too.
+fn kernel_center(kernel: &[Vec<f32>]) -> (i32, i32) {
+    if let Err(err) = validate_kernel(kernel) {
+        panic!("Invalid kernel: {}", err);
+    }
+    (kernel[0].len() as i32 / 2, kernel.len() as i32 / 2)
+}
+
+/// Applies the kernel over the neighborhood of (x, y) and returns the per-channel sums.
+fn weighted_sum<P, const N: usize>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    kernel: &[Vec<f32>],
+    border: BorderMode,
+    x: u32,
+    y: u32,
+    half_kx: i32,
+    half_ky: i32,
+) -> [f32; N]
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    let mut sums = [0.0; N];
+
+    for (ky, kernel_row) in kernel.iter().enumerate() {
+        for (kx, &weight) in kernel_row.iter().enumerate() {
+            let nx = x as i32 + kx as i32 - half_kx;
+            let ny = y as i32 + ky as i32 - half_ky;
+
+            let neighbor_pixel = border.sample(image, nx, ny);
+            for (sum, &channel) in sums.iter_mut().zip(neighbor_pixel.channels()) {
+                *sum += channel.to_f32() * weight;
+            }
+        }
+    }
+
+    sums
+}
+
+/// Convolves with the cheaper two-pass path when the kernel is separable (box, Gaussian, straight motion),
+/// falling back to the full 2D convolution otherwise. Kernels whose weights are all equal (box blurs)
+/// take the exact integer path instead.
+pub(crate) fn convolve(image: &RgbImage, kernel: &[Vec<f32>]) -> RgbImage {
+    if let Some(weight) = uniform_weight(kernel) {
+        return apply_uniform_convolution(image, kernel[0].len(), kernel.len(), weight);
+    }
+
+    match separate_kernel(kernel) {
+        Some((horizontal, vertical)) => apply_separable_convolution(image, &horizontal, &vertical, 0.0),
+        None => apply_convolution(image, kernel, BorderMode::Clamp, 0.0),
+    }
+}
+
+/// Blurs an RGBA image with premultiplied alpha (see `AlphaMode::Premultiplied`) through the same fast path as
+/// `convolve`: separable kernels (box, Gaussian and straight motion blurs) premultiply, take two 1D passes over all
+/// four channels and unpremultiply; only kernels that don't separate fall back to `apply_convolution_rgba`.
+pub(crate) fn convolve_rgba_premultiplied(image: &RgbaImage, kernel: &[Vec<f32>]) -> RgbaImage {
+    let Some((horizontal, vertical)) = separate_kernel(kernel) else {
+        return apply_convolution_rgba(image, kernel, BorderMode::Clamp, AlphaMode::Premultiplied);
+    };
+
+    let mut output: RgbaImage = ImageBuffer::new(image.width(), image.height());
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    separable_in_strips(&premultiply(image), &mut output, &horizontal, &vertical, TILE_HEIGHT.load(Ordering::Relaxed), BLOCKED_VERTICAL.load(Ordering::Relaxed), |sums: [f32; 4]| {
+        let [r, g, b, alpha] = unpremultiply(sums);
+        // Alpha is coverage, not color, so it is never soft clipped
+        [clip_u8(r, soft), clip_u8(g, soft), clip_u8(b, soft), clip_u8(alpha, false)]
+    });
+
+    output
+}
+
+/// Same as `convolve` on the f32 working format.
+pub(crate) fn convolve_float(image: &RgbImageF32, kernel: &[Vec<f32>]) -> RgbImageF32 {
+    match separate_kernel(kernel) {
+        Some((horizontal, vertical)) => apply_separable_convolution_f32(image, &horizontal, &vertical, 0.0),
+        None => apply_convolution_f32(image, kernel, BorderMode::Clamp, 0.0),
+    }
+}
+
+/// The shared weight of a kernel whose weights are all equal and non-zero, such as a box blur.
+fn uniform_weight(kernel: &[Vec<f32>]) -> Option<f32> {
+    let weight = *kernel.first()?.first()?;
+    let uniform = weight != 0.0 && kernel.iter().flatten().all(|&other| (other - weight).abs() <= weight.abs() * 1e-5);
+    uniform.then_some(weight)
+}
+
+/// Convolves with a `kernel_width x kernel_height` kernel whose weights all equal `weight`, accumulating
+/// the window sums in u64 (two passes, exact) and scaling once at the end. When the weights average
+/// (weight = 1 / count) the sum is divided with integer rounding, so there is no f32 drift at all.
+/// Edges are clamped and the work is done in strips of `set_tile_height` rows, like the separable path.
+pub fn apply_uniform_convolution(image: &RgbImage, kernel_width: usize, kernel_height: usize, weight: f32) -> RgbImage {
+    uniform_in_strips(image, kernel_width, kernel_height, weight, TILE_HEIGHT.load(Ordering::Relaxed))
+}
+
+/// `apply_uniform_convolution` in strips of `tile_height` output rows (0 for a single strip).
+fn uniform_in_strips(image: &RgbImage, kernel_width: usize, kernel_height: usize, weight: f32, tile_height: usize) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    if width == 0 || height == 0 {
+        return output;
+    }
+
+    let (half_x, half_y) = ((kernel_width / 2) as i32, (kernel_height / 2) as u32);
+    // u64 throughout: a window of 255s sums to 255·kw·kh, past u32 for windows a few thousand pixels on a side
+    let count = (kernel_width * kernel_height) as u64;
+    let averages = (weight * count as f32 - 1.0).abs() <= 1e-4;
+    let scale = |sum: u64| {
+        if averages {
+            ((sum + count / 2) / count) as u8
+        } else {
+            (sum as f32 * weight).round().clamp(0.0, 255.0) as u8
+        }
+    };
+
+    let strip_rows = match tile_height {
+        0 => height,
+        rows => (rows as u32).min(height),
+    };
+    let row_len = width as usize * 3;
+
+    for (strip, strip_output) in output.chunks_mut(row_len * strip_rows as usize).enumerate() {
+        let first_row = strip as u32 * strip_rows;
+        let band_start = first_row.saturating_sub(half_y);
+        let band_end = (first_row + strip_rows + half_y).min(height);
+
+        // Horizontal pass: per-row window sums for the strip and its halo
+        let mut row_sums = vec![[0u64; 3]; (band_end - band_start) as usize * width as usize];
+        row_sums
+            .par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(row, sums)| {
+                let y = band_start + row as u32;
+                for (x, sum) in sums.iter_mut().enumerate() {
+                    for k in -half_x..=half_x {
+                        let nx = (x as i32 + k).clamp(0, width as i32 - 1) as u32;
+                        for (total, &channel) in sum.iter_mut().zip(image.get_pixel(nx, y).0.iter()) {
+                            *total += channel as u64;
+                        }
+                    }
+                }
+            });
+
+        // Vertical pass: add up the row sums of the window, clamping against the whole image
+        strip_output
+            .par_chunks_mut(row_len)
+            .enumerate()
+            .for_each(|(row, pixels)| {
+                let y = first_row + row as u32;
+                for (x, pixel) in pixels.chunks_mut(3).enumerate() {
+                    let mut total = [0u64; 3];
+                    for k in -(half_y as i32)..=half_y as i32 {
+                        let ny = (y as i32 + k).clamp(0, height as i32 - 1) as u32;
+                        let sums = &row_sums[(ny - band_start) as usize * width as usize + x];
+                        for (total, sum) in total.iter_mut().zip(sums) {
+                            *total += sum;
+                        }
+                    }
+                    pixel.copy_from_slice(&total.map(scale));
+                }
+            });
+    }
+
+    output
+}
+
+/// Applies a separable kernel as a horizontal 1D pass followed by a vertical 1D pass using multi-threading.
+/// This costs O(k) per pixel instead of O(k²); the intermediate stays in f32 so only the final pass rounds.
+/// `bias` is added after the vertical pass, before clamping, matching `apply_convolution`.
+/// The output is produced in strips of `set_tile_height` rows, so the f32 intermediate only ever holds one strip.
+pub fn apply_separable_convolution(image: &RgbImage, horizontal: &[f32], vertical: &[f32], bias: f32) -> RgbImage {
+    let (width, height) = image.dimensions();
+
+    // Vertical pass: convolve each column of the intermediate and round back to u8
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    let soft = SOFT_CLIP.load(Ordering::Relaxed);
+    let (tile_height, blocked) = (TILE_HEIGHT.load(Ordering::Relaxed), BLOCKED_VERTICAL.load(Ordering::Relaxed));
+    separable_in_strips(image, &mut output, horizontal, vertical, tile_height, blocked, |sums: [f32; 3]| sums.map(|sum| clip_u8(sum + bias, soft)));
+
+    output
+}
+
+/// Same as `apply_separable_convolution` on the f32 working format, without rounding or clamping.
+pub fn apply_separable_convolution_f32(image: &RgbImageF32, horizontal: &[f32], vertical: &[f32], bias: f32) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+
+    let mut output: RgbImageF32 = ImageBuffer::new(width, height);
+    let (tile_height, blocked) = (TILE_HEIGHT.load(Ordering::Relaxed), BLOCKED_VERTICAL.load(Ordering::Relaxed));
+    separable_in_strips(image, &mut output, horizontal, vertical, tile_height, blocked, |sums: [f32; 3]| sums.map(|sum| sum + bias));
+
+    output
+}
+
+/// Runs both separable passes one horizontal strip of `tile_height` output rows at a time (0 for a single strip),
+/// over the first `N` channels of each pixel into the raw `output` buffer (`N` values per pixel). The vertical
+/// pass works on blocks of columns when `blocked` (see `set_blocked_vertical_pass`), otherwise pixel by pixel.
+/// Each strip's horizontal pass also covers the `half_v` rows above and below it (the halo the vertical
+/// pass reads), clamped to the image, so the result is identical to convolving the whole image at once.
+fn separable_in_strips<P, S, const N: usize>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    output: &mut [S],
+    horizontal: &[f32],
+    vertical: &[f32],
+    tile_height: usize,
+    blocked: bool,
+    store: impl Fn([f32; N]) -> [S; N] + Sync,
+) where
+    P: Pixel + Sync,
+    P::Subpixel: Channel,
+    S: Channel,
+{
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let half_v = vertical.len() as u32 / 2;
+    let strip_rows = match tile_height {
+        0 => height,
+        rows => (rows as u32).min(height),
+    };
+    let row_len = width as usize * N;
+
+    for (strip, strip_output) in output.chunks_mut(row_len * strip_rows as usize).enumerate() {
+        let first_row = strip as u32 * strip_rows;
+        let band_start = first_row.saturating_sub(half_v);
+        let band_end = (first_row + strip_rows + half_v).min(height);
+        let intermediate = horizontal_pass::<P, N>(image, horizontal, band_start..band_end);
+
+        strip_output
+            .par_chunks_mut(row_len)
+            .enumerate()
+            .for_each(|(row, pixels)| {
+                let y = first_row + row as u32;
+                if blocked {
+                    vertical_row_blocked(&intermediate, vertical, y, band_start, height, pixels, &store);
+                    return;
+                }
+                for (x, pixel) in pixels.chunks_mut(N).enumerate() {
+                    let sums = vertical_sum::<N>(&intermediate, row_len, vertical, x, y, band_start, height);
+                    pixel.copy_from_slice(&store(sums));
+                }
+            });
+    }
+}
+
+/// Horizontal pass of the separable convolution: convolves the first `N` channels of image rows `rows`
+/// into an f32 buffer (`N` values per pixel) whose first row is `rows.start`.
+fn horizontal_pass<P, const N: usize>(image: &ImageBuffer<P, Vec<P::Subpixel>>, horizontal: &[f32], rows: Range<u32>) -> Vec<f32>
+where
+    P: Pixel + Sync,
+    P::Subpixel: Channel,
+{
+    let width = image.width();
+    let half_h = horizontal.len() as i32 / 2;
+
+    let mut intermediate = vec![0.0f32; width as usize * rows.len() * N];
+    intermediate
+        .par_chunks_mut(width as usize * N)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_mut(N).enumerate() {
+                let mut sums = [0.0f32; N];
+
+                for (k, &weight) in horizontal.iter().enumerate() {
+                    let nx = (x as i32 + k as i32 - half_h).clamp(0, (width - 1) as i32) as u32;
+
+                    let neighbor_pixel = image.get_pixel(nx, rows.start + y as u32).channels();
+                    for (sum, &channel) in sums.iter_mut().zip(neighbor_pixel) {
+                        *sum += channel.to_f32() * weight;
+                    }
+                }
+
+                pixel.copy_from_slice(&sums);
+            }
+        });
+
+    intermediate
+}
+
+/// Vertical pass of the separable convolution at (x, y), reading the horizontal pass output `intermediate`
+/// (rows of `row_len` values). `y` is an image row; `band_start` is the image row stored first in `intermediate`.
+fn vertical_sum<const N: usize>(intermediate: &[f32], row_len: usize, vertical: &[f32], x: usize, y: u32, band_start: u32, height: u32) -> [f32; N] {
+    let half_v = vertical.len() as i32 / 2;
+
+    let mut sums = [0.0f32; N];
+    for (k, &weight) in vertical.iter().enumerate() {
+        // Clamp against the whole image, not the band, so strip edges read the same rows as the untiled pass
+        let ny = (y as i32 + k as i32 - half_v).clamp(0, (height - 1) as i32) as u32;
+
+        let neighbor_pixel = &intermediate[(ny - band_start) as usize * row_len + x * N..][..N];
+        for (sum, &value) in sums.iter_mut().zip(neighbor_pixel) {
+            *sum += value * weight;
+        }
+    }
+
+    sums
+}
+
+/// Vertical pass for a whole output row `y`, `COLUMN_BLOCK` pixels at a time: each source row of the window is
+/// read contiguously and added into the block's sums. Per value this is exactly the sum `vertical_sum` computes.
+fn vertical_row_blocked<S: Channel, const N: usize>(
+    intermediate: &[f32],
+    vertical: &[f32],
+    y: u32,
+    band_start: u32,
+    height: u32,
+    pixels: &mut [S],
+    store: impl Fn([f32; N]) -> [S; N],
+) {
+    let half_v = vertical.len() as i32 / 2;
+    let row_len = pixels.len();
+    // Room for a block of up to four channels
+    let mut sums = [0.0f32; COLUMN_BLOCK * 4];
+
+    for (block, block_pixels) in pixels.chunks_mut(COLUMN_BLOCK * N).enumerate() {
+        let start = block * COLUMN_BLOCK * N;
+        let sums = &mut sums[..block_pixels.len()];
+        sums.fill(0.0);
+
+        for (k, &weight) in vertical.iter().enumerate() {
+            let ny = (y as i32 + k as i32 - half_v).clamp(0, (height - 1) as i32) as u32;
+            let offset = (ny - band_start) as usize * row_len + start;
+            for (sum, &value) in sums.iter_mut().zip(&intermediate[offset..offset + block_pixels.len()]) {
+                *sum += value * weight;
+            }
+        }
+
+        for (pixel, sum) in block_pixels.chunks_mut(N).zip(sums.chunks(N)) {
+            pixel.copy_from_slice(&store(std::array::from_fn(|channel| sum[channel])));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::kernel::{generate_box_blur_kernel, generate_gaussian_kernel, separate_kernel, BlurKind};
+    use crate::pattern::{generate_test_image, TestPattern};
+    use crate::pipeline::Pipeline;
+    use image::DynamicImage;
+
+    /// Largest per-channel difference between two images of the same size.
+    fn max_difference(a: &RgbImage, b: &RgbImage) -> u8 {
+        a.as_raw().iter().zip(b.as_raw()).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0)
+    }
+
+    /// A 40x30 image with detail in every channel: a gradient with a checkerboard laid over its blue channel.
+    fn detailed_image() -> RgbImage {
+        let checker = generate_test_image(40, 30, TestPattern::Checkerboard { cell: 3 });
+        let mut image = generate_test_image(40, 30, TestPattern::Gradient);
+        for (pixel, checker) in image.pixels_mut().zip(checker.pixels()) {
+            pixel[2] = checker[0] / 2 + 64;
+        }
+        image
+    }
+
+    #[test]
+    fn separable_gaussian_matches_the_2d_convolution() {
+        let image = detailed_image();
+        let kernel = generate_gaussian_kernel(7, 1.5);
+        let (horizontal, vertical) = separate_kernel(&kernel).expect("a Gaussian kernel separates");
+
+        let separable = apply_separable_convolution(&image, &horizontal, &vertical, 0.0);
+        let full = apply_convolution(&image, &kernel, BorderMode::Clamp, 0.0);
+        assert!(max_difference(&separable, &full) <= 1);
+    }
+
+    #[test]
+    fn convolve_takes_a_fast_path_that_matches_the_2d_convolution() {
+        let image = detailed_image();
+        for kernel in [generate_box_blur_kernel(5), generate_gaussian_kernel(9, 2.0)] {
+            let full = apply_convolution(&image, &kernel, BorderMode::Clamp, 0.0);
+            assert!(max_difference(&convolve(&image, &kernel), &full) <= 1);
+        }
+    }
+
+    /// A 3x3 image whose pixels are 10, 20, ... 90 in reading order, the same in every channel.
+    fn numbered_3x3() -> RgbImage {
+        RgbImage::from_fn(3, 3, |x, y| Rgb([(10 * (y * 3 + x) + 10) as u8; 3]))
+    }
+
+    #[test]
+    fn corner_lookup_follows_the_border_mode() {
+        let image = numbered_3x3();
+        // (-1, -1) is diagonally past the top-left corner
+        let cases = [(BorderMode::Clamp, 10), (BorderMode::Reflect, 50), (BorderMode::Wrap, 90), (BorderMode::Constant(7), 7)];
+        for (border, expected) in cases {
+            assert_eq!(border.sample(&image, -1, -1).0, [expected; 3], "{:?}", border);
+
+            // A kernel that copies the top-left neighbor reads the same value at the corner pixel
+            let shift = vec![vec![1.0, 0.0, 0.0], vec![0.0; 3], vec![0.0; 3]];
+            assert_eq!(apply_convolution(&image, &shift, border, 0.0).get_pixel(0, 0).0, [expected; 3], "{:?}", border);
+        }
+    }
+
+    #[test]
+    fn lookups_past_the_opposite_corner() {
+        let image = numbered_3x3();
+        // (3, 4) is one column right and two rows below the bottom-right pixel
+        assert_eq!(BorderMode::Clamp.sample(&image, 3, 4).0, [90; 3]);
+        assert_eq!(BorderMode::Reflect.sample(&image, 3, 4).0, [20; 3]);
+        assert_eq!(BorderMode::Wrap.sample(&image, 3, 4).0, [40; 3]);
+        assert_eq!(BorderMode::Constant(7).sample(&image, 3, 4).0, [7; 3]);
+    }
+
+    #[test]
+    fn rectangular_kernels_blur_in_one_direction() {
+        // One-pixel vertical stripes: every column is flat, every row alternates
+        let stripes = RgbImage::from_fn(9, 6, |x, _| Rgb([if x % 2 == 0 { 200 } else { 0 }; 3]));
+        let horizontal = vec![vec![0.2; 5]];
+        let vertical = vec![vec![0.2]; 5];
+
+        // Along the rows the 1x5 kernel averages three bright and two dark columns (or the reverse)
+        let across = apply_convolution(&stripes, &horizontal, BorderMode::Clamp, 0.0);
+        for x in 2..7 {
+            let expected = if x % 2 == 0 { 120 } else { 80 };
+            for y in 0..6 {
+                assert_eq!(across.get_pixel(x, y).0, [expected; 3], "pixel ({}, {})", x, y);
+            }
+        }
+
+        // Down the columns the 5x1 kernel only ever sees one value
+        assert_eq!(apply_convolution(&stripes, &vertical, BorderMode::Clamp, 0.0), stripes);
+    }
+
+    #[test]
+    fn bias_shifts_a_zero_sum_kernel_to_mid_gray() {
+        let flat = RgbImage::from_pixel(6, 4, Rgb([200, 90, 10]));
+        let laplacian = crate::kernel::generate_laplacian_kernel();
+
+        assert_eq!(apply_convolution(&flat, &laplacian, BorderMode::Clamp, 0.0), RgbImage::from_pixel(6, 4, Rgb([0; 3])));
+        assert_eq!(apply_convolution(&flat, &laplacian, BorderMode::Clamp, 128.0), RgbImage::from_pixel(6, 4, Rgb([128; 3])));
+    }
+
+    #[test]
+    fn one_thread_gives_the_same_output_as_the_default_pool() {
+        let image = detailed_image();
+        let sharpen = crate::kernel::generate_sharpen_kernel(1.0);
+        let gaussian = generate_gaussian_kernel(5, 1.0);
+        let run = || (apply_convolution(&image, &sharpen, BorderMode::Reflect, 0.0), convolve(&image, &gaussian));
+
+        let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap().install(run);
+        assert_eq!(single, run());
+    }
+
+    #[test]
+    fn masked_out_channels_are_copied_through() {
+        let image = detailed_image();
+        let kernel = generate_box_blur_kernel(3);
+        let blurred = apply_convolution(&image, &kernel, BorderMode::Clamp, 0.0);
+        let masked = apply_convolution_masked(&image, &kernel, BorderMode::Clamp, 0.0, ChannelMask([false, true, true]));
+
+        for ((masked, original), blurred) in masked.pixels().zip(image.pixels()).zip(blurred.pixels()) {
+            assert_eq!(masked[0], original[0]);
+            assert_eq!(masked.0[1..], blurred.0[1..]);
+        }
+        // The checkerboard in blue really was blurred
+        assert_ne!(masked, image);
+    }
+
+    #[test]
+    fn sixteen_bit_values_survive_a_blur() {
+        // A ramp in steps of 1000, far past what 8 bits can hold
+        let ramp: RgbImage16 = ImageBuffer::from_fn(9, 3, |x, _| Rgb([x as u16 * 1000, 60000 - x as u16 * 1000, 300]));
+        let blurred = apply_convolution_u16(&ramp, &generate_box_blur_kernel(3), BorderMode::Clamp, 0.0);
+        // A box blur of a linear ramp returns the ramp away from the clamped ends
+        for x in 1..8 {
+            assert_eq!(blurred.get_pixel(x, 1).0, ramp.get_pixel(x, 1).0, "column {}", x);
+        }
+
+        let pipeline = Pipeline::from(Filter::Blur { size: 3, kind: BlurKind::Box, linear: false });
+        let DynamicImage::ImageRgb16(through_pipeline) = pipeline.apply_dynamic(DynamicImage::ImageRgb16(ramp.clone())) else {
+            panic!("the pipeline should keep 16 bits");
+        };
+        assert_eq!(through_pipeline.get_pixel(4, 1).0, [4000, 56000, 300]);
+    }
+
+    /// `apply_separable_convolution` in strips of `tile_height` rows, with the blocked or per-pixel vertical pass.
+    fn separable_with(image: &RgbImage, horizontal: &[f32], vertical: &[f32], tile_height: usize, blocked: bool) -> RgbImage {
+        let mut output = RgbImage::new(image.width(), image.height());
+        separable_in_strips(image, &mut output, horizontal, vertical, tile_height, blocked, |sums: [f32; 3]| sums.map(|sum| clip_u8(sum, false)));
+        output
+    }
+
+    #[test]
+    fn tiled_output_matches_the_untiled_path() {
+        let image = generate_test_image(300, 200, TestPattern::Checkerboard { cell: 7 });
+        let gray = gray_to_f32(&crate::color::to_luma8(&image));
+        let gaussian = generate_gaussian_kernel(9, 2.0);
+        let (horizontal, vertical) = separate_kernel(&gaussian).unwrap();
+        let sharpen = crate::kernel::generate_sharpen_kernel(1.0);
+        let run = |rows: usize| {
+            (
+                separable_with(&image, &horizontal, &vertical, rows, true),
+                uniform_in_strips(&image, 7, 7, 1.0 / 49.0, rows),
+                gray_in_strips(&gray, &gaussian, BorderMode::Reflect, 0.0, rows),
+                gray_in_strips(&gray, &sharpen, BorderMode::Clamp, 0.0, rows),
+            )
+        };
+
+        let whole = run(0);
+        for rows in [1, 7, 64] {
+            assert!(run(rows) == whole, "tile height {}", rows);
+        }
+    }
+
+    #[test]
+    fn sequential_mode_matches_the_parallel_result() {
+        let image = detailed_image();
+        let kernel = crate::kernel::generate_emboss_kernel();
+
+        let parallel = apply_convolution_with(&image, &kernel, BorderMode::Wrap, 128.0, false, Schedule::Auto);
+        let sequential = apply_convolution_with(&image, &kernel, BorderMode::Wrap, 128.0, true, Schedule::Auto);
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn integer_box_blur_is_exact_and_matches_the_f32_path() {
+        let image = detailed_image();
+        for (kernel_width, kernel_height) in [(3, 3), (5, 5), (7, 3), (1, 9)] {
+            let count = (kernel_width * kernel_height) as u32;
+            let kernel = vec![vec![1.0 / count as f32; kernel_width]; kernel_height];
+            let integer = apply_uniform_convolution(&image, kernel_width, kernel_height, 1.0 / count as f32);
+            assert!(max_difference(&integer, &apply_convolution(&image, &kernel, BorderMode::Clamp, 0.0)) <= 1);
+
+            // Every output is the rounded window average, with no rounding drift
+            let (half_x, half_y) = ((kernel_width / 2) as i32, (kernel_height / 2) as i32);
+            for (x, y, pixel) in integer.enumerate_pixels() {
+                for c in 0..3 {
+                    let mut sum = 0;
+                    for dy in -half_y..=half_y {
+                        for dx in -half_x..=half_x {
+                            let nx = (x as i32 + dx).clamp(0, image.width() as i32 - 1) as u32;
+                            let ny = (y as i32 + dy).clamp(0, image.height() as i32 - 1) as u32;
+                            sum += image.get_pixel(nx, ny)[c] as u32;
+                        }
+                    }
+                    assert_eq!(pixel[c] as u32, (sum + count / 2) / count, "pixel ({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn constant_border_color_bleeds_into_the_edges() {
+        let black = RgbImage::new(6, 6);
+        let blurred = apply_convolution(&black, &generate_box_blur_kernel(3), BorderMode::ConstantRgb([255, 0, 0]), 0.0);
+
+        // A corner sees 5 of its 9 neighbors outside the image, an edge pixel 3 and the inside none
+        assert_eq!(blurred.get_pixel(0, 0).0, [142, 0, 0]);
+        assert_eq!(blurred.get_pixel(3, 0).0, [85, 0, 0]);
+        assert_eq!(blurred.get_pixel(2, 3).0, [0, 0, 0]);
+        assert_eq!(apply_convolution(&black, &generate_box_blur_kernel(3), BorderMode::Clamp, 0.0), black);
+    }
+
+    #[test]
+    fn tiles_and_rows_give_identical_output() {
+        let image = detailed_image();
+        let kernel = crate::kernel::generate_emboss_kernel();
+
+        let rows = apply_convolution_with(&image, &kernel, BorderMode::Reflect, 128.0, false, Schedule::Rows);
+        let tiles = apply_convolution_with(&image, &kernel, BorderMode::Reflect, 128.0, false, Schedule::Tiles);
+        assert_eq!(rows, tiles);
+
+        assert_eq!(schedule_for(40, 30, Schedule::Auto), Schedule::Tiles);
+        assert_eq!(schedule_for(1024, 1024, Schedule::Auto), Schedule::Rows);
+        assert_eq!(schedule_for(40, 30, Schedule::Rows), Schedule::Rows);
+    }
+
+    #[test]
+    fn tiles_count_each_row_once_when_it_ends() {
+        // A 7-pixel-wide image in tiles of 5 pixels: rows end at indices 6, 13, 20 and 27
+        let tiles: Vec<usize> = (0..28).step_by(5).map(|start| rows_ending_in(start..(start + 5).min(28), 7)).collect();
+        assert_eq!(tiles, vec![0, 1, 1, 0, 1, 1]);
+        assert_eq!(rows_ending_in(0..28, 7), 4);
+    }
+
+    #[test]
+    fn blocked_vertical_pass_matches_the_per_pixel_pass() {
+        // Wider than two column blocks, so the last block is a partial one
+        let image = generate_test_image(COLUMN_BLOCK as u32 * 2 + 57, 24, TestPattern::Checkerboard { cell: 5 });
+        let (horizontal, vertical) = separate_kernel(&generate_gaussian_kernel(9, 2.0)).unwrap();
+
+        for tile_height in [0, 7] {
+            assert_eq!(
+                separable_with(&image, &horizontal, &vertical, tile_height, false),
+                separable_with(&image, &horizontal, &vertical, tile_height, true),
+                "tile height {}", tile_height,
+            );
+        }
+
+        // The f32 path keeps the unrounded sums, so any difference in the order of the additions would show
+        let image = to_f32(&image);
+        let mut outputs = [false, true].map(|_| RgbImageF32::new(image.width(), image.height()));
+        for (output, blocked) in outputs.iter_mut().zip([false, true]) {
+            separable_in_strips(&image, output, &horizontal, &vertical, DEFAULT_TILE_HEIGHT, blocked, |sums: [f32; 3]| sums);
+        }
+        assert_eq!(outputs[0], outputs[1]);
+    }
+
+    #[test]
+    fn soft_clip_keeps_midtones_and_compresses_the_ends() {
+        for value in [SOFT_CLIP_KNEE, 64.0, 128.0, 200.0, 255.0 - SOFT_CLIP_KNEE] {
+            assert_eq!(soft_clip(value), value);
+        }
+
+        // Overshoots stay distinct instead of all clipping to 255, and never leave the range
+        let (near, over, far) = (soft_clip(250.0), soft_clip(300.0), soft_clip(1000.0));
+        assert!(near < over && over < far && far <= 255.0, "{} {} {}", near, over, far);
+        assert!(clip_u8(250.0, true) < clip_u8(300.0, true));
+        assert_eq!((clip_u8(250.0, false), clip_u8(300.0, false)), (250, 255));
+        assert!(clip_u8(-40.0, true) > clip_u8(0.0, false) && clip_u8(-40.0, true) < clip_u8(10.0, true));
+
+        // The curve is continuous and rising through the knee
+        let knee = 255.0 - SOFT_CLIP_KNEE;
+        assert!((soft_clip(knee + 0.01) - (knee + 0.01)).abs() < 1e-3);
+        assert!(soft_clip(knee + 1.0) < knee + 1.0);
+    }
+
+    #[test]
+    fn transparent_border_does_not_darken_the_opaque_content() {
+        // An orange 6x6 square inside a 3 pixel border of transparent black
+        let image = RgbaImage::from_fn(12, 12, |x, y| {
+            if (3..9).contains(&x) && (3..9).contains(&y) { Rgba([200, 100, 50, 255]) } else { Rgba([0, 0, 0, 0]) }
+        });
+        let kernel = generate_box_blur_kernel(5);
+        let premultiplied = convolve_rgba_premultiplied(&image, &kernel);
+
+        // Wherever anything is visible the color stays orange; only the coverage fades out
+        for (x, y, pixel) in premultiplied.enumerate_pixels().filter(|(_, _, pixel)| pixel[3] > 0) {
+            assert!([200u8, 100, 50].iter().zip(&pixel.0).all(|(&expected, &channel)| expected.abs_diff(channel) <= 1), "pixel ({}, {}) is {:?}", x, y, pixel);
+        }
+        assert!(premultiplied.get_pixel(3, 3)[3] < 255);
+
+        // Blurring alpha as a plain fourth channel pulls the black in
+        let straight = apply_convolution_rgba(&image, &kernel, BorderMode::Clamp, AlphaMode::Convolve);
+        assert!(straight.get_pixel(3, 3)[0] < 150);
+        assert_eq!(apply_convolution_rgba(&image, &kernel, BorderMode::Clamp, AlphaMode::Premultiplied), premultiplied);
+    }
+}
@@ -0,0 +1,294 @@
+use image::{DynamicImage, ImageBuffer, Pixel, RgbImage};
+use rayon::prelude::*;
+
+use crate::blur::SummedAreaTable;
+use crate::color::to_luma8;
+use crate::convolution::{convolve_float, convolve_raw, to_f32, to_u8, BorderMode, RgbImageF32};
+use crate::kernel::{
+    clamp_kernel_size, generate_gaussian_kernel, generate_laplacian_kernel, generate_prewitt_kernels, generate_scharr_kernels,
+    generate_sobel_kernels, kernel_size_for_sigma,
+};
+
+/// First-derivative operator used by `Filter::Edges`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EdgeOperator {
+    /// 1-2-1 smoothing across the edge.
+    #[default]
+    Sobel,
+    /// Flat 1-1-1 smoothing across the edge.
+    Prewitt,
+    /// 3-10-3 smoothing; more accurate on diagonals, with 4x Sobel's response.
+    Scharr,
+}
+
+impl EdgeOperator {
+    /// The operator's horizontal and vertical gradient kernels, `(gx, gy)`.
+    pub fn kernels(self) -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+        match self {
+            EdgeOperator::Sobel => generate_sobel_kernels(),
+            EdgeOperator::Prewitt => generate_prewitt_kernels(),
+            EdgeOperator::Scharr => generate_scharr_kernels(),
+        }
+    }
+}
+
+/// Detects edges with the Sobel operator, combining the gradients per channel as sqrt(gx² + gy²).
+/// The magnitude can reach ~1140 on a full black/white step, so it is clamped to 255 (strong edges saturate to white).
+pub fn sobel_edges(image: &RgbImage) -> RgbImage {
+    operator_edges(image, EdgeOperator::Sobel)
+}
+
+/// Same as `sobel_edges` but runs on the luminance alone, doing a third of the work.
+/// The single edge value is written to all three channels.
+pub fn sobel_edges_luma(image: &RgbImage) -> RgbImage {
+    operator_edges_luma(image, EdgeOperator::Sobel)
+}
+
+/// Detects edges with the Prewitt operator; the magnitude is combined and clamped like `sobel_edges`.
+pub fn prewitt_edges(image: &RgbImage) -> RgbImage {
+    operator_edges(image, EdgeOperator::Prewitt)
+}
+
+/// Detects edges with the Scharr operator. Its weights sum to 16 against Sobel's 4, so edges saturate to white
+/// at a quarter of the contrast, but the response varies much less with edge orientation.
+pub fn scharr_edges(image: &RgbImage) -> RgbImage {
+    operator_edges(image, EdgeOperator::Scharr)
+}
+
+/// Per-channel gradient magnitude edges with any `EdgeOperator`.
+pub fn operator_edges(image: &RgbImage, operator: EdgeOperator) -> RgbImage {
+    let (kernel_x, kernel_y) = operator.kernels();
+    gradient_edges::<_, 3>(image, &kernel_x, &kernel_y)
+}
+
+/// Gradient magnitude edges on the luminance alone, written to all three channels.
+pub fn operator_edges_luma(image: &RgbImage, operator: EdgeOperator) -> RgbImage {
+    let (kernel_x, kernel_y) = operator.kernels();
+    let edges = gradient_edges::<_, 1>(&to_luma8(image), &kernel_x, &kernel_y);
+    DynamicImage::ImageLuma8(edges).into_rgb8()
+}
+
+/// Detects edges with the Laplacian (second derivative) kernel, shown as the absolute response per channel.
+/// The raw response is signed (dark on one side of an edge, bright on the other), so it is kept in f32 and
+/// folded with `abs` before clamping; this gives thin double-lined edges compared to Sobel.
+pub fn laplacian_edges(image: &RgbImage) -> RgbImage {
+    let response: Vec<[f32; 3]> = convolve_raw(image, &generate_laplacian_kernel(), BorderMode::Clamp);
+
+    let (width, height) = image.dimensions();
+    let mut output: RgbImage = ImageBuffer::new(width, height);
+    output
+        .par_chunks_mut(3)
+        .zip(response.par_iter())
+        .for_each(|(pixel, response)| {
+            for (channel, value) in pixel.iter_mut().zip(response) {
+                *channel = value.abs().round().clamp(0.0, 255.0) as u8;
+            }
+        });
+
+    output
+}
+
+/// Gray level `difference_of_gaussians` maps a zero response to, so both signs stay visible.
+pub const DOG_BIAS: f32 = 128.0;
+
+/// Difference of Gaussians: `gaussian(sigma1) - gaussian(sigma2)` per channel, a band-pass filter that keeps
+/// detail between the two scales. With `sigma1 < sigma2` edges appear as light/dark pairs around mid-gray
+/// (`DOG_BIAS`), and flat areas come out as plain mid-gray.
+pub fn difference_of_gaussians(image: &RgbImage, sigma1: f32, sigma2: f32) -> RgbImage {
+    to_u8(&difference_of_gaussians_f32(&to_f32(image), sigma1, sigma2))
+}
+
+/// Same as `difference_of_gaussians` in the f32 working format. The subtraction is done before any
+/// clamping, so the negative half of the response survives; only the bias is added.
+pub fn difference_of_gaussians_f32(image: &RgbImageF32, sigma1: f32, sigma2: f32) -> RgbImageF32 {
+    let (width, height) = image.dimensions();
+    let gaussian = |sigma: f32| {
+        let size = clamp_kernel_size(kernel_size_for_sigma(sigma), width, height);
+        convolve_float(image, &generate_gaussian_kernel(size, sigma))
+    };
+    let (mut output, wide) = rayon::join(|| gaussian(sigma1), || gaussian(sigma2));
+
+    output
+        .par_iter_mut()
+        .zip(wide.par_iter())
+        .for_each(|(narrow, wide)| *narrow = *narrow - wide + DOG_BIAS);
+    output
+}
+
+/// Convolves with a pair of gradient kernels and combines them per channel as a clamped magnitude.
+fn gradient_edges<P: Pixel<Subpixel = u8> + Sync + Send, const N: usize>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    kernel_x: &[Vec<f32>],
+    kernel_y: &[Vec<f32>],
+) -> ImageBuffer<P, Vec<u8>> {
+    // Keep the signed gradients in f32 so negative responses are not clipped away
+    let gx: Vec<[f32; N]> = convolve_raw(image, kernel_x, BorderMode::Clamp);
+    let gy: Vec<[f32; N]> = convolve_raw(image, kernel_y, BorderMode::Clamp);
+
+    let (width, height) = image.dimensions();
+    gradient_magnitude(width, height, &gx, &gy)
+}
+
+/// Combines per-pixel horizontal and vertical gradients as sqrt(gx² + gy²), clamped to 0–255.
+/// Takes the raw signed responses rather than 8-bit images, which would have clipped the negative half.
+fn gradient_magnitude<P: Pixel<Subpixel = u8> + Sync + Send, const N: usize>(
+    width: u32,
+    height: u32,
+    gx: &[[f32; N]],
+    gy: &[[f32; N]],
+) -> ImageBuffer<P, Vec<u8>> {
+    let mut output: ImageBuffer<P, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .par_chunks_mut(N)
+        .zip(gx.par_iter().zip(gy.par_iter()))
+        .for_each(|(pixel, (gx, gy))| {
+            for ((channel, gx), gy) in pixel.iter_mut().zip(gx).zip(gy) {
+                let magnitude = (gx * gx + gy * gy).sqrt();
+                *channel = magnitude.round().clamp(0.0, 255.0) as u8;
+            }
+        });
+
+    output
+}
+
+/// Texture map: the standard deviation of each channel over the `(2 * radius + 1)` square window around every
+/// pixel, doubled so the largest possible spread (half the window black, half white) is 255. Flat areas come out
+/// black and busy texture or edges bright, e.g. for detail masks. Edges are clamped like `apply_convolution`;
+/// sums and sums of squares come from summed-area tables, so the cost per pixel doesn't depend on the radius.
+/// A radius of 0 gives a black image.
+pub fn local_stddev(image: &RgbImage, radius: usize) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let table = SummedAreaTable::from_fn(width, height, radius, |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0.map(u64::from);
+        [r, g, b, r * r, g * g, b * b]
+    });
+
+    let size = 2 * radius + 1;
+    let area = (size * size) as u64;
+    let mut output = RgbImage::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let sums = table.window_sum(x as usize, y as usize, size);
+                for (channel, (sum, squares)) in pixel.0.iter_mut().zip(sums[..3].iter().zip(&sums[3..])) {
+                    // area² · variance, exact in integers
+                    let spread = area * squares - sum * sum;
+                    let deviation = (spread as f64).sqrt() / area as f64;
+                    *channel = (2.0 * deviation).round().min(255.0) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// A 10x6 image that is black in columns 0–4 and white in 5–9.
+    fn vertical_step() -> RgbImage {
+        RgbImage::from_fn(10, 6, |x, _| Rgb([if x < 5 { 0 } else { 255 }; 3]))
+    }
+
+    #[test]
+    fn sobel_responds_brightly_at_a_vertical_step() {
+        for edges in [sobel_edges(&vertical_step()), sobel_edges_luma(&vertical_step())] {
+            for (x, y, pixel) in edges.enumerate_pixels() {
+                // Only the two columns either side of the step have the boundary in their 3x3 window
+                let expected = if x == 4 || x == 5 { 255 } else { 0 };
+                assert_eq!(pixel.0, [expected; 3], "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn laplacian_responds_on_both_sides_of_a_step() {
+        let step = RgbImage::from_fn(10, 6, |x, _| Rgb([if x < 5 { 50 } else { 150 }; 3]));
+        for (x, y, pixel) in laplacian_edges(&step).enumerate_pixels() {
+            // -100 on the dark side and +100 on the bright side, folded with abs
+            let expected = if x == 4 || x == 5 { 100 } else { 0 };
+            assert_eq!(pixel.0, [expected; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn gradient_kernels_respond_to_their_own_direction() {
+        // Dark to bright steps, left to right and top to bottom, with small contrast so nothing clips
+        let vertical = RgbImage::from_fn(8, 8, |x, _| Rgb([if x < 4 { 100 } else { 110 }; 3]));
+        let horizontal = RgbImage::from_fn(8, 8, |_, y| Rgb([if y < 4 { 100 } else { 110 }; 3]));
+        let response = |image: &RgbImage, kernel: &[Vec<f32>]| {
+            crate::convolution::apply_convolution(image, kernel, BorderMode::Clamp, 128.0).get_pixel(3, 3)[0]
+        };
+
+        for operator in [EdgeOperator::Sobel, EdgeOperator::Prewitt] {
+            let (gx, gy) = operator.kernels();
+            // Sobel weighs the 10-level step by 1+2+1, Prewitt by 1+1+1
+            let weight = if operator == EdgeOperator::Sobel { 40 } else { 30 };
+            assert_eq!(response(&vertical, &gx), 128 + weight, "{:?} gx", operator);
+            assert_eq!(response(&vertical, &gy), 128, "{:?} gy", operator);
+            assert_eq!(response(&horizontal, &gx), 128, "{:?} gx", operator);
+            assert_eq!(response(&horizontal, &gy), 128 + weight, "{:?} gy", operator);
+        }
+    }
+
+    /// Mean error, in radians, of the gradient direction `operator` measures on the first channel against `angle`,
+    /// ignoring the clamped border.
+    fn direction_error(image: &RgbImage, operator: EdgeOperator, angle: f32) -> f32 {
+        let (gx, gy) = operator.kernels();
+        let (gx, gy): (Vec<[f32; 3]>, Vec<[f32; 3]>) = (convolve_raw(image, &gx, BorderMode::Clamp), convolve_raw(image, &gy, BorderMode::Clamp));
+        let width = image.width() as usize;
+        let interior: Vec<f32> = (0..gx.len())
+            .filter(|index| (2..width - 2).contains(&(index % width)) && (2..width - 2).contains(&(index / width)))
+            .filter(|&index| gx[index][0].hypot(gy[index][0]) > 1.0)
+            .map(|index| {
+                // Orientation only, so the error wraps at 180°
+                let error = (gy[index][0].atan2(gx[index][0]) - angle).rem_euclid(std::f32::consts::PI);
+                error.min(std::f32::consts::PI - error)
+            })
+            .collect();
+        interior.iter().sum::<f32>() / interior.len() as f32
+    }
+
+    #[test]
+    fn scharr_measures_oblique_edges_more_accurately_than_sobel() {
+        // Ripples running at 22.5°, about six pixels apart; on straight ramps both operators are exact
+        let angle = 22.5f32.to_radians();
+        let ripples = RgbImage::from_fn(24, 24, |x, y| {
+            let along = x as f32 * angle.cos() + y as f32 * angle.sin();
+            Rgb([(128.0 + 100.0 * along.sin()).round() as u8; 3])
+        });
+
+        let sobel = direction_error(&ripples, EdgeOperator::Sobel, angle);
+        let scharr = direction_error(&ripples, EdgeOperator::Scharr, angle);
+        assert!(scharr < sobel / 2.0, "Scharr {} vs Sobel {}", scharr, sobel);
+    }
+
+    #[test]
+    fn difference_of_gaussians_of_a_flat_image_is_mid_gray() {
+        let flat = RgbImage::from_pixel(24, 16, Rgb([90, 140, 200]));
+        let response = difference_of_gaussians_f32(&to_f32(&flat), 1.0, 2.0);
+        assert!(response.pixels().flat_map(|pixel| pixel.0).all(|value| (value - DOG_BIAS).abs() < 1e-3));
+        assert!(difference_of_gaussians(&flat, 1.0, 2.0).pixels().all(|pixel| pixel.0 == [128; 3]));
+
+        // An edge does respond, on both sides of the bias
+        let edges = difference_of_gaussians(&vertical_step(), 1.0, 2.0);
+        let row: Vec<u8> = (0..10).map(|x| edges.get_pixel(x, 3)[0]).collect();
+        assert!(row.iter().any(|&value| value < 128) && row.iter().any(|&value| value > 128), "{:?}", row);
+    }
+
+    #[test]
+    fn local_stddev_is_zero_on_flat_areas_and_high_on_texture() {
+        // Flat on the left, a one-pixel checkerboard on the right
+        let image = RgbImage::from_fn(20, 10, |x, y| Rgb([if x < 10 { 90 } else if (x + y) % 2 == 0 { 255 } else { 0 }; 3]));
+        let texture = local_stddev(&image, 2);
+
+        assert!((0..10).all(|y| (0..7).all(|x| texture.get_pixel(x, y).0 == [0; 3])));
+        // Away from the clamped edges, 13 of the 25 window pixels are at one extreme and 12 at the other: nearly the largest spread
+        assert!((2..8).all(|y| (12..18).all(|x| texture.get_pixel(x, y)[0] >= 250)));
+        assert!(local_stddev(&image, 0).pixels().all(|pixel| pixel.0 == [0; 3]));
+    }
+}
@@ -0,0 +1,151 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::blur::bilateral_filter;
+use crate::color::posterize;
+use crate::edge::{operator_edges_luma, EdgeOperator};
+use crate::rank::median_filter;
+
+/// Darkens the image towards the corners with a smooth falloff. Distance from the center is normalized by
+/// half the diagonal, so the falloff is circular and reaches the corners on any aspect ratio.
+/// The center pixel is unchanged and the corners are scaled by `1 - strength` (0 is no effect, 1 is black).
+pub fn apply_vignette(image: &RgbImage, strength: f32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let strength = strength.clamp(0.0, 1.0);
+
+    // Pixel centers, so the middle pixel of an odd-sized image is exactly at distance 0
+    let center_x = (width as f32 - 1.0) / 2.0;
+    let center_y = (height as f32 - 1.0) / 2.0;
+    let half_diagonal = center_x.hypot(center_y).max(f32::EPSILON);
+
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let distance = (x as f32 - center_x).hypot(y as f32 - center_y) / half_diagonal;
+                // Smoothstep: flat near the center, steepest halfway out, flat again at the corners
+                let falloff = distance * distance * (3.0 - 2.0 * distance);
+                let factor = 1.0 - strength * falloff;
+
+                let source = image.get_pixel(x, y);
+                pixel.0 = source.0.map(|channel| (channel as f32 * factor).round().clamp(0.0, 255.0) as u8);
+            }
+        });
+
+    output
+}
+
+/// Mosaic effect: averages each `block x block` square and fills it with that color.
+/// Blocks along the right and bottom margins are cut short by the image edge and average only the pixels they cover.
+pub fn pixelate(image: &RgbImage, block: u32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let block = block.max(1);
+    let blocks_x = width.div_ceil(block);
+    let blocks_y = height.div_ceil(block);
+
+    // One average per block, computed a row of blocks at a time
+    let averages: Vec<[u8; 3]> = (0..blocks_y)
+        .into_par_iter()
+        .flat_map_iter(|by| {
+            (0..blocks_x).map(move |bx| {
+                let (x0, y0) = (bx * block, by * block);
+                let (x1, y1) = ((x0 + block).min(width), (y0 + block).min(height));
+
+                let mut sums = [0u64; 3];
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        for (sum, &channel) in sums.iter_mut().zip(image.get_pixel(x, y).0.iter()) {
+                            *sum += channel as u64;
+                        }
+                    }
+                }
+
+                let count = ((x1 - x0) * (y1 - y0)) as u64;
+                sums.map(|sum| ((sum + count / 2) / count) as u8)
+            })
+        })
+        .collect();
+
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                pixel.0 = averages[((y / block) * blocks_x + x / block) as usize];
+            }
+        });
+
+    output
+}
+
+/// Cartoon look: colors flattened by median and bilateral smoothing, posterized to `levels` per channel,
+/// with black outlines wherever the Sobel edge magnitude of the smoothed luminance exceeds `edge_threshold`
+/// (lower thresholds draw more outlines).
+pub fn cartoonify(image: &RgbImage, levels: u16, edge_threshold: u8) -> RgbImage {
+    // The median pass removes speckles that would otherwise turn into stray outline dots
+    let smoothed = bilateral_filter(&median_filter(image, 2), 3.0, 30.0);
+    let edges = operator_edges_luma(&smoothed, EdgeOperator::Sobel);
+
+    let mut output = posterize(&smoothed, levels);
+    output
+        .par_chunks_mut(3)
+        .zip(edges.par_chunks(3))
+        .for_each(|(pixel, edge)| {
+            if edge[0] > edge_threshold {
+                pixel.fill(0);
+            }
+        });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn vignette_keeps_the_center_and_darkens_corners_by_strength() {
+        let image = RgbImage::from_pixel(21, 11, Rgb([200, 100, 50]));
+        for strength in [0.0, 0.25, 0.5, 1.0] {
+            let vignetted = apply_vignette(&image, strength);
+            assert_eq!(vignetted.get_pixel(10, 5).0, [200, 100, 50]);
+            let expected = [200.0, 100.0, 50.0].map(|channel: f32| (channel * (1.0 - strength)).round() as u8);
+            for (x, y) in [(0, 0), (20, 0), (0, 10), (20, 10)] {
+                assert_eq!(vignetted.get_pixel(x, y).0, expected, "strength {} at ({}, {})", strength, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn one_block_covering_the_image_is_its_average_color() {
+        // Two columns of (0, 100, 200) and two of (100, 200, 0)
+        let image = RgbImage::from_fn(4, 4, |x, _| Rgb(if x < 2 { [0, 100, 200] } else { [100, 200, 0] }));
+        assert_eq!(pixelate(&image, 4), RgbImage::from_pixel(4, 4, Rgb([50, 150, 100])));
+        assert_eq!(pixelate(&image, 1), image);
+    }
+
+    #[test]
+    fn cartoon_flattens_colors_and_outlines_edges() {
+        // A bright square on a dark, slightly graded background, so the input has plenty of distinct colors
+        let image = RgbImage::from_fn(40, 40, |x, y| {
+            if (12..28).contains(&x) && (12..28).contains(&y) {
+                Rgb([230, 200 + (x % 4) as u8, 60])
+            } else {
+                Rgb([(20 + x) as u8, (30 + y) as u8, 80])
+            }
+        });
+        let cartoon = cartoonify(&image, 4, 64);
+
+        let colors = |image: &RgbImage| image.pixels().map(|pixel| pixel.0).collect::<std::collections::HashSet<_>>().len();
+        assert!(colors(&cartoon) < colors(&image), "{} vs {} colors", colors(&cartoon), colors(&image));
+
+        // The square's left edge is drawn black, its middle and the far background are not
+        assert!((14..26).any(|y| (10..14).any(|x| cartoon.get_pixel(x, y).0 == [0; 3])));
+        assert_ne!(cartoon.get_pixel(20, 20).0, [0; 3]);
+        assert_ne!(cartoon.get_pixel(2, 2).0, [0; 3]);
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::io;
+
+use image::error::ImageFormatHint;
+use image::ImageError;
+
+use crate::filter::ParseFilterError;
+use crate::kernel::KernelError;
+use crate::transform::CropError;
+
+/// Everything that can go wrong in the file-level functions of this crate, such as `blur_image` or `save_image`.
+/// Validators of in-memory values (`crop`, `validate_kernel`, filter spec parsing) return their own
+/// more detailed error types, which convert into this one with `?`.
+#[derive(Debug)]
+pub enum Error {
+    /// A file or stream could not be read or written.
+    Io(io::Error),
+    /// The input is not a valid image, or it could not be encoded.
+    ImageDecode(ImageError),
+    /// The image format (e.g. from the file extension) is not supported for reading or writing.
+    UnsupportedFormat(String),
+    /// A custom kernel has bad values or an unusable shape.
+    InvalidKernel(KernelError),
+    /// A parameter such as a crop rectangle or a filter spec is out of range or malformed.
+    InvalidArgument(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::ImageDecode(err) => write!(f, "{}", err),
+            Error::UnsupportedFormat(format) => write!(f, "unsupported image format: {}", format),
+            Error::InvalidKernel(err) => write!(f, "invalid kernel: {}", err),
+            Error::InvalidArgument(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::ImageDecode(err) => Some(err),
+            Error::InvalidKernel(err) => Some(err),
+            Error::UnsupportedFormat(_) | Error::InvalidArgument(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Sorts `image` errors into the matching variant, so callers can tell a missing file from a corrupt one.
+impl From<ImageError> for Error {
+    fn from(err: ImageError) -> Self {
+        match err {
+            ImageError::IoError(err) => Error::Io(err),
+            ImageError::Unsupported(err) => Error::UnsupportedFormat(match err.format_hint() {
+                ImageFormatHint::Exact(format) => format!("{:?}", format),
+                ImageFormatHint::Name(name) => name,
+                ImageFormatHint::PathExtension(extension) => format!(".{}", extension.to_string_lossy()),
+                _ => err.to_string(),
+            }),
+            ImageError::Parameter(err) => Error::InvalidArgument(err.to_string()),
+            err => Error::ImageDecode(err),
+        }
+    }
+}
+
+impl From<KernelError> for Error {
+    fn from(err: KernelError) -> Self {
+        Error::InvalidKernel(err)
+    }
+}
+
+impl From<CropError> for Error {
+    fn from(err: CropError) -> Self {
+        Error::InvalidArgument(err.to_string())
+    }
+}
+
+impl From<ParseFilterError> for Error {
+    fn from(err: ParseFilterError) -> Self {
+        Error::InvalidArgument(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GrayImage, RgbImage, RgbaImage};
+    use crate::filter::Filter;
+    use crate::{crop, merge_channels, save_image, write_image, OutputFormat};
+
+    #[test]
+    fn unknown_output_extension_is_an_unsupported_format() {
+        let path = std::env::temp_dir().join(format!("kernel-rs-{}-unsupported.xyz", std::process::id()));
+        let result = save_image(DynamicImage::ImageRgb8(RgbImage::new(2, 2)), &path.to_string_lossy());
+        assert!(matches!(&result, Err(Error::UnsupportedFormat(format)) if format == ".xyz"), "{:?}", result);
+    }
+
+    #[test]
+    fn bad_parameters_are_invalid_arguments() {
+        let transparent = DynamicImage::ImageRgba8(RgbaImage::new(2, 2));
+        assert!(matches!(write_image(&transparent, &mut Vec::new(), OutputFormat::Jpeg), Err(Error::InvalidArgument(_))));
+
+        let mismatched = merge_channels(&GrayImage::new(2, 2), &GrayImage::new(2, 2), &GrayImage::new(3, 2));
+        assert!(matches!(mismatched, Err(Error::InvalidArgument(_))));
+
+        let outside = crop(&RgbImage::new(4, 4), 2, 2, 4, 4).map_err(Error::from);
+        assert!(matches!(outside, Err(Error::InvalidArgument(_))));
+        let typo = "blurr:3".parse::<Filter>().map_err(Error::from);
+        assert!(matches!(typo, Err(Error::InvalidArgument(message)) if message.contains("blurr")));
+    }
+
+    #[test]
+    fn kernel_errors_keep_their_details() {
+        let err = Error::from(KernelError::Empty);
+        assert!(matches!(err, Error::InvalidKernel(KernelError::Empty)));
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(err.to_string(), format!("invalid kernel: {}", KernelError::Empty));
+    }
+}
@@ -0,0 +1,508 @@
+use std::fmt;
+use std::str::FromStr;
+
+use image::{DynamicImage, GrayImage, RgbImage, RgbaImage};
+
+use crate::convolution::*;
+use crate::blur::{bilateral_filter, chroma_blur, chroma_blur_f32, kuwahara_filter, linear_light_blur, linear_light_blur_f32};
+use crate::color::{adjust_brightness_contrast, adjust_saturation, apply_lut, dither_floyd_steinberg, gamma_lut, invert, posterize, sepia, to_grayscale};
+use crate::effect::{apply_vignette, cartoonify, pixelate};
+use crate::edge::{difference_of_gaussians, difference_of_gaussians_f32, laplacian_edges, local_stddev, operator_edges, operator_edges_luma, EdgeOperator};
+use crate::histogram::{equalize_histogram, equalize_histogram_per_channel};
+use crate::kernel::*;
+use crate::rank::{box_range, dilate, erode, median_filter};
+use crate::sharpen::{guided_sharpen, sharpen_conservative, unsharp_mask};
+use crate::threshold::{adaptive_threshold, threshold};
+use crate::EMBOSS_BIAS;
+
+/// A filter together with its parameters, as selected from the menu or the command line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Box or Gaussian blur with an odd kernel size, optionally averaged in linear light.
+    Blur { size: usize, kind: BlurKind, linear: bool },
+    /// 3x3 sharpen; `strength` 0 is the identity and 1 the classic kernel.
+    /// `conservative` keeps each result within its neighborhood's range, so edges get no halos.
+    Sharpen { strength: f32, conservative: bool },
+    /// Directional motion blur of `length` pixels at `angle` degrees (0 is horizontal).
+    MotionBlur { length: usize, angle: f32 },
+    /// Gradient magnitude edge map, optionally computed on luminance alone (faster, grayscale result).
+    Edges { operator: EdgeOperator, luma: bool },
+    /// Absolute Laplacian response (second-derivative edges).
+    Laplacian,
+    /// Difference of Gaussians band-pass, `gaussian(sigma1) - gaussian(sigma2)`, centered on mid-gray.
+    DifferenceOfGaussians { sigma1: f32, sigma2: f32 },
+    /// Per-channel standard deviation over a `(2 * radius + 1)` square window, doubled: a texture map.
+    LocalStddev { radius: usize },
+    /// Emboss relief centered on mid-gray.
+    Emboss,
+    /// Luminance written to all three channels.
+    Grayscale,
+    /// Unsharp mask with Gaussian `sigma` and boost `amount`.
+    UnsharpMask { sigma: f32, amount: f32 },
+    /// Gaussian blur of odd size `chroma_size` on the YCbCr chroma and `luma_size` on the luminance (1 keeps it sharp).
+    ChromaBlur { luma_size: usize, chroma_size: usize },
+    /// Edge-aware detail boost by `amount` over a self-guided filter with window radius `radius` and
+    /// edge threshold `epsilon` (0–1 variance scale); no halos along strong edges.
+    GuidedSharpen { radius: usize, epsilon: f32, amount: f32 },
+    /// Per-channel median over a `(2 * radius + 1)` square window.
+    Median { radius: usize },
+    /// Per-channel maximum over a `(2 * radius + 1)` square window.
+    Dilate { radius: usize },
+    /// Per-channel minimum over a `(2 * radius + 1)` square window.
+    Erode { radius: usize },
+    /// Per-channel maximum minus minimum over a `(2 * radius + 1)` square window.
+    BoxRange { radius: usize },
+    /// Edge-preserving blur weighted by distance (`spatial_sigma`) and color difference (`range_sigma`).
+    Bilateral { spatial_sigma: f32, range_sigma: f32 },
+    /// Painterly smoothing: the mean of the calmest of four `(radius + 1)` square quadrants around each pixel.
+    Kuwahara { radius: usize },
+    /// Black and white split at a fixed luminance `level`.
+    Threshold { level: u8 },
+    /// Black and white split at the local mean of a `block_size` square minus `c`.
+    AdaptiveThreshold { block_size: usize, c: f32 },
+    /// Brightness offset and contrast factor around mid-gray.
+    BrightnessContrast { brightness: i32, contrast: f32 },
+    /// HSV saturation scaled by `factor`.
+    Saturation { factor: f32 },
+    /// Histogram equalization on luminance (keeps colors) or on each channel separately.
+    Equalize { per_channel: bool },
+    /// Photographic negative.
+    Invert,
+    /// Warm brown old-photo tone.
+    Sepia,
+    /// Mosaic of `block x block` squares filled with their average color.
+    Pixelate { block: u32 },
+    /// Darkened corners; `strength` 0 is no effect and 1 turns the corners black.
+    Vignette { strength: f32 },
+    /// Gamma tone curve; above 1 brightens the midtones, 1 is the identity.
+    Gamma { gamma: f32 },
+    /// Each channel reduced to `levels` evenly spaced values.
+    Posterize { levels: u16 },
+    /// Each channel reduced to `levels` values with Floyd–Steinberg error diffusion.
+    Dither { levels: u16 },
+    /// Smoothed, posterized colors with black outlines where edges exceed `edge_threshold`.
+    Cartoon { levels: u16, edge_threshold: u8 },
+}
+
+impl Filter {
+    /// Applies the filter to an in-memory image.
+    pub fn apply(&self, image: &RgbImage) -> RgbImage {
+        match *self {
+            Filter::Blur { size, kind, linear: false } => convolve(image, &blur_kernel(size, kind, image.dimensions())),
+            Filter::Blur { size, kind, linear: true } => linear_light_blur(image, &blur_kernel(size, kind, image.dimensions())),
+            Filter::Sharpen { strength, conservative: false } => apply_convolution(image, &generate_sharpen_kernel(strength), BorderMode::Clamp, 0.0),
+            Filter::Sharpen { strength, conservative: true } => sharpen_conservative(image, strength),
+            Filter::MotionBlur { length, angle } => convolve(image, &motion_blur_kernel(length, angle, image.dimensions())),
+            Filter::Edges { operator, luma: false } => operator_edges(image, operator),
+            Filter::Edges { operator, luma: true } => operator_edges_luma(image, operator),
+            Filter::Laplacian => laplacian_edges(image),
+            Filter::DifferenceOfGaussians { sigma1, sigma2 } => difference_of_gaussians(image, sigma1, sigma2),
+            Filter::LocalStddev { radius } => local_stddev(image, radius),
+            Filter::Emboss => apply_convolution(image, &generate_emboss_kernel(), BorderMode::Clamp, EMBOSS_BIAS),
+            Filter::Grayscale => to_grayscale(image),
+            Filter::UnsharpMask { sigma, amount } => unsharp_mask(image, sigma, amount),
+            Filter::GuidedSharpen { radius, epsilon, amount } => guided_sharpen(image, radius, epsilon, amount),
+            Filter::ChromaBlur { luma_size, chroma_size } => chroma_blur(image, odd(luma_size), odd(chroma_size)),
+            Filter::Median { radius } => median_filter(image, radius),
+            Filter::Dilate { radius } => dilate(image, radius),
+            Filter::Erode { radius } => erode(image, radius),
+            Filter::BoxRange { radius } => box_range(image, radius),
+            Filter::Bilateral { spatial_sigma, range_sigma } => bilateral_filter(image, spatial_sigma, range_sigma),
+            Filter::Kuwahara { radius } => kuwahara_filter(image, radius),
+            Filter::Threshold { level } => threshold(image, level),
+            Filter::AdaptiveThreshold { block_size, c } => adaptive_threshold(image, odd(block_size), c),
+            Filter::BrightnessContrast { brightness, contrast } => adjust_brightness_contrast(image, brightness, contrast),
+            Filter::Saturation { factor } => adjust_saturation(image, factor),
+            Filter::Equalize { per_channel: false } => equalize_histogram(image),
+            Filter::Equalize { per_channel: true } => equalize_histogram_per_channel(image),
+            Filter::Invert => invert(image),
+            Filter::Sepia => sepia(image),
+            Filter::Pixelate { block } => pixelate(image, block),
+            Filter::Vignette { strength } => apply_vignette(image, strength),
+            Filter::Gamma { gamma } => apply_lut(image, &gamma_lut(gamma)),
+            Filter::Posterize { levels } => posterize(image, levels),
+            Filter::Dither { levels } => dither_floyd_steinberg(image, levels),
+            Filter::Cartoon { levels, edge_threshold } => cartoonify(image, levels, edge_threshold),
+        }
+    }
+
+    /// Applies the filter to an image in the f32 working format, without clamping.
+    /// Convolution-based filters run natively in f32; the rest round-trip through 8 bits.
+    pub fn apply_f32(&self, image: &RgbImageF32) -> RgbImageF32 {
+        match *self {
+            Filter::Blur { size, kind, linear: false } => convolve_float(image, &blur_kernel(size, kind, image.dimensions())),
+            Filter::Blur { size, kind, linear: true } => linear_light_blur_f32(image, &blur_kernel(size, kind, image.dimensions())),
+            Filter::Sharpen { strength, conservative: false } => apply_convolution_f32(image, &generate_sharpen_kernel(strength), BorderMode::Clamp, 0.0),
+            Filter::MotionBlur { length, angle } => convolve_float(image, &motion_blur_kernel(length, angle, image.dimensions())),
+            Filter::Emboss => apply_convolution_f32(image, &generate_emboss_kernel(), BorderMode::Clamp, EMBOSS_BIAS),
+            Filter::DifferenceOfGaussians { sigma1, sigma2 } => difference_of_gaussians_f32(image, sigma1, sigma2),
+            Filter::ChromaBlur { luma_size, chroma_size } => chroma_blur_f32(image, odd(luma_size), odd(chroma_size)),
+            _ => to_f32(&self.apply(&to_u8(image))),
+        }
+    }
+
+    /// Applies the filter to an RGBA image without losing transparency.
+    /// Blurs convolve alpha along with color so soft edges stay soft, with premultiplied alpha so the hidden color of
+    /// transparent pixels doesn't bleed into visible ones (see `AlphaMode::Premultiplied`), taking the same separable
+    /// fast path as RGB blurs; every other filter (including linear-light blurs) runs on the color channels and keeps
+    /// the original alpha.
+    pub fn apply_rgba(&self, image: &RgbaImage) -> RgbaImage {
+        match *self {
+            Filter::Blur { size, kind, linear: false } => convolve_rgba_premultiplied(image, &blur_kernel(size, kind, image.dimensions())),
+            Filter::MotionBlur { length, angle } => convolve_rgba_premultiplied(image, &motion_blur_kernel(length, angle, image.dimensions())),
+            _ => {
+                let color = DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+                let mut output = DynamicImage::ImageRgb8(self.apply(&color)).into_rgba8();
+                for (pixel, original) in output.pixels_mut().zip(image.pixels()) {
+                    pixel.0[3] = original[3];
+                }
+                output
+            }
+        }
+    }
+
+    /// Applies a convolution-based filter to a single-channel image at a third of the RGB cost.
+    /// Returns `None` for filters without a grayscale path; run those on an RGB copy instead.
+    pub fn apply_gray(&self, image: &GrayImage) -> Option<GrayImage> {
+        let (kernel, bias) = self.gray_kernel(image.dimensions())?;
+        Some(apply_convolution_luma8(image, &kernel, BorderMode::Clamp, bias))
+    }
+
+    /// Same as `apply_gray` in the f32 working format, without clamping.
+    pub fn apply_gray_f32(&self, image: &GrayImageF32) -> Option<GrayImageF32> {
+        let (kernel, bias) = self.gray_kernel(image.dimensions())?;
+        Some(apply_convolution_gray_f32(image, &kernel, BorderMode::Clamp, bias))
+    }
+
+    /// Whether `apply_gray` handles this filter.
+    pub(crate) fn has_gray_path(&self) -> bool {
+        matches!(
+            self,
+            Filter::Blur { linear: false, .. } | Filter::Sharpen { conservative: false, .. } | Filter::MotionBlur { .. } | Filter::Emboss
+        )
+    }
+
+    /// Kernel and bias of the filters that run on one channel the same way they do on each RGB channel.
+    fn gray_kernel(&self, dimensions: (u32, u32)) -> Option<(Vec<Vec<f32>>, f32)> {
+        match *self {
+            Filter::Blur { size, kind, linear: false } => Some((blur_kernel(size, kind, dimensions), 0.0)),
+            Filter::Sharpen { strength, conservative: false } => Some((generate_sharpen_kernel(strength), 0.0)),
+            Filter::MotionBlur { length, angle } => Some((motion_blur_kernel(length, angle, dimensions), 0.0)),
+            Filter::Emboss => Some((generate_emboss_kernel(), EMBOSS_BIAS)),
+            _ => None,
+        }
+    }
+
+    /// The kernel the filter convolves each channel with, e.g. for `render_kernel`. Unsharp masks and
+    /// Differences of Gaussians are listed as the single kernel they amount to: `(1 + amount) * identity - amount *
+    /// gaussian` and `gaussian(sigma1) - gaussian(sigma2)`. `None` for filters that are not one convolution,
+    /// including edge maps, which combine two gradient kernels.
+    pub fn kernel(&self) -> Option<Vec<Vec<f32>>> {
+        let gaussian = |sigma: f32| generate_gaussian_kernel(kernel_size_for_sigma(sigma), sigma);
+        match *self {
+            Filter::Blur { size, kind, .. } => Some(generate_blur_kernel(odd(size), resolve_blur_kind(odd(size), kind))),
+            Filter::Sharpen { strength, conservative: false } => Some(generate_sharpen_kernel(strength)),
+            Filter::MotionBlur { length, angle } => Some(generate_motion_blur_kernel(odd(length), angle)),
+            Filter::Laplacian => Some(generate_laplacian_kernel()),
+            Filter::Emboss => Some(generate_emboss_kernel()),
+            Filter::UnsharpMask { sigma, amount } if sigma > 0.0 => {
+                let mut blur = gaussian(sigma);
+                blur.iter_mut().flatten().for_each(|weight| *weight *= amount);
+                Some(kernel_difference(&[vec![1.0 + amount]], &blur))
+            }
+            Filter::DifferenceOfGaussians { sigma1, sigma2 } => Some(kernel_difference(&gaussian(sigma1), &gaussian(sigma2))),
+            _ => None,
+        }
+    }
+
+    /// How many pixels past the edge of a `width x height` image the filter reads: half its `kernel_size`,
+    /// capped at the image size (kernels are clamped to fit the image anyway). Pixelate works in blocks and reads none.
+    pub fn border_reach(&self, (width, height): (u32, u32)) -> u32 {
+        match self {
+            Filter::Pixelate { .. } => 0,
+            _ => self.kernel_size().map_or(0, |size| (size / 2).min(width.max(height) as usize) as u32),
+        }
+    }
+
+    /// Side length of the square neighborhood each output pixel reads, or `None` for per-pixel filters.
+    pub fn kernel_size(&self) -> Option<usize> {
+        match *self {
+            Filter::Blur { size, .. } => Some(odd(size)),
+            Filter::MotionBlur { length, .. } => Some(odd(length)),
+            Filter::Sharpen { .. } | Filter::Edges { .. } | Filter::Laplacian | Filter::Emboss => Some(3),
+            Filter::Grayscale | Filter::Threshold { .. } | Filter::BrightnessContrast { .. } => None,
+            Filter::Saturation { .. } | Filter::Invert | Filter::Sepia => None,
+            Filter::Equalize { .. } | Filter::Vignette { .. } | Filter::Gamma { .. } => None,
+            Filter::Posterize { .. } | Filter::Dither { .. } => None,
+            Filter::AdaptiveThreshold { block_size, .. } => Some(odd(block_size)),
+            // Gaussian-based filters cover ±3σ like `unsharp_mask` and `bilateral_filter`
+            Filter::UnsharpMask { sigma, .. } => Some(kernel_size_for_sigma(sigma)),
+            Filter::Bilateral { spatial_sigma, .. } => Some(kernel_size_for_sigma(spatial_sigma)),
+            // The bilateral pass inside `cartoonify` has the widest reach
+            Filter::Cartoon { .. } => Some(kernel_size_for_sigma(3.0)),
+            Filter::DifferenceOfGaussians { sigma1, sigma2 } => Some(kernel_size_for_sigma(sigma1.max(sigma2))),
+            Filter::Median { radius } | Filter::Dilate { radius } | Filter::Erode { radius } | Filter::BoxRange { radius } | Filter::Kuwahara { radius } | Filter::LocalStddev { radius } => Some(2 * radius + 1),
+            // The window averages are averaged again over the same window
+            Filter::GuidedSharpen { radius, .. } => Some(4 * radius + 1),
+            Filter::ChromaBlur { luma_size, chroma_size } => Some(odd(luma_size.max(chroma_size))),
+            Filter::Pixelate { block } => Some(block as usize),
+        }
+    }
+
+    /// The suffix appended to the input file stem when naming the output (e.g. `blurred_5`).
+    pub fn suffix(&self) -> String {
+        match *self {
+            Filter::Blur { size, kind: BlurKind::Box, linear: false } => format!("blurred_{}", odd(size)),
+            Filter::Blur { size, kind: BlurKind::Box, linear: true } => format!("blurred_linear_{}", odd(size)),
+            Filter::Blur { size, kind: BlurKind::Gaussian { .. }, linear: false } => format!("gaussian_{}", odd(size)),
+            Filter::Blur { size, kind: BlurKind::Gaussian { .. }, linear: true } => format!("gaussian_linear_{}", odd(size)),
+            Filter::Sharpen { conservative: false, .. } => "sharpened".to_string(),
+            Filter::Sharpen { conservative: true, .. } => "sharpened_conservative".to_string(),
+            Filter::MotionBlur { length, .. } => format!("motion_{}", odd(length)),
+            Filter::Edges { operator: EdgeOperator::Sobel, .. } => "edges".to_string(),
+            Filter::Edges { operator: EdgeOperator::Prewitt, .. } => "edges_prewitt".to_string(),
+            Filter::Edges { operator: EdgeOperator::Scharr, .. } => "edges_scharr".to_string(),
+            Filter::Laplacian => "laplacian".to_string(),
+            Filter::DifferenceOfGaussians { .. } => "bandpass".to_string(),
+            Filter::LocalStddev { radius } => format!("stddev_{}", radius),
+            Filter::Emboss => "embossed".to_string(),
+            Filter::Grayscale => "grayscale".to_string(),
+            Filter::UnsharpMask { .. } => "unsharp".to_string(),
+            Filter::GuidedSharpen { .. } => "guided".to_string(),
+            Filter::ChromaBlur { chroma_size, .. } => format!("chroma_{}", odd(chroma_size)),
+            Filter::Median { radius } => format!("median_{}", radius),
+            Filter::Dilate { radius } => format!("dilated_{}", radius),
+            Filter::Erode { radius } => format!("eroded_{}", radius),
+            Filter::BoxRange { radius } => format!("boxrange_{}", radius),
+            Filter::Bilateral { .. } => "bilateral".to_string(),
+            Filter::Kuwahara { radius } => format!("kuwahara_{}", radius),
+            Filter::Threshold { level } => format!("threshold_{}", level),
+            Filter::AdaptiveThreshold { block_size, .. } => format!("adaptive_{}", odd(block_size)),
+            Filter::BrightnessContrast { .. } => "adjusted".to_string(),
+            Filter::Saturation { .. } => "saturated".to_string(),
+            Filter::Equalize { .. } => "equalized".to_string(),
+            Filter::Invert => "inverted".to_string(),
+            Filter::Sepia => "sepia".to_string(),
+            Filter::Pixelate { block } => format!("pixelated_{}", block),
+            Filter::Vignette { .. } => "vignette".to_string(),
+            Filter::Gamma { .. } => "gamma".to_string(),
+            Filter::Posterize { levels } => format!("posterized_{}", levels),
+            Filter::Dither { levels } => format!("dithered_{}", levels),
+            Filter::Cartoon { .. } => "cartoon".to_string(),
+        }
+    }
+}
+
+/// Error returned when a filter or pipeline spec such as `blur:5,sharpen` cannot be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseFilterError {
+    message: String,
+}
+
+impl ParseFilterError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        ParseFilterError { message: message.into() }
+    }
+}
+
+impl fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ParseFilterError {}
+
+impl FromStr for Filter {
+    type Err = ParseFilterError;
+
+    /// Parses `name[:param[:param]]`, e.g. `blur:5`, `gaussian:7:1.5`, `motion:9:45` or `edges:prewitt:gray`.
+    /// Blurs also take a trailing `:linear`, e.g. `gaussian:7:linear`, and sharpen a trailing `:conservative`.
+    /// Omitted parameters take the same defaults as the command-line flags.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut parts = spec.trim().split(':');
+        let name = parts.next().unwrap_or_default();
+        let mut params = Params { spec, values: parts.collect() };
+
+        // Strip the flags first so they don't count against the numeric parameters
+        let linear = matches!(name, "blur" | "gaussian") && params.values.last() == Some(&"linear");
+        let conservative = name == "sharpen" && params.values.last() == Some(&"conservative");
+        if linear || conservative {
+            params.values.pop();
+        }
+
+        let (filter, arity) = match name {
+            "blur" => (Filter::Blur { size: params.size(0, 5)?, kind: BlurKind::Box, linear }, 1),
+            "gaussian" => (Filter::Blur { size: params.size(0, 5)?, kind: BlurKind::Gaussian { sigma: params.number(1, 0.0)? }, linear }, 2),
+            "sharpen" => (Filter::Sharpen { strength: params.number(0, 1.0)?, conservative }, 1),
+            "motion" => (Filter::MotionBlur { length: params.size(0, 9)?, angle: params.number(1, 0.0)? }, 2),
+            "edges" => {
+                // An optional operator name, then an optional `gray`
+                let (operator, rest) = match params.values.split_first() {
+                    Some((&"sobel", rest)) => (EdgeOperator::Sobel, rest),
+                    Some((&"prewitt", rest)) => (EdgeOperator::Prewitt, rest),
+                    Some((&"scharr", rest)) => (EdgeOperator::Scharr, rest),
+                    _ => (EdgeOperator::Sobel, params.values.as_slice()),
+                };
+                match rest {
+                    [] => (Filter::Edges { operator, luma: false }, params.values.len()),
+                    ["gray"] => (Filter::Edges { operator, luma: true }, params.values.len()),
+                    _ => {
+                        return Err(ParseFilterError::new(format!(
+                            "Invalid edges option in '{}': expected edges[:sobel|:prewitt|:scharr][:gray]",
+                            spec
+                        )))
+                    }
+                }
+            }
+            "laplacian" => (Filter::Laplacian, 0),
+            "dog" => (Filter::DifferenceOfGaussians { sigma1: params.number(0, 1.0)?, sigma2: params.number(1, 2.0)? }, 2),
+            "stddev" => (Filter::LocalStddev { radius: params.number(0, 2)? }, 1),
+            "emboss" => (Filter::Emboss, 0),
+            "grayscale" => (Filter::Grayscale, 0),
+            "saturate" => (Filter::Saturation { factor: params.number(0, 1.5)? }, 1),
+            "equalize" => match params.values.as_slice() {
+                [] => (Filter::Equalize { per_channel: false }, 0),
+                ["channels"] => (Filter::Equalize { per_channel: true }, 1),
+                _ => return Err(ParseFilterError::new(format!("Invalid equalize option in '{}': expected 'equalize' or 'equalize:channels'", spec))),
+            },
+            "invert" => (Filter::Invert, 0),
+            "sepia" => (Filter::Sepia, 0),
+            "pixelate" => (Filter::Pixelate { block: params.size(0, 16)? as u32 }, 1),
+            "vignette" => (Filter::Vignette { strength: params.number(0, 0.5)? }, 1),
+            "gamma" => (Filter::Gamma { gamma: params.number(0, 2.2)? }, 1),
+            "posterize" => (Filter::Posterize { levels: params.number(0, 4)? }, 1),
+            "dither" => (Filter::Dither { levels: params.number(0, 2)? }, 1),
+            "cartoon" => (Filter::Cartoon { levels: params.number(0, 8)?, edge_threshold: params.number(1, 64)? }, 2),
+            "threshold" => (Filter::Threshold { level: params.number(0, 128)? }, 1),
+            "adaptive" => (Filter::AdaptiveThreshold { block_size: params.size(0, 15)?, c: params.number(1, 5.0)? }, 2),
+            "adjust" => (Filter::BrightnessContrast { brightness: params.number(0, 0)?, contrast: params.number(1, 1.0)? }, 2),
+            "unsharp" => (Filter::UnsharpMask { sigma: params.number(0, 1.0)?, amount: params.number(1, 1.0)? }, 2),
+            "guided" => (Filter::GuidedSharpen { radius: params.number(0, 2)?, epsilon: params.number(1, 0.01)?, amount: params.number(2, 1.0)? }, 3),
+            "chroma" => (Filter::ChromaBlur { luma_size: params.size(0, 1)?, chroma_size: params.size(1, 9)? }, 2),
+            "median" => (Filter::Median { radius: params.number(0, 1)? }, 1),
+            "kuwahara" => (Filter::Kuwahara { radius: params.number(0, 2)? }, 1),
+            "dilate" | "boxmax" => (Filter::Dilate { radius: params.number(0, 1)? }, 1),
+            "erode" | "boxmin" => (Filter::Erode { radius: params.number(0, 1)? }, 1),
+            "boxrange" => (Filter::BoxRange { radius: params.number(0, 1)? }, 1),
+            "bilateral" => (Filter::Bilateral { spatial_sigma: params.number(0, 3.0)?, range_sigma: params.number(1, 25.0)? }, 2),
+            _ => return Err(ParseFilterError::new(format!("Unknown filter '{}'", name))),
+        };
+
+        if params.values.len() > arity {
+            return Err(ParseFilterError::new(format!("Too many parameters in '{}': {} takes at most {}", spec, name, arity)));
+        }
+
+        Ok(filter)
+    }
+}
+
+/// The `:`-separated parameters of a single filter spec.
+struct Params<'a> {
+    spec: &'a str,
+    values: Vec<&'a str>,
+}
+
+impl Params<'_> {
+    /// Parses the parameter at `index`, or returns `default` when it was omitted.
+    fn number<T: FromStr>(&self, index: usize, default: T) -> Result<T, ParseFilterError> {
+        match self.values.get(index) {
+            None => Ok(default),
+            Some(raw) => raw
+                .parse()
+                .map_err(|_| ParseFilterError::new(format!("Invalid parameter '{}' in '{}'", raw, self.spec))),
+        }
+    }
+
+    /// Parses a kernel size, which must be a positive whole number.
+    fn size(&self, index: usize, default: usize) -> Result<usize, ParseFilterError> {
+        match self.number(index, default)? {
+            0 => Err(ParseFilterError::new(format!("Size must be at least 1 in '{}'", self.spec))),
+            size => Ok(size),
+        }
+    }
+}
+
+/// Rounds even kernel sizes up so the kernel has a center pixel.
+fn odd(size: usize) -> usize {
+    if size.is_multiple_of(2) { size + 1 } else { size }
+}
+
+/// The blur kernel for an image of the given dimensions, no larger than it needs (see `clamp_kernel_size`).
+/// A Gaussian without an explicit sigma keeps the one of the requested size, so clamping doesn't weaken it.
+pub(crate) fn blur_kernel(size: usize, kind: BlurKind, (width, height): (u32, u32)) -> Vec<Vec<f32>> {
+    let size = odd(size);
+    generate_blur_kernel(clamp_kernel_size(size, width, height), resolve_blur_kind(size, kind))
+}
+
+/// Gives a Gaussian without a sigma the one that spans ±3σ over `size`.
+fn resolve_blur_kind(size: usize, kind: BlurKind) -> BlurKind {
+    match kind {
+        BlurKind::Gaussian { sigma } if sigma <= 0.0 => BlurKind::Gaussian { sigma: size as f32 / 6.0 },
+        kind => kind,
+    }
+}
+
+/// `a - b` for two odd square kernels of possibly different sizes, lined up on their centers.
+fn kernel_difference(a: &[Vec<f32>], b: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let size = a.len().max(b.len());
+    let at = |kernel: &[Vec<f32>], x: usize, y: usize| {
+        let offset = (size - kernel.len()) / 2;
+        let (x, y) = (x.checked_sub(offset), y.checked_sub(offset));
+        x.zip(y).and_then(|(x, y)| kernel.get(y)?.get(x).copied()).unwrap_or(0.0)
+    };
+    (0..size).map(|y| (0..size).map(|x| at(a, x, y) - at(b, x, y)).collect()).collect()
+}
+
+/// Same as `blur_kernel` for a motion blur of `length` pixels.
+pub(crate) fn motion_blur_kernel(length: usize, angle: f32, (width, height): (u32, u32)) -> Vec<Vec<f32>> {
+    generate_motion_blur_kernel(clamp_kernel_size(odd(length), width, height), angle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::LogLevel;
+    use crate::pattern::{generate_test_image, TestPattern};
+    use image::Rgb;
+
+    #[test]
+    fn emboss_centers_flat_areas_on_the_bias() {
+        let flat = RgbImage::from_pixel(5, 5, Rgb([60; 3]));
+        // The emboss weights sum to 0, so without the bias a flat area would be black
+        assert_eq!(apply_convolution(&flat, &generate_emboss_kernel(), BorderMode::Clamp, 0.0), RgbImage::from_pixel(5, 5, Rgb([0; 3])));
+        assert_eq!(Filter::Emboss.apply(&flat), RgbImage::from_pixel(5, 5, Rgb([128; 3])));
+
+        // Brightness rising towards the bottom-right is raised above the bias
+        let ramp = RgbImage::from_fn(5, 5, |x, y| Rgb([(20 * (x + y)) as u8; 3]));
+        assert!(Filter::Emboss.apply(&ramp).get_pixel(2, 2)[0] > 128);
+    }
+
+    #[test]
+    fn sharpen_strength_zero_returns_the_original() {
+        // A mid-gray checkerboard, so sharpening has room to overshoot
+        let image = Filter::BrightnessContrast { brightness: 0, contrast: 0.4 }.apply(&generate_test_image(12, 12, TestPattern::Checkerboard { cell: 2 }));
+        for conservative in [false, true] {
+            assert_eq!(Filter::Sharpen { strength: 0.0, conservative }.apply(&image), image);
+        }
+
+        // Any other strength does change a textured image
+        assert_ne!(Filter::Sharpen { strength: 0.5, conservative: false }.apply(&image), image);
+    }
+
+    #[test]
+    fn huge_blur_on_a_tiny_image_is_capped() {
+        let image = generate_test_image(4, 4, TestPattern::Gradient);
+        crate::log::tests::take_records();
+
+        // A 99x99 kernel would re-read the clamped border 600 times per pixel; 7 already sees the whole image
+        for kind in [BlurKind::Box, BlurKind::Gaussian { sigma: 0.0 }] {
+            assert_eq!(blur_kernel(99, kind, image.dimensions()).len(), 7);
+        }
+        let blurred = Filter::Blur { size: 99, kind: BlurKind::Box, linear: false }.apply(&image);
+        assert_eq!(blurred, Filter::Blur { size: 7, kind: BlurKind::Box, linear: false }.apply(&image));
+
+        let records = crate::log::tests::take_records();
+        assert!(records.contains(&(LogLevel::Warn, "Kernel size 99 is larger than the 4x4 image needs; using 7.".to_string())), "{:?}", records);
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Whether `--input` is a wildcard pattern such as `photos/*.jpg` rather than a single file.
+pub fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Expands a wildcard pattern into the existing files it matches, sorted by path.
+/// Each path component may use `*` (any run of characters), `?` (one character) and `[abc]`, `[a-z]` or `[!a]`
+/// (one character from, or not from, a set); wildcards never match `/`, and hidden files only match a
+/// pattern that starts with `.`. Expanding here rather than relying on the shell also works where the shell
+/// leaves patterns alone, such as the Windows command prompt, or when the pattern is quoted.
+pub fn expand(pattern: &str) -> Vec<PathBuf> {
+    let pattern = Path::new(pattern);
+    let mut candidates = vec![PathBuf::new()];
+
+    for component in pattern.components() {
+        let Component::Normal(part) = component else {
+            // Roots, prefixes, `.` and `..` are kept as they are
+            candidates.iter_mut().for_each(|candidate| candidate.push(component));
+            continue;
+        };
+
+        let part = part.to_string_lossy();
+        if !is_pattern(&part) {
+            candidates.iter_mut().for_each(|candidate| candidate.push(&*part));
+            continue;
+        }
+
+        candidates = candidates
+            .iter()
+            .flat_map(|directory| {
+                let listed = if directory.as_os_str().is_empty() { Path::new(".") } else { directory.as_path() };
+                // Unreadable folders simply match nothing
+                let mut names: Vec<String> = fs::read_dir(listed)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .filter(|name| matches(&part, name))
+                    .collect();
+                names.sort();
+                names.into_iter().map(move |name| directory.join(name))
+            })
+            .collect();
+    }
+
+    let mut files: Vec<PathBuf> = candidates.into_iter().filter(|path| path.is_file()).collect();
+    files.sort();
+    files
+}
+
+/// Whether a single file name matches one pattern component.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_from(&pattern, &name)
+}
+
+fn matches_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        // Try every split point; file names are short, so backtracking stays cheap
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches_from(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches_from(rest, &name[1..]),
+        Some(('[', rest)) => match (name.split_first(), class_end(rest)) {
+            (Some((&c, name)), Some(end)) => in_class(&rest[..end], c) && matches_from(&rest[end + 1..], name),
+            (None, Some(_)) => false,
+            // An unclosed `[` is an ordinary character
+            (_, None) => name.first() == Some(&'[') && matches_from(rest, &name[1..]),
+        },
+        Some((&literal, rest)) => name.first() == Some(&literal) && matches_from(rest, &name[1..]),
+    }
+}
+
+/// A pattern that matches `literal` and nothing else: each wildcard character becomes a one-character class.
+pub fn escape(literal: &str) -> String {
+    literal.chars().map(|c| if matches!(c, '*' | '?' | '[') { format!("[{}]", c) } else { c.to_string() }).collect()
+}
+
+/// Index of the `]` closing a character class that starts right after `[`; a `]` in first place is a member.
+fn class_end(class: &[char]) -> Option<usize> {
+    let start = if class.first() == Some(&'!') { 1 } else { 0 };
+    (start + 1..class.len()).find(|&index| class[index] == ']')
+}
+
+/// Whether `c` is in a class body such as `abc`, `a-z` or `!0-9`.
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some(('!', rest)) => (true, rest),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut index = 0;
+    while index < class.len() {
+        if index + 2 < class.len() && class[index + 1] == '-' {
+            found |= (class[index]..=class[index + 2]).contains(&c);
+            index += 3;
+        } else {
+            found |= class[index] == c;
+            index += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn pattern_matches_the_two_files_it_names() {
+        let dir = env::temp_dir().join(format!("kernel-rs-{}-glob", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for name in ["b.jpg", "a.jpg", "c.png", ".hidden.jpg", "nested/d.jpg"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let pattern = dir.join("*.jpg");
+        assert_eq!(expand(&pattern.to_string_lossy()), vec![dir.join("a.jpg"), dir.join("b.jpg")]);
+        assert_eq!(expand(&dir.join("*/*.jpg").to_string_lossy()), vec![dir.join("nested/d.jpg")]);
+        assert!(expand(&dir.join("*.gif").to_string_lossy()).is_empty());
+    }
+
+    #[test]
+    fn wildcards_match_within_one_name() {
+        assert!(matches("*.jpg", "cat.jpg"));
+        assert!(!matches("*.jpg", "cat.jpeg"));
+        assert!(matches("img_??.png", "img_07.png"));
+        assert!(!matches("img_??.png", "img_7.png"));
+        assert!(matches("[a-c]at.png", "bat.png"));
+        assert!(!matches("[!a-c]at.png", "bat.png"));
+        assert!(!matches("*", ".hidden"));
+        assert!(matches(".*", ".hidden"));
+        assert!(matches("[x.png", "[x.png"));
+        assert!(matches(&escape("a*[b]?.png"), "a*[b]?.png"));
+        assert!(!matches(&escape("a*.png"), "ab.png"));
+        assert!(is_pattern("photos/*.jpg") && !is_pattern("photos/cat.jpg"));
+    }
+}
@@ -0,0 +1,108 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::color::luminance;
+
+/// Spreads the luminance histogram across the full 0-255 range to boost contrast.
+/// Each pixel's R, G and B are scaled by the same factor, so color ratios (and thus hues) are kept.
+pub fn equalize_histogram(image: &RgbImage) -> RgbImage {
+    let luma = |pixel: &Rgb<u8>| luminance(pixel).round().clamp(0.0, 255.0) as usize;
+
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[luma(pixel)] += 1;
+    }
+    let lut = equalization_lut(&histogram);
+
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let input = image.get_pixel(x, y);
+                let level = luma(input);
+
+                // Pure black has no color ratio to keep, so it just becomes the mapped gray
+                pixel.0 = if level == 0 {
+                    [lut[0]; 3]
+                } else {
+                    let scale = lut[level] as f32 / level as f32;
+                    input.0.map(|channel| (channel as f32 * scale).round().clamp(0.0, 255.0) as u8)
+                };
+            }
+        });
+
+    output
+}
+
+/// Equalizes R, G and B independently. Stretches contrast harder than `equalize_histogram` but can shift colors.
+pub fn equalize_histogram_per_channel(image: &RgbImage) -> RgbImage {
+    let mut histograms = [[0usize; 256]; 3];
+    for pixel in image.pixels() {
+        for (histogram, &channel) in histograms.iter_mut().zip(pixel.0.iter()) {
+            histogram[channel as usize] += 1;
+        }
+    }
+    let luts = histograms.map(|histogram| equalization_lut(&histogram));
+
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                for ((channel, &value), lut) in pixel.0.iter_mut().zip(image.get_pixel(x, y).0.iter()).zip(&luts) {
+                    *channel = lut[value as usize];
+                }
+            }
+        });
+
+    output
+}
+
+/// Maps each level through the normalized cumulative histogram, so the darkest used level
+/// lands on 0 and the brightest on 255. A single-level image is left unchanged.
+fn equalization_lut(histogram: &[usize; 256]) -> [u8; 256] {
+    let total: usize = histogram.iter().sum();
+    let cdf_min = histogram.iter().copied().find(|&count| count > 0).unwrap_or(0);
+    if total == cdf_min {
+        return std::array::from_fn(|level| level as u8);
+    }
+
+    let mut lut = [0u8; 256];
+    let mut cumulative = 0;
+    for (entry, &count) in lut.iter_mut().zip(histogram.iter()) {
+        cumulative += count;
+        let scaled = cumulative.saturating_sub(cdf_min) as f32 / (total - cdf_min) as f32;
+        *entry = (scaled * 255.0).round() as u8;
+    }
+
+    lut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// Darkest and brightest channel value in the image.
+    fn value_range(image: &RgbImage) -> (u8, u8) {
+        let raw = image.as_raw();
+        (*raw.iter().min().unwrap(), *raw.iter().max().unwrap())
+    }
+
+    #[test]
+    fn equalization_stretches_a_low_contrast_gradient() {
+        // A gray ramp squeezed into 100–140
+        let dull = RgbImage::from_fn(41, 4, |x, _| Rgb([100 + x as u8; 3]));
+        for equalized in [equalize_histogram(&dull), equalize_histogram_per_channel(&dull)] {
+            let (low, high) = value_range(&equalized);
+            assert!(low < 20 && high == 255, "spans {}–{}", low, high);
+            // Brightness order is kept
+            assert!(equalized.get_pixel(10, 0)[0] < equalized.get_pixel(30, 0)[0]);
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use image::{DynamicImage, GrayImage};
+use kernel_rs::{apply_convolution_gray_f32, apply_kernel, apply_to_luma, crop_dynamic, debug, filter_gif_frames, is_gif_path, fit_dimensions, flip_dynamic, limit_dimensions, load_image_from_reader, merge_luma, merge_masked, replace_image, resize, save_image_as, resize_dynamic, rotate_dynamic, save_image, validate_kernel, warn, BorderMode, ChannelMask, ColorSpace, Error, Flip, Noise, OutputFormat, Pipeline, ResampleMode, Rotation};
+
+/// `--input` value that reads the image from stdin instead of a file.
+pub const STDIN_PATH: &str = "-";
+
+/// Decodes the input image, reading it from stdin when the path is `-`.
+pub fn load_input(image_path: &str) -> Result<DynamicImage, Error> {
+    if image_path == STDIN_PATH {
+        load_image_from_reader(io::stdin().lock())
+    } else {
+        Ok(image::open(image_path)?)
+    }
+}
+
+/// Saves a result, replacing the input safely (see `replace_image`) when `output_path` is the input itself, e.g. with `--in-place`.
+/// A `format` (from `--format`) picks the encoder instead of the extension of `output_path`.
+pub fn save_output(image: DynamicImage, input_path: &str, output_path: &str, format: Option<OutputFormat>) -> Result<(), Error> {
+    if let Some(format) = format {
+        save_image_as(&image, output_path, format)
+    } else if input_path == output_path {
+        replace_image(image, output_path)
+    } else {
+        save_image(image, output_path)
+    }
+}
+
+/// What the command line asked to do to each image.
+pub struct Job {
+    /// Region from `--crop x,y,w,h`, cut out before the operation.
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Mirroring from `--flip`, applied after cropping.
+    pub flip: Option<Flip>,
+    /// Quarter turns from `--rotate`, applied after flipping.
+    pub rotation: Option<Rotation>,
+    /// Longest side allowed from `--max-dimension`; larger images are downscaled after rotating, before the noise and operation.
+    pub max_dimension: Option<u32>,
+    /// Synthetic noise from `--add-noise`, added after cropping and before the operation.
+    pub noise: Option<Noise>,
+    /// Seed for `noise`, from `--seed`.
+    pub seed: u64,
+    pub operation: Operation,
+    /// From `--luma-only`: keep only the operation's luminance changes, with the input's chroma.
+    pub luma_only: bool,
+    /// From `--mask`: where the operation's result is kept, blended with its input elsewhere.
+    pub mask: Option<Mask>,
+    /// Final size for `--resize`, applied after the operation.
+    pub resize: Option<Resize>,
+    /// Encoder from `--format`, used whatever the output's extension.
+    pub format: Option<OutputFormat>,
+    /// File name template for derived outputs from `--output-suffix`, e.g. `{stem}-{filter}{ext}`.
+    pub output_suffix: Option<String>,
+}
+
+/// A grayscale `--mask` image: white keeps the operation's result, black the image as it was before, grays blend.
+pub struct Mask {
+    pub path: String,
+    pub image: GrayImage,
+}
+
+impl Mask {
+    /// Decodes the mask image at `path`, reduced to its luminance.
+    pub fn load(path: &str) -> Result<Mask, Error> {
+        Ok(Mask { path: path.to_string(), image: image::open(path)?.into_luma8() })
+    }
+
+    /// The mask lined up with the image a job filters: stretched (bilinear) to the `input` size if it was made
+    /// for another one, then given the job's crop, flip and rotation, and finally scaled to `target`, which
+    /// differs from the cropped and rotated size only when `--max-dimension` downscaled the image.
+    fn fitted(&self, job: &Job, input: (u32, u32), target: (u32, u32)) -> Result<GrayImage, Error> {
+        let stretch = |mask: GrayImage, (width, height): (u32, u32)| {
+            if mask.dimensions() == (width, height) {
+                return mask;
+            }
+            debug!("Resizing the {}x{} mask to {}x{}", mask.width(), mask.height(), width, height);
+            resize(&mask, width, height, ResampleMode::Bilinear)
+        };
+
+        let mask = DynamicImage::ImageLuma8(stretch(self.image.clone(), input));
+        let mask = match job.crop {
+            Some((x, y, width, height)) => crop_dynamic(&mask, x, y, width, height)?,
+            None => mask,
+        };
+        let mask = match job.flip {
+            Some(flip) => flip_dynamic(&mask, flip),
+            None => mask,
+        };
+        let mask = match job.rotation {
+            Some(rotation) => rotate_dynamic(&mask, rotation),
+            None => mask,
+        };
+        Ok(stretch(mask.into_luma8(), target))
+    }
+}
+
+/// The filtering step of a `Job`.
+pub enum Operation {
+    /// A `--filter` or `--pipeline` of built-in filters.
+    Pipeline(Pipeline),
+    /// A custom kernel loaded from the `--kernel` file, applied to the selected channels with the given border,
+    /// as many times as the count (`--iterations`). Each pass rounds to the image's bit depth.
+    Kernel(Vec<Vec<f32>>, ChannelMask, BorderMode, usize),
+}
+
+/// Target size from `--resize WxH`; a missing dimension keeps the aspect ratio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Resize {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub mode: ResampleMode,
+}
+
+impl Job {
+    /// Name fragment used for derived output paths.
+    pub fn suffix(&self) -> String {
+        let suffix = match &self.operation {
+            Operation::Pipeline(pipeline) => pipeline.suffix(),
+            Operation::Kernel(.., 1) => "kernel".to_string(),
+            Operation::Kernel(.., iterations) => format!("kernel_x{}", iterations),
+        };
+        if self.noise.is_some() { format!("noisy_{}", suffix) } else { suffix }
+    }
+
+    /// Processes an already decoded image. Fails if the `--crop` region doesn't fit or the custom kernel is invalid.
+    pub fn apply(&self, image: DynamicImage) -> Result<DynamicImage, Error> {
+        if let Operation::Kernel(kernel, ..) = &self.operation {
+            validate_kernel(kernel)?;
+        }
+        debug!("Input is {}x{} {:?}", image.width(), image.height(), image.color());
+        let input_size = (image.width(), image.height());
+        let image = match self.crop {
+            Some((x, y, width, height)) => crop_dynamic(&image, x, y, width, height)?,
+            None => image,
+        };
+        let image = match self.flip {
+            Some(flip) => flip_dynamic(&image, flip),
+            None => image,
+        };
+        let image = match self.rotation {
+            Some(rotation) => rotate_dynamic(&image, rotation),
+            None => image,
+        };
+        let image = match self.max_dimension {
+            Some(max_dimension) => limit_size(image, max_dimension),
+            None => image,
+        };
+
+        // Noise is generated on 8 bits, so 16-bit inputs are reduced first
+        let image = match self.noise {
+            Some(noise) if image.color().has_alpha() => DynamicImage::ImageRgba8(noise.apply(&image.into_rgba8(), self.seed)),
+            Some(noise) => DynamicImage::ImageRgb8(noise.apply(&image.into_rgb8(), self.seed)),
+            None => image,
+        };
+
+        let original = self.mask.is_some().then(|| image.clone());
+        debug!("Kernel: {}", self.kernel_description());
+        let start = Instant::now();
+        let image = match self.luma_only.then(|| self.filter_luma(&image)).flatten() {
+            Some(filtered) => filtered,
+            None if self.luma_only => {
+                let filtered = self.filter(image.clone())?;
+                merge_luma(&image, filtered)
+            }
+            None => self.filter(image)?,
+        };
+        debug!("Filtering took {:.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+        let image = match (&original, &self.mask) {
+            (Some(original), Some(mask)) => merge_masked(original, image, &mask.fitted(self, input_size, (original.width(), original.height()))?),
+            _ => image,
+        };
+
+        let Some(size) = self.resize else {
+            return Ok(image);
+        };
+        let (width, height) = fit_dimensions(image.width(), image.height(), size.width, size.height);
+        Ok(resize_dynamic(image, width, height, size.mode))
+    }
+
+    /// Runs the operation itself.
+    fn filter(&self, image: DynamicImage) -> Result<DynamicImage, Error> {
+        match &self.operation {
+            Operation::Pipeline(pipeline) => Ok(pipeline.apply_dynamic(image)),
+            Operation::Kernel(kernel, channels, border, iterations) => {
+                (0..*iterations).try_fold(image, |image, _| apply_kernel(image, kernel, *channels, *border))
+            }
+        }
+    }
+
+    /// `--luma-only` for convolutions on 8-bit color: the kernels run on the luminance alone, in f32 with a single
+    /// clamp at the end (see `apply_to_luma`). `None` when that doesn't apply (other filters, 16-bit or gray images,
+    /// `--channels`), in which case `apply` filters all of RGB and keeps the result's luminance with `merge_luma`.
+    fn filter_luma(&self, image: &DynamicImage) -> Option<DynamicImage> {
+        let rgb = match image {
+            DynamicImage::ImageRgb8(rgb) => Cow::Borrowed(rgb),
+            DynamicImage::ImageRgba8(_) => Cow::Owned(image.to_rgb8()),
+            _ => return None,
+        };
+        let filtered = match &self.operation {
+            Operation::Pipeline(pipeline) => pipeline.apply_luma(&rgb)?,
+            Operation::Kernel(kernel, channels, border, iterations) if *channels == ChannelMask::ALL => apply_to_luma(&rgb, |luma| {
+                (0..*iterations).fold(luma, |luma, _| apply_convolution_gray_f32(&luma, kernel, *border, 0.0))
+            }),
+            Operation::Kernel(..) => return None,
+        };
+
+        // Alpha isn't touched by a luminance change
+        let DynamicImage::ImageRgba8(rgba) = image else {
+            return Some(DynamicImage::ImageRgb8(filtered));
+        };
+        let mut output = rgba.clone();
+        for (pixel, color) in output.pixels_mut().zip(filtered.pixels()) {
+            pixel.0[..3].copy_from_slice(&color.0);
+        }
+        Some(DynamicImage::ImageRgba8(output))
+    }
+
+    /// The steps `apply` will run, in order, as human-readable lines for `--dry-run`.
+    pub fn describe(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        if let Some((x, y, width, height)) = self.crop {
+            steps.push(format!("crop {}x{} at ({}, {})", width, height, x, y));
+        }
+        match self.flip {
+            Some(Flip::Horizontal) => steps.push("flip horizontally".to_string()),
+            Some(Flip::Vertical) => steps.push("flip vertically".to_string()),
+            None => {}
+        }
+        if let Some(rotation) = self.rotation {
+            steps.push(format!("rotate {}° clockwise", rotation.degrees()));
+        }
+        if let Some(max_dimension) = self.max_dimension {
+            steps.push(format!("downscale to fit within {}x{} if larger", max_dimension, max_dimension));
+        }
+        if let Some(noise) = self.noise {
+            steps.push(format!("add {:?} noise (seed {})", noise, self.seed));
+        }
+
+        let (channels, border) = match &self.operation {
+            Operation::Pipeline(pipeline) => {
+                if pipeline.color_space == ColorSpace::Lab {
+                    steps.push("convert to CIE Lab (D65)".to_string());
+                }
+                steps.extend(pipeline.stages.iter().map(|stage| format!("{:?}", stage)));
+                if pipeline.iterations > 1 {
+                    steps.push(format!("repeat the filters above {} times in all", pipeline.iterations));
+                }
+                if pipeline.color_space == ColorSpace::Lab {
+                    steps.push("convert back to sRGB".to_string());
+                }
+                (pipeline.channels, pipeline.border)
+            }
+            Operation::Kernel(kernel, channels, border, iterations) => {
+                steps.push(format!("custom {} kernel", kernel_dimensions(kernel)));
+                if *iterations > 1 {
+                    steps.push(format!("repeat the kernel {} times in all", iterations));
+                }
+                (*channels, *border)
+            }
+        };
+        if let BorderMode::ConstantRgb([r, g, b]) = border {
+            steps.push(format!("filters read pixels past the edges as {},{},{}", r, g, b));
+        }
+        if self.luma_only {
+            steps.push("keep luminance changes only (original chroma)".to_string());
+        }
+        if let Some(mask) = &self.mask {
+            steps.push(format!("blend with the unfiltered image through the mask '{}'", mask.path));
+        }
+        if channels != ChannelMask::ALL {
+            let names: String = ['R', 'G', 'B'].iter().zip(channels.0).filter(|(_, on)| *on).map(|(name, _)| name).collect();
+            steps.push(format!("keep changes to channels {} only", names));
+        }
+
+        if let Some(size) = self.resize {
+            let dimension = |value: Option<u32>| value.map_or("auto".to_string(), |value| value.to_string());
+            steps.push(format!("resize to {}x{} ({:?})", dimension(size.width), dimension(size.height), size.mode));
+        }
+        steps
+    }
+
+    /// Kernel sizes for `--time`, e.g. `5x5` or `blur 5x5, sharpen 3x3` for a pipeline.
+    pub fn kernel_description(&self) -> String {
+        match &self.operation {
+            Operation::Kernel(kernel, ..) => kernel_dimensions(kernel),
+            Operation::Pipeline(pipeline) => pipeline
+                .stages
+                .iter()
+                .map(|stage| match stage.kernel_size() {
+                    Some(size) => format!("{} {}x{}", stage.suffix(), size, size),
+                    None => format!("{} (per pixel)", stage.suffix()),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+
+    /// Whether processing `input_path` into `output_path` keeps every frame: both are GIFs, which may be animated,
+    /// and `--format` doesn't ask for another encoder. Other outputs of a GIF get its first frame only.
+    pub fn is_animation(&self, input_path: &str, output_path: &str) -> bool {
+        self.format.is_none() && is_gif_path(input_path) && is_gif_path(output_path)
+    }
+
+    /// Processes one image file (or stdin) and saves the result. A GIF saved as a GIF is processed frame by frame
+    /// (see `is_animation`). Unlike the library's file wrappers this prints nothing, so batch progress isn't
+    /// interleaved with messages.
+    pub fn run(&self, input_path: &str, output_path: &str) -> Result<(), Error> {
+        if self.is_animation(input_path, output_path) {
+            return self.run_animation(input_path, output_path);
+        }
+        save_output(self.apply(load_input(input_path)?)?, input_path, output_path, self.format)
+    }
+
+    /// Applies the job to every frame of a GIF, keeping the frame delays and loop count.
+    fn run_animation(&self, input_path: &str, output_path: &str) -> Result<(), Error> {
+        let bytes = fs::read(input_path)?;
+        let encoded = filter_gif_frames(&bytes, |frame| Ok(self.apply(DynamicImage::ImageRgba8(frame))?.into_rgba8()))?;
+
+        if let Some(parent) = Path::new(output_path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        if input_path != output_path {
+            return Ok(fs::write(output_path, encoded)?);
+        }
+        // Replaced the same way as `replace_image`, so a failed write leaves the original intact
+        let target = Path::new(output_path);
+        let name = target.file_name().map_or("image".into(), |name| name.to_string_lossy());
+        let temporary = target.with_file_name(format!(".{}.kernel-rs-tmp", name));
+        fs::write(&temporary, encoded).and_then(|_| fs::rename(&temporary, target)).inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })?;
+        Ok(())
+    }
+}
+
+/// Downscales `image` (area average) so neither side exceeds `max_dimension`, with a warning; smaller images pass through.
+fn limit_size(image: DynamicImage, max_dimension: u32) -> DynamicImage {
+    let (width, height) = limit_dimensions(image.width(), image.height(), max_dimension);
+    if (width, height) == (image.width(), image.height()) {
+        return image;
+    }
+    warn!(
+        "Image is {}x{}, larger than --max-dimension {}; downscaling to {}x{} before filtering.",
+        image.width(), image.height(), max_dimension, width, height
+    );
+    resize_dynamic(image, width, height, ResampleMode::Box)
+}
+
+/// A kernel's size as `WxH`, e.g. `5x5`.
+fn kernel_dimensions(kernel: &[Vec<f32>]) -> String {
+    format!("{}x{}", kernel[0].len(), kernel.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Luma, Rgb, RgbImage};
+    use kernel_rs::Filter;
+
+    /// A job that inverts, with nothing else but the given crop, flip and mask.
+    fn invert_job(crop: Option<(u32, u32, u32, u32)>, flip: Option<Flip>, mask: GrayImage) -> Job {
+        Job {
+            crop,
+            flip,
+            rotation: None,
+            max_dimension: None,
+            noise: None,
+            seed: 0,
+            operation: Operation::Pipeline(Pipeline::from(Filter::Invert)),
+            luma_only: false,
+            mask: Some(Mask { path: "mask.png".to_string(), image: mask }),
+            resize: None,
+            format: None,
+            output_suffix: None,
+        }
+    }
+
+    /// A mask for an 8x4 input that is white in columns 0–3 and black in 4–7.
+    fn left_half_mask() -> GrayImage {
+        GrayImage::from_fn(8, 4, |x, _| Luma([if x < 4 { 255 } else { 0 }]))
+    }
+
+    #[test]
+    fn mask_is_cropped_with_the_image() {
+        let input = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([100; 3])));
+        let output = invert_job(Some((3, 0, 4, 4)), None, left_half_mask()).apply(input).unwrap().into_rgb8();
+
+        // The crop covers input columns 3–6: only the first was under white (stretching the whole mask
+        // onto the crop instead would filter two columns)
+        assert_eq!(output.dimensions(), (4, 4));
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let expected = if x < 1 { 155 } else { 100 };
+            assert_eq!(pixel.0, [expected; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn mask_is_flipped_with_the_image() {
+        let input = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([100; 3])));
+        let output = invert_job(None, Some(Flip::Horizontal), left_half_mask()).apply(input).unwrap().into_rgb8();
+
+        // The input's left half, where the mask is white, ends up on the right
+        for (x, _, pixel) in output.enumerate_pixels() {
+            assert_eq!(pixel.0, [if x >= 4 { 155 } else { 100 }; 3]);
+        }
+    }
+
+    #[test]
+    fn mask_of_another_size_is_stretched_to_the_input() {
+        let input = DynamicImage::ImageRgb8(RgbImage::from_pixel(16, 8, Rgb([100; 3])));
+        let output = invert_job(None, None, left_half_mask()).apply(input).unwrap().into_rgb8();
+
+        assert_eq!(output.get_pixel(0, 0).0, [155; 3]);
+        assert_eq!(output.get_pixel(15, 7).0, [100; 3]);
+    }
+
+    #[test]
+    fn large_input_is_downscaled_before_the_operation() {
+        // Black then white halves; the kernel copies each pixel's left neighbor, a one-pixel shift to the right
+        let input = DynamicImage::ImageRgb8(RgbImage::from_fn(1000, 10, |x, _| Rgb([if x < 500 { 0 } else { 255 }; 3])));
+        let shift = Operation::Kernel(vec![vec![1.0, 0.0, 0.0]], ChannelMask::ALL, BorderMode::Clamp, 1);
+        let job = Job { max_dimension: Some(100), mask: None, operation: shift, ..invert_job(None, None, left_half_mask()) };
+
+        let output = job.apply(input).unwrap().into_rgb8();
+        assert_eq!(output.dimensions(), (100, 1));
+        // Shifted after downscaling, the edge moves a whole output pixel; shifting first would only move it a tenth
+        assert_eq!(output.get_pixel(50, 0).0, [0; 3]);
+        assert_eq!(output.get_pixel(51, 0).0, [255; 3]);
+    }
+}
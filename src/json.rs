@@ -0,0 +1,266 @@
+//! A small hand-rolled JSON reader for `kernel-rs.json` and batch manifests, plus `quote` for writing the manifest back.
+//!
+//! The crate deliberately depends only on `image` and `rayon`. The config and manifest are a handful of flat
+//! objects of numbers, strings and arrays that `preset` and `manifest` walk by hand, so pulling in `serde` and
+//! `serde_json` (and a derive macro) would buy little beyond a longer build.
+
+/// Parses a whole JSON document, e.g. the text of `kernel-rs.json`; errors name the line and column.
+pub fn parse(text: &str) -> Result<Json, String> {
+    JsonParser::new(text).parse_document()
+}
+
+/// `text` as a quoted JSON string, with quotes, backslashes and control characters escaped.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A parsed JSON value. Objects keep their keys in file order.
+#[derive(Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Just enough of a JSON parser for the config file and batch manifest; errors name the line and column.
+struct JsonParser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(text: &'a str) -> Self {
+        JsonParser { text, position: 0 }
+    }
+
+    /// Parses one value and rejects anything but whitespace after it.
+    fn parse_document(&mut self) -> Result<Json, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(value),
+            Some(_) => Err(self.error("unexpected text after the end of the document")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => self.parse_string().map(Json::String),
+            Some('t') => self.parse_literal("true", Json::Bool(true)),
+            Some('f') => self.parse_literal("false", Json::Bool(false)),
+            Some('n') => self.parse_literal("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) => Err(self.error(&format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(Json::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a quoted key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.parse_value()?));
+
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Json::Object(entries));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.parse_value()?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(Json::Array(items));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('/') => value.push('/'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some('b') => value.push('\u{8}'),
+                    Some('f') => value.push('\u{c}'),
+                    Some('u') => {
+                        let hex = self.text.get(self.position..self.position + 4).ok_or_else(|| self.error("incomplete \\u escape"))?;
+                        let code = u32::from_str_radix(hex, 16).map_err(|_| self.error("invalid \\u escape"))?;
+                        self.position += 4;
+                        // Surrogate pairs are not needed for filter specs; they become U+FFFD
+                        value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    _ => return Err(self.error("invalid escape in string")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.position += 1;
+        }
+        let raw = &self.text[start..self.position];
+        raw.parse().map(Json::Number).map_err(|_| self.error(&format!("invalid number '{}'", raw)))
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, String> {
+        if self.text[self.position..].starts_with(literal) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("expected true, false or null"))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    /// Consumes `expected` if it is the next character.
+    fn eat(&mut self, expected: char) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.position += expected.len_utf8();
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) { Ok(()) } else { Err(self.error(&format!("expected '{}'", expected))) }
+    }
+
+    /// Formats `message` with the 1-based line and column of the current position.
+    fn error(&self, message: &str) -> String {
+        let before = &self.text[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        format!("{} at line {}, column {}", message, line, column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scalars() {
+        assert_eq!(parse("null"), Ok(Json::Null));
+        assert_eq!(parse(" true "), Ok(Json::Bool(true)));
+        assert_eq!(parse("false"), Ok(Json::Bool(false)));
+        assert_eq!(parse("-1.5e2"), Ok(Json::Number(-150.0)));
+        assert_eq!(parse("\"blur\""), Ok(Json::String("blur".to_string())));
+    }
+
+    #[test]
+    fn parses_string_escapes() {
+        let parsed = parse(r#""a\"b\\c\/d\n\t\r\b\f\u00e9""#);
+        assert_eq!(parsed, Ok(Json::String("a\"b\\c/d\n\t\r\u{8}\u{c}é".to_string())));
+    }
+
+    #[test]
+    fn parses_nested_values_in_file_order() {
+        let parsed = parse(r#"{"jobs": [{"filter": "blur", "size": 5}, []], "empty": {}}"#).unwrap();
+        let expected = Json::Object(vec![
+            (
+                "jobs".to_string(),
+                Json::Array(vec![
+                    Json::Object(vec![
+                        ("filter".to_string(), Json::String("blur".to_string())),
+                        ("size".to_string(), Json::Number(5.0)),
+                    ]),
+                    Json::Array(vec![]),
+                ]),
+            ),
+            ("empty".to_string(), Json::Object(vec![])),
+        ]);
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn malformed_input_names_the_line_and_column() {
+        assert_eq!(parse("{\n  \"size\" 5\n}"), Err("expected ':' at line 2, column 10".to_string()));
+        assert_eq!(parse("[1, 2"), Err("expected ',' at line 1, column 6".to_string()));
+        assert_eq!(parse("{5: 1}"), Err("expected a quoted key at line 1, column 2".to_string()));
+        assert_eq!(parse("\"open"), Err("unterminated string at line 1, column 6".to_string()));
+        assert_eq!(parse(r#""\q""#), Err("invalid escape in string at line 1, column 4".to_string()));
+        assert_eq!(parse(r#""\u12""#), Err("incomplete \\u escape at line 1, column 4".to_string()));
+        assert_eq!(parse("nul"), Err("expected true, false or null at line 1, column 1".to_string()));
+        assert_eq!(parse("1 2"), Err("unexpected text after the end of the document at line 1, column 3".to_string()));
+        assert_eq!(parse(""), Err("unexpected end of file at line 1, column 1".to_string()));
+        assert!(parse("1.2.3").is_err());
+    }
+
+    #[test]
+    fn quote_round_trips_through_parse() {
+        let text = "say \"hi\"\\\n\ttab\u{1}";
+        assert_eq!(quote(text), r#""say \"hi\"\\\n\ttab\u0001""#);
+        assert_eq!(parse(&quote(text)), Ok(Json::String(text.to_string())));
+    }
+}
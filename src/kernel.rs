@@ -0,0 +1,434 @@
+use std::{fmt, fs};
+
+use image::{GrayImage, Luma};
+
+use crate::error::Error;
+
+/// Blur kernels supported by `blur_image` and `Filter::Blur`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlurKind {
+    /// Flat average over the whole window.
+    Box,
+    /// Gaussian-weighted average; a non-positive sigma is derived from the kernel size.
+    Gaussian { sigma: f32 },
+}
+
+/// Returns `size` if it is odd, otherwise warns on stderr and returns `size + 1` so the kernel has a center pixel.
+/// `what` names the value in the warning, e.g. "Blur size 4 is even; using 5 for a centered kernel."
+pub fn ensure_odd_size(size: usize, what: &str) -> usize {
+    if !size.is_multiple_of(2) {
+        return size;
+    }
+
+    crate::warn!("{} {} is even; using {} for a centered kernel.", what, size, size + 1);
+    size + 1
+}
+
+/// Caps an odd kernel size for a `width x height` image at `2 * max(width, height) - 1`, the size at which every
+/// output pixel already sees the whole image; anything larger only re-reads the clamped border and costs time.
+/// Warns on stderr when it clamps.
+pub fn clamp_kernel_size(size: usize, width: u32, height: u32) -> usize {
+    let limit = (2 * width.max(height) as usize).saturating_sub(1).max(1);
+    if size <= limit {
+        return size;
+    }
+
+    crate::warn!("Kernel size {} is larger than the {}x{} image needs; using {}.", size, width, height, limit);
+    limit
+}
+
+pub fn generate_box_blur_kernel(size: usize) -> Vec<Vec<f32>> {
+    // Filled with 1/(n*n) to average (blur) neighboring pixels
+    // Kernel size must be odd to ensure the center pixel is included.
+    // Kernel is normalized to ensure output has the same brightness as the input.
+    let value = 1.0 / (size * size) as f32;
+    vec![vec![value; size]; size]
+}
+
+/// Odd Gaussian kernel size that covers ±3σ, i.e. `2 * ceil(3σ) + 1` (sigma 1.0 gives 7).
+/// Non-positive sigmas give 1, the identity.
+pub fn kernel_size_for_sigma(sigma: f32) -> usize {
+    2 * (3.0 * sigma.max(0.0)).ceil() as usize + 1
+}
+
+pub fn generate_gaussian_kernel(size: usize, sigma: f32) -> Vec<Vec<f32>> {
+    // Each cell holds the 2D Gaussian exp(-(dx² + dy²) / (2σ²)) measured from the center pixel.
+    // A non-positive sigma falls back to size / 6 so the kernel spans roughly ±3σ.
+    let sigma = if sigma > 0.0 { sigma } else { size as f32 / 6.0 };
+    let half = (size / 2) as f32;
+
+    let mut kernel: Vec<Vec<f32>> = (0..size)
+        .map(|y| {
+            (0..size)
+                .map(|x| {
+                    let dx = x as f32 - half;
+                    let dy = y as f32 - half;
+                    (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+                })
+                .collect()
+        })
+        .collect();
+
+    // Normalize so the weights sum to 1.0, keeping brightness unchanged like the box kernel.
+    normalize_kernel(&mut kernel);
+
+    kernel
+}
+
+/// Builds a `size x size` blur kernel of the requested kind.
+pub fn generate_blur_kernel(size: usize, kind: BlurKind) -> Vec<Vec<f32>> {
+    match kind {
+        BlurKind::Box => generate_box_blur_kernel(size),
+        BlurKind::Gaussian { sigma } => generate_gaussian_kernel(size, sigma),
+    }
+}
+
+/// Divides every weight by the total so the kernel sums to 1 and keeps overall brightness unchanged.
+/// Kernels whose weights sum to roughly zero, like edge detectors, are left as they are.
+pub fn normalize_kernel(kernel: &mut [Vec<f32>]) {
+    let sum: f32 = kernel.iter().flatten().sum();
+    if sum.abs() <= 1e-6 {
+        return;
+    }
+
+    for value in kernel.iter_mut().flatten() {
+        *value /= sum;
+    }
+}
+
+/// Draws a kernel as a grayscale picture with one `cell x cell` square per weight, for seeing what a kernel does.
+/// Weights are scaled between the smallest (black) and the largest (white); a kernel whose weights are all equal,
+/// like a box blur, is drawn all white. Negative weights simply come out darker than positive ones.
+pub fn render_kernel(kernel: &[Vec<f32>], cell: u32) -> GrayImage {
+    let cell = cell.max(1);
+    let rows = kernel.len() as u32;
+    let columns = kernel.iter().map(Vec::len).max().unwrap_or(0) as u32;
+
+    let weights = || kernel.iter().flatten().copied();
+    let low = weights().fold(f32::INFINITY, f32::min);
+    let high = weights().fold(f32::NEG_INFINITY, f32::max);
+    let shade = |weight: f32| {
+        if high - low <= f32::EPSILON {
+            255
+        } else {
+            ((weight - low) / (high - low) * 255.0).round() as u8
+        }
+    };
+
+    GrayImage::from_fn(columns * cell, rows * cell, |x, y| {
+        let weight = kernel[(y / cell) as usize].get((x / cell) as usize).copied().unwrap_or(low);
+        Luma([shade(weight)])
+    })
+}
+
+/// Splits a rank-1 kernel into the horizontal and vertical 1D vectors whose outer product rebuilds it.
+/// Returns `None` when the kernel is not separable (e.g. the sharpen kernel).
+pub fn separate_kernel(kernel: &[Vec<f32>]) -> Option<(Vec<f32>, Vec<f32>)> {
+    // Pivot on the largest weight so the division below is well conditioned
+    let (pivot_y, pivot_x, pivot) = kernel
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, &weight)| (y, x, weight)))
+        .max_by(|a, b| a.2.abs().total_cmp(&b.2.abs()))?;
+    if pivot == 0.0 {
+        return None;
+    }
+
+    // The pivot row gives the horizontal shape and the pivot column carries the scale
+    let horizontal: Vec<f32> = kernel[pivot_y].iter().map(|weight| weight / pivot).collect();
+    let vertical: Vec<f32> = kernel.iter().map(|row| row.get(pivot_x).copied()).collect::<Option<_>>()?;
+
+    // Only accept the split if the outer product reproduces every weight
+    let separable = kernel.iter().zip(&vertical).all(|(row, &v)| {
+        row.len() == horizontal.len()
+            && row.iter().zip(&horizontal).all(|(&weight, &h)| (weight - v * h).abs() <= 1e-6)
+    });
+
+    separable.then_some((horizontal, vertical))
+}
+
+pub fn generate_motion_blur_kernel(length: usize, angle_degrees: f32) -> Vec<Vec<f32>> {
+    // Marks the cells crossed by a line of `length` pixels through the center at the given angle.
+    // Angles are measured counter-clockwise from the horizontal, so 0 averages along the row only.
+    // Kernel is normalized over the marked cells to keep brightness unchanged.
+    let mut kernel = vec![vec![0.0; length]; length];
+    let half = (length / 2) as f32;
+    let (sin, cos) = angle_degrees.to_radians().sin_cos();
+
+    for step in 0..length {
+        let t = step as f32 - half;
+        let x = (half + t * cos).round() as usize;
+        let y = (half - t * sin).round() as usize;
+        kernel[y.min(length - 1)][x.min(length - 1)] = 1.0;
+    }
+
+    normalize_kernel(&mut kernel);
+
+    kernel
+}
+
+pub fn generate_sharpen_kernel(strength: f32) -> Vec<Vec<f32>> {
+    // This kernel is used to enhance edges in an image.
+    // The center pixel is given a higher weight (1 + 4 * strength) to make it stand out more, while the neighboring
+    // pixels are given a negative weight (-strength) to reduce their influence, effectively highlighting edges.
+    // The weights always sum to 1, so flat areas are unchanged; strength 0 is the identity and 1 the classic 5/-1 kernel.
+    vec![
+        vec![0.0, -strength, 0.0],
+        vec![-strength, 1.0 + 4.0 * strength, -strength],
+        vec![0.0, -strength, 0.0],
+    ]
+}
+
+pub fn generate_laplacian_kernel() -> Vec<Vec<f32>> {
+    // Discrete second derivative: the center minus its four neighbors. Flat areas and even smooth ramps give 0,
+    // while both sides of an edge respond with opposite signs. Note `generate_sharpen_kernel(1.0)` is exactly this
+    // plus the identity, i.e. the image with its Laplacian added back.
+    vec![
+        vec![ 0.0, -1.0,  0.0],
+        vec![-1.0,  4.0, -1.0],
+        vec![ 0.0, -1.0,  0.0],
+    ]
+}
+
+pub fn generate_emboss_kernel() -> Vec<Vec<f32>> {
+    // Light appears to come from the top-left: brightness increasing towards the bottom-right is raised,
+    // the opposite direction is sunk. The weights sum to 0, so flat areas land on whatever bias is added.
+    vec![
+        vec![-2.0, -1.0, 0.0],
+        vec![-1.0,  0.0, 1.0],
+        vec![ 0.0,  1.0, 2.0],
+    ]
+}
+
+pub fn generate_sobel_kernels() -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    // Gx responds to horizontal changes in brightness (vertical edges) and Gy to vertical changes.
+    // Both smooth across the perpendicular direction with weights 1-2-1 to suppress noise.
+    let gx = vec![
+        vec![-1.0, 0.0, 1.0],
+        vec![-2.0, 0.0, 2.0],
+        vec![-1.0, 0.0, 1.0],
+    ];
+    let gy = vec![
+        vec![-1.0, -2.0, -1.0],
+        vec![ 0.0,  0.0,  0.0],
+        vec![ 1.0,  2.0,  1.0],
+    ];
+    (gx, gy)
+}
+
+pub fn generate_prewitt_kernels() -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    // Same layout as Sobel but with a flat 1-1-1 average across the edge instead of 1-2-1,
+    // so it smooths less and responds a little more to noise. A full step gives 765 instead of Sobel's 1020.
+    let gx = vec![
+        vec![-1.0, 0.0, 1.0],
+        vec![-1.0, 0.0, 1.0],
+        vec![-1.0, 0.0, 1.0],
+    ];
+    let gy = vec![
+        vec![-1.0, -1.0, -1.0],
+        vec![ 0.0,  0.0,  0.0],
+        vec![ 1.0,  1.0,  1.0],
+    ];
+    (gx, gy)
+}
+
+pub fn generate_scharr_kernels() -> (Vec<Vec<f32>>, Vec<Vec<f32>>) {
+    // Sobel's 1-2-1 becomes 3-10-3: the heavier center row is tuned so the gradient direction is
+    // close to correct at every angle, where Sobel over-weights the axes and under-reads diagonals.
+    // The weights sum to 16 per side instead of 4, so responses are 4x Sobel's and saturate sooner.
+    let gx = vec![
+        vec![ -3.0, 0.0,  3.0],
+        vec![-10.0, 0.0, 10.0],
+        vec![ -3.0, 0.0,  3.0],
+    ];
+    let gy = vec![
+        vec![-3.0, -10.0, -3.0],
+        vec![ 0.0,   0.0,  0.0],
+        vec![ 3.0,  10.0,  3.0],
+    ];
+    (gx, gy)
+}
+
+/// Reasons a kernel's contents can't be used.
+#[derive(Debug)]
+pub enum KernelError {
+    /// A cell on the given (1-based) line is not a number.
+    InvalidValue { line: usize, value: String },
+    /// The kernel has no rows or no columns.
+    Empty,
+    /// A (1-based) row has a different number of values than the first row.
+    RaggedRows { row: usize, expected: usize, found: usize },
+    /// The width or height is even, so the kernel has no center pixel.
+    EvenDimension { width: usize, height: usize },
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KernelError::InvalidValue { line, value } => write!(f, "invalid value '{}' on line {}", value, line),
+            KernelError::Empty => f.write_str("kernel is empty"),
+            KernelError::RaggedRows { row, expected, found } => {
+                write!(f, "row {} has {} values but the first row has {}", row, found, expected)
+            }
+            KernelError::EvenDimension { width, height } => {
+                write!(f, "kernel is {}x{} but both dimensions must be odd", width, height)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KernelError {}
+
+/// Reads a kernel from a text file with one row per line and values separated by whitespace and/or commas.
+/// Blank lines and lines starting with `#` are skipped. The result must pass `validate_kernel`.
+/// Fails with `Error::Io` if the file can't be read and `Error::InvalidKernel` if its contents are unusable.
+pub fn load_kernel_from_file(path: &str) -> Result<Vec<Vec<f32>>, Error> {
+    let contents = fs::read_to_string(path)?;
+
+    let mut kernel = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let row = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse().map_err(|_| KernelError::InvalidValue { line: index + 1, value: value.to_string() }))
+            .collect::<Result<Vec<f32>, _>>()?;
+        kernel.push(row);
+    }
+
+    validate_kernel(&kernel)?;
+    Ok(kernel)
+}
+
+/// Checks that a kernel can be convolved: at least one value, every row as long as the first,
+/// and an odd width and height so there is a center pixel. Rectangular kernels (e.g. 1 x 5) are fine.
+pub fn validate_kernel(kernel: &[Vec<f32>]) -> Result<(), KernelError> {
+    let width = kernel.first().map_or(0, Vec::len);
+    if width == 0 {
+        return Err(KernelError::Empty);
+    }
+    if let Some((index, row)) = kernel.iter().enumerate().find(|(_, row)| row.len() != width) {
+        return Err(KernelError::RaggedRows { row: index + 1, expected: width, found: row.len() });
+    }
+    if width.is_multiple_of(2) || kernel.len().is_multiple_of(2) {
+        return Err(KernelError::EvenDimension { width, height: kernel.len() });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::LogLevel;
+
+    #[test]
+    fn blur_kernels_separate_and_sharpen_does_not() {
+        for kernel in [generate_box_blur_kernel(5), generate_gaussian_kernel(7, 1.5)] {
+            let (horizontal, vertical) = separate_kernel(&kernel).expect("blur kernels separate");
+            for (row, &v) in kernel.iter().zip(&vertical) {
+                for (&weight, &h) in row.iter().zip(&horizontal) {
+                    assert!((weight - v * h).abs() <= 1e-6);
+                }
+            }
+        }
+        assert_eq!(separate_kernel(&generate_sharpen_kernel(1.0)), None);
+    }
+
+    /// Writes `contents` to a file named after the test in the system temp dir and returns its path.
+    fn kernel_file(test: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kernel-rs-{}-{}.txt", std::process::id(), test));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn loads_a_3x3_kernel_file() {
+        let path = kernel_file("load-3x3", "# sharpen\n0, -1, 0\n\n-1 5 -1\n0,-1,0\n");
+        let kernel = load_kernel_from_file(&path).unwrap();
+        assert_eq!(kernel, vec![vec![0.0, -1.0, 0.0], vec![-1.0, 5.0, -1.0], vec![0.0, -1.0, 0.0]]);
+    }
+
+    #[test]
+    fn rejects_ragged_and_empty_kernel_files() {
+        let ragged = load_kernel_from_file(&kernel_file("load-ragged", "1 1 1\n1 1\n1 1 1\n"));
+        assert!(matches!(ragged, Err(Error::InvalidKernel(KernelError::RaggedRows { row: 2, expected: 3, found: 2 }))));
+
+        let empty = load_kernel_from_file(&kernel_file("load-empty", "# nothing but a comment\n\n"));
+        assert!(matches!(empty, Err(Error::InvalidKernel(KernelError::Empty))));
+        assert_eq!(empty.unwrap_err().to_string(), "invalid kernel: kernel is empty");
+    }
+
+    #[test]
+    fn normalized_kernel_passes_a_flat_image_through() {
+        let mut kernel = vec![vec![2.0; 3]; 3];
+        normalize_kernel(&mut kernel);
+        assert!(kernel.iter().flatten().all(|&weight| (weight - 1.0 / 9.0).abs() < 1e-6));
+
+        let flat = image::RgbImage::from_pixel(5, 5, image::Rgb([90, 150, 30]));
+        assert_eq!(crate::convolution::apply_convolution(&flat, &kernel, crate::convolution::BorderMode::Clamp, 0.0), flat);
+
+        // Zero-sum kernels are left alone
+        let mut laplacian = generate_laplacian_kernel();
+        normalize_kernel(&mut laplacian);
+        assert_eq!(laplacian, generate_laplacian_kernel());
+    }
+
+    #[test]
+    fn validation_reports_each_error_variant() {
+        assert!(matches!(validate_kernel(&[]), Err(KernelError::Empty)));
+        assert!(matches!(validate_kernel(&[vec![]]), Err(KernelError::Empty)));
+        assert!(matches!(
+            validate_kernel(&[vec![1.0; 3], vec![1.0; 3], vec![1.0; 4]]),
+            Err(KernelError::RaggedRows { row: 3, expected: 3, found: 4 })
+        ));
+        assert!(matches!(validate_kernel(&vec![vec![1.0; 4]; 3]), Err(KernelError::EvenDimension { width: 4, height: 3 })));
+        assert!(matches!(validate_kernel(&vec![vec![1.0; 3]; 2]), Err(KernelError::EvenDimension { width: 3, height: 2 })));
+        assert!(validate_kernel(&[vec![1.0; 5]]).is_ok());
+
+        let invalid = load_kernel_from_file(&kernel_file("validate-invalid", "1 1 1\n1 x 1\n1 1 1\n"));
+        let Err(Error::InvalidKernel(err @ KernelError::InvalidValue { line: 2, .. })) = invalid else {
+            panic!("expected an invalid value error, got {:?}", invalid);
+        };
+        assert_eq!(err.to_string(), "invalid value 'x' on line 2");
+    }
+
+    #[test]
+    fn even_sizes_are_rounded_up_with_a_warning() {
+        crate::log::tests::take_records();
+        assert_eq!(ensure_odd_size(4, "Blur size"), 5);
+        assert_eq!(crate::log::tests::take_records(), vec![(LogLevel::Warn, "Blur size 4 is even; using 5 for a centered kernel.".to_string())]);
+
+        assert_eq!(ensure_odd_size(7, "Blur size"), 7);
+        assert!(crate::log::tests::take_records().is_empty());
+    }
+
+    #[test]
+    fn sigma_sizes_the_kernel_to_three_sigmas() {
+        assert_eq!(kernel_size_for_sigma(1.0), 7);
+        assert_eq!(kernel_size_for_sigma(0.5), 5);
+        assert_eq!(kernel_size_for_sigma(2.1), 15);
+        assert_eq!(kernel_size_for_sigma(0.0), 1);
+        assert_eq!(generate_gaussian_kernel(kernel_size_for_sigma(1.0), 1.0).len(), 7);
+    }
+
+    #[test]
+    fn rendered_kernel_is_brightest_at_the_largest_weight() {
+        let kernel = generate_sharpen_kernel(1.0);
+        let rendered = render_kernel(&kernel, 1);
+        assert_eq!(rendered.dimensions(), (3, 3));
+        assert_eq!(rendered.get_pixel(1, 1)[0], 255);
+        assert_eq!(rendered.pixels().filter(|pixel| pixel[0] == 255).count(), 1);
+        assert!(rendered.pixels().any(|pixel| pixel[0] == 0));
+
+        // Larger cells upscale every weight into a square
+        let upscaled = render_kernel(&kernel, 4);
+        assert_eq!(upscaled.dimensions(), (12, 12));
+        assert!((4..8).all(|x| (4..8).all(|y| upscaled.get_pixel(x, y)[0] == 255)));
+        assert!(render_kernel(&generate_box_blur_kernel(3), 1).pixels().all(|pixel| pixel[0] == 255));
+    }
+}
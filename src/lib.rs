@@ -0,0 +1,637 @@
+mod animation;
+mod blur;
+mod color;
+mod compare;
+mod convolution;
+mod edge;
+mod effect;
+mod error;
+mod filter;
+mod histogram;
+mod kernel;
+mod log;
+mod noise;
+mod pattern;
+mod pipeline;
+mod preview;
+mod progress;
+mod rank;
+mod resize;
+mod sharpen;
+mod threshold;
+mod transform;
+
+pub use animation::*;
+pub use blur::*;
+pub use color::*;
+pub use compare::*;
+pub use convolution::*;
+pub use edge::*;
+pub use effect::*;
+pub use error::*;
+pub use filter::*;
+pub use histogram::*;
+pub use kernel::*;
+pub use log::*;
+pub use noise::*;
+pub use pattern::*;
+pub use pipeline::*;
+pub use preview::*;
+pub use progress::*;
+pub use rank::*;
+pub use resize::*;
+pub use sharpen::*;
+pub use threshold::*;
+pub use transform::*;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Read, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use convolution::{convolve, convolve_rgba_premultiplied};
+use filter::{blur_kernel, motion_blur_kernel};
+use image::codecs::jpeg::JpegEncoder;
+use image::{ColorType, DynamicImage, GenericImageView, GrayImage, ImageOutputFormat, RgbImage};
+use rayon::prelude::*;
+
+/// JPEG quality used by `save_image` unless `set_jpeg_quality` changes it.
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+static JPEG_QUALITY: AtomicU8 = AtomicU8::new(DEFAULT_JPEG_QUALITY);
+
+/// Sets the quality (1–100, clamped) of JPEG files written by `save_image`; other formats ignore it.
+/// Lower values give smaller files with more visible compression artifacts. This is the process-wide `--quality`
+/// setting; `write_jpeg` takes the quality as an argument instead.
+pub fn set_jpeg_quality(quality: u8) {
+    JPEG_QUALITY.store(quality.clamp(1, 100), Ordering::Relaxed);
+}
+
+/// Blurs an image using a dynamically generated box or Gaussian kernel with multi-threading.
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn blur_image(input_path: &str, output_path: &str, blur_size: usize, kind: BlurKind) -> Result<(), Error> {
+    // Ensure the kernel size is odd (required for centering)
+    let blur_size = ensure_odd_size(blur_size, "Blur size");
+
+    // Load the image
+    let image = image::open(input_path)?;
+
+    // Generate the blur kernel dynamically, no larger than the image needs
+    let kernel = blur_kernel(blur_size, kind, (image.width(), image.height()));
+
+    // Apply the blur using convolution (multi-threaded)
+    // Images with transparency take the RGBA path so alpha is blurred instead of dropped,
+    // and grayscale images are blurred on their single channel
+    let blurred_image = if let DynamicImage::ImageLuma8(gray) = &image {
+        DynamicImage::ImageLuma8(apply_convolution_luma8(gray, &kernel, BorderMode::Clamp, 0.0))
+    } else if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(convolve_rgba_premultiplied(&image.into_rgba8(), &kernel))
+    } else {
+        DynamicImage::ImageRgb8(convolve(&image.into_rgb8(), &kernel))
+    };
+
+    // Save the blurred image
+    save_image(blurred_image, output_path)?;
+
+    info!("Blurred image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Applies a directional motion blur of `length` pixels at `angle_degrees` (0 is horizontal).
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn motion_blur_image(input_path: &str, output_path: &str, length: usize, angle_degrees: f32) -> Result<(), Error> {
+    // Ensure the kernel size is odd (required for centering)
+    let length = ensure_odd_size(length, "Motion blur length");
+
+    // Load the image and convert it to RGB format
+    let image = image::open(input_path)?
+        .into_rgb8();
+
+    // Generate the motion kernel and apply it (horizontal and vertical streaks take the separable path)
+    let kernel = motion_blur_kernel(length, angle_degrees, image.dimensions());
+    let blurred_image = convolve(&image, &kernel);
+
+    // Save the motion-blurred image
+    save_image(DynamicImage::ImageRgb8(blurred_image), output_path)?;
+
+    info!("Motion-blurred image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Sharpens an image using a convolutional sharpening filter.
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn sharpen_image(input_path: &str, output_path: &str, strength: f32) -> Result<(), Error> {
+    // Load the image and convert it to RGB format
+    let image = image::open(input_path)?
+        .into_rgb8();
+
+    // Generate the sharpening kernel
+    let kernel = generate_sharpen_kernel(strength);
+
+    // Apply the sharpening filter using convolution
+    let sharpened_image = apply_convolution(&image, &kernel, BorderMode::Clamp, 0.0);
+
+    // Save the sharpened image
+    save_image(DynamicImage::ImageRgb8(sharpened_image), output_path)?;
+
+    info!("Sharpened image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Bias that centers zero-sum kernels (like emboss) around mid-gray instead of black.
+pub const EMBOSS_BIAS: f32 = 128.0;
+
+/// Embosses an image so it looks like a raised relief lit from the top-left.
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn emboss_image(input_path: &str, output_path: &str) -> Result<(), Error> {
+    // Load the image and convert it to RGB format
+    let image = image::open(input_path)?
+        .into_rgb8();
+
+    // Generate the emboss kernel
+    let kernel = generate_emboss_kernel();
+
+    // Apply it with a mid-gray bias so flat regions don't collapse to black
+    let embossed_image = apply_convolution(&image, &kernel, BorderMode::Clamp, EMBOSS_BIAS);
+
+    // Save the embossed image
+    save_image(DynamicImage::ImageRgb8(embossed_image), output_path)?;
+
+    info!("Embossed image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Produces a Sobel edge map of an image (bright where brightness changes sharply).
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn edge_detect_image(input_path: &str, output_path: &str) -> Result<(), Error> {
+    // Load the image and convert it to RGB format
+    let image = image::open(input_path)?
+        .into_rgb8();
+
+    // Compute the gradient magnitude
+    let edge_image = sobel_edges(&image);
+
+    // Save the edge map
+    save_image(DynamicImage::ImageRgb8(edge_image), output_path)?;
+
+    info!("Edge map saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Convolves an image file with a caller-supplied kernel, such as one read by `load_kernel_from_file`.
+/// Returns an error if the kernel is invalid, the input cannot be opened or decoded, or the output cannot be saved.
+pub fn kernel_image(input_path: &str, output_path: &str, kernel: &[Vec<f32>]) -> Result<(), Error> {
+    validate_kernel(kernel)?;
+
+    // Load the image, keeping transparency when it has any
+    let image = image::open(input_path)?;
+
+    // Convolve and save the result
+    let convolved_image = apply_kernel(image, kernel, ChannelMask::ALL, BorderMode::Clamp)?;
+    save_image(convolved_image, output_path)?;
+
+    info!("Convolved image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Convolves the selected color channels of a decoded image with a caller-supplied kernel,
+/// reading pixels past the edges according to `border`.
+/// Arbitrary kernels may not sum to 1, so alpha is left untouched rather than convolved.
+/// Returns an error if `validate_kernel` rejects the kernel.
+pub fn apply_kernel(image: DynamicImage, kernel: &[Vec<f32>], channels: ChannelMask, border: BorderMode) -> Result<DynamicImage, Error> {
+    validate_kernel(kernel)?;
+
+    Ok(if let DynamicImage::ImageLuma8(gray) = &image
+        && channels == ChannelMask::ALL
+    {
+        DynamicImage::ImageLuma8(apply_convolution_luma8(gray, kernel, border, 0.0))
+    } else if is_16_bit(&image) {
+        map_16_bit(image, |rgb| channels.merge(rgb, &apply_convolution_u16(rgb, kernel, border, 0.0)))
+    } else if image.color().has_alpha() {
+        let image = image.into_rgba8();
+        let convolved = apply_convolution_rgba(&image, kernel, border, AlphaMode::Preserve);
+        DynamicImage::ImageRgba8(channels.merge(&image, &convolved))
+    } else {
+        DynamicImage::ImageRgb8(apply_convolution_masked(&image.into_rgb8(), kernel, border, 0.0, channels))
+    })
+}
+
+/// Applies any `Filter` to an image file and saves the result.
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn filter_image(input_path: &str, output_path: &str, filter: &Filter) -> Result<(), Error> {
+    // Load the image, keeping transparency when it has any
+    let image = image::open(input_path)?;
+
+    // Apply the filter and save the result
+    let gray = match &image {
+        DynamicImage::ImageLuma8(gray) => filter.apply_gray(gray),
+        _ => None,
+    };
+    let filtered_image = if let Some(gray) = gray {
+        DynamicImage::ImageLuma8(gray)
+    } else if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(filter.apply_rgba(&image.into_rgba8()))
+    } else {
+        DynamicImage::ImageRgb8(filter.apply(&image.into_rgb8()))
+    };
+    save_image(filtered_image, output_path)?;
+
+    info!("Filtered image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Runs a `Pipeline` of filters over an image file and saves only the final result.
+/// Returns an error if the input cannot be opened or decoded, or the output cannot be saved.
+pub fn pipeline_image(input_path: &str, output_path: &str, pipeline: &Pipeline) -> Result<(), Error> {
+    // Load the image, keeping transparency when it has any
+    let image = image::open(input_path)?;
+
+    // Thread the image through every stage and save the result
+    let processed_image = pipeline.apply_dynamic(image);
+    save_image(processed_image, output_path)?;
+
+    info!("Processed image saved to '{}'", output_path);
+    Ok(())
+}
+
+/// Reads an encoded image (any supported format) from `reader`, e.g. stdin, and decodes it.
+/// Returns an error if nothing was read or the bytes are not a valid image.
+pub fn load_image_from_reader<R: Read>(mut reader: R) -> Result<DynamicImage, Error> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    // An empty stream would otherwise surface as a confusing "format could not be determined" error
+    if buffer.is_empty() {
+        return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "no image data received")));
+    }
+
+    Ok(image::load_from_memory(&buffer)?)
+}
+
+/// Encoders that `write_image` and `save_image_as` can be told to use, whatever a file's extension says.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Bmp,
+    Tiff,
+}
+
+impl OutputFormat {
+    /// The format written for an extension such as `jpg` or `TIFF`, or `None` if this crate can't encode it.
+    pub fn from_extension(extension: &str) -> Option<OutputFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "bmp" => Some(OutputFormat::Bmp),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            _ => None,
+        }
+    }
+
+    /// Upper-case name for messages, e.g. `JPEG`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "PNG",
+            OutputFormat::Jpeg => "JPEG",
+            OutputFormat::Bmp => "BMP",
+            OutputFormat::Tiff => "TIFF",
+        }
+    }
+}
+
+/// Encodes an image as PNG and writes it to `writer` (e.g. stdout) in one go, for pipe-based workflows.
+pub fn write_png<W: Write>(image: &DynamicImage, writer: &mut W) -> Result<(), Error> {
+    write_image(image, writer, OutputFormat::Png)
+}
+
+/// Encodes an image as `format` and writes it to `writer` in one go. The encoders need a seekable target,
+/// so the image is encoded into memory first. 16-bit images are reduced to 8 bits for JPEG and BMP, and JPEG
+/// uses the `set_jpeg_quality` quality. Unlike `save_image`, which drops alpha for a `.jpg` name, asking for
+/// JPEG with an image that has transparency is an `Error::InvalidArgument`.
+pub fn write_image<W: Write>(image: &DynamicImage, writer: &mut W, format: OutputFormat) -> Result<(), Error> {
+    if format == OutputFormat::Jpeg && image.color().has_alpha() {
+        return Err(Error::InvalidArgument(
+            "JPEG has no alpha channel, so the image's transparency would be lost; use png, bmp or tiff instead".to_string(),
+        ));
+    }
+
+    let reduced;
+    let image = if matches!(format, OutputFormat::Jpeg | OutputFormat::Bmp) && is_16_bit(image) {
+        reduced = if image.color().has_alpha() { DynamicImage::ImageRgba8(image.to_rgba8()) } else { DynamicImage::ImageRgb8(image.to_rgb8()) };
+        &reduced
+    } else {
+        image
+    };
+    let output_format = match format {
+        OutputFormat::Png => ImageOutputFormat::Png,
+        OutputFormat::Jpeg => ImageOutputFormat::Jpeg(JPEG_QUALITY.load(Ordering::Relaxed)),
+        OutputFormat::Bmp => ImageOutputFormat::Bmp,
+        OutputFormat::Tiff => ImageOutputFormat::Tiff,
+    };
+
+    let mut buffer = Cursor::new(Vec::new());
+    image.write_to(&mut buffer, output_format)?;
+    writer.write_all(buffer.get_ref())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Encodes an RGB image as a JPEG of the given `quality` (1–100, clamped) into `writer`.
+/// `save_image` uses it with the `set_jpeg_quality` setting.
+pub fn write_jpeg<W: Write>(image: &RgbImage, writer: W, quality: u8) -> Result<(), Error> {
+    JpegEncoder::new_with_quality(writer, quality.clamp(1, 100)).encode(image.as_raw(), image.width(), image.height(), ColorType::Rgb8)?;
+    Ok(())
+}
+
+/// Saves an image with the encoder `format` whatever the extension of `output_path`, e.g. a PNG named `photo.jpg`.
+/// Fails like `write_image` if `format` can't represent the image.
+pub fn save_image_as(image: &DynamicImage, output_path: &str, format: OutputFormat) -> Result<(), Error> {
+    if let Some(parent) = std::path::Path::new(output_path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    write_image(image, &mut BufWriter::new(File::create(output_path)?), format)
+}
+
+/// Saves an image, dropping alpha first when the target format (JPEG) cannot store it
+/// and reducing 16-bit images to 8 bits for formats other than PNG and TIFF.
+/// Missing parent directories of `output_path` are created.
+pub fn save_image(image: DynamicImage, output_path: &str) -> Result<(), Error> {
+    // Explicit `--output` paths may point into folders that don't exist yet
+    if let Some(parent) = std::path::Path::new(output_path).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let extension = std::path::Path::new(output_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let is_jpeg = matches!(extension.as_str(), "jpg" | "jpeg");
+    let keeps_16_bit = matches!(extension.as_str(), "png" | "tif" | "tiff");
+
+    if is_jpeg {
+        // JPEG has no alpha or 16-bit support, so everything is flattened to 8-bit RGB
+        write_jpeg(&image.into_rgb8(), BufWriter::new(File::create(output_path)?), JPEG_QUALITY.load(Ordering::Relaxed))?;
+    } else if is_16_bit(&image) && !keeps_16_bit {
+        let image = if image.color().has_alpha() {
+            DynamicImage::ImageRgba8(image.into_rgba8())
+        } else {
+            DynamicImage::ImageRgb8(image.into_rgb8())
+        };
+        image.save(output_path)?;
+    } else {
+        image.save(output_path)?;
+    }
+    Ok(())
+}
+
+/// Saves an image over `path`, e.g. to replace the input it was made from. The image is written to a hidden
+/// temporary file next to `path` first and then renamed over it, so a failed encode or a full disk leaves
+/// the original untouched instead of truncated.
+pub fn replace_image(image: DynamicImage, path: &str) -> Result<(), Error> {
+    let target = std::path::Path::new(path);
+    let name = target.file_name().map_or("image".into(), |name| name.to_string_lossy());
+    // The temporary name keeps the extension, which `save_image` picks the format from
+    let extension = target.extension().map(|extension| format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+    let temporary = target.with_file_name(format!(".{}.kernel-rs-tmp{}", name, extension));
+    let temporary = temporary.to_string_lossy();
+
+    if let Err(err) = save_image(image, &temporary) {
+        let _ = std::fs::remove_file(&*temporary);
+        return Err(err);
+    }
+    std::fs::rename(&*temporary, target).inspect_err(|_| {
+        let _ = std::fs::remove_file(&*temporary);
+    })?;
+    Ok(())
+}
+
+/// Keeps the luminance of `processed` but takes the chroma from `original` (see `merge_luma_f32`), preserving
+/// the bit depth and the alpha of `processed`. Both images must have the same dimensions.
+pub fn merge_luma(original: &DynamicImage, processed: DynamicImage) -> DynamicImage {
+    if is_16_bit(&processed) {
+        let original = from_u16(&original.to_rgb16());
+        return map_16_bit(processed, |rgb| to_u16(&merge_luma_f32(&original, &from_u16(rgb))));
+    }
+
+    let original = to_f32(&original.to_rgb8());
+    if !processed.color().has_alpha() {
+        return DynamicImage::ImageRgb8(to_u8(&merge_luma_f32(&original, &to_f32(&processed.into_rgb8()))));
+    }
+
+    let mut output = processed.into_rgba8();
+    let color = to_u8(&merge_luma_f32(&original, &to_f32(&DynamicImage::ImageRgba8(output.clone()).into_rgb8())));
+    for (pixel, color) in output.pixels_mut().zip(color.pixels()) {
+        pixel.0[..3].copy_from_slice(&color.0);
+    }
+    DynamicImage::ImageRgba8(output)
+}
+
+/// Blends `processed` over `original` through `mask` like `apply_masked`, for any decoded image: alpha is blended
+/// along with color and the result keeps the color type of `processed`. All three must have the same dimensions.
+pub fn merge_masked(original: &DynamicImage, processed: DynamicImage, mask: &GrayImage) -> DynamicImage {
+    if let DynamicImage::ImageRgb8(filtered) = &processed {
+        return DynamicImage::ImageRgb8(apply_masked(&original.to_rgb8(), filtered, mask));
+    }
+
+    // Everything else mixes in f32 RGBA (0–1), which holds 8- and 16-bit values alike
+    assert_eq!(processed.dimensions(), mask.dimensions(), "Mask and image differ in size");
+    let color = processed.color();
+    let original = original.to_rgba32f();
+    let mut output = processed.into_rgba32f();
+    output
+        .par_chunks_mut(4)
+        .zip(original.par_chunks(4))
+        .zip(mask.par_iter())
+        .for_each(|((pixel, original), &weight)| {
+            let weight = weight as f32 / 255.0;
+            for (value, &original) in pixel.iter_mut().zip(original) {
+                *value = original + (*value - original) * weight;
+            }
+        });
+
+    let output = DynamicImage::ImageRgba32F(output);
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(output.into_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(output.into_luma_alpha8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(output.into_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(output.into_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(output.into_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(output.into_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(output.into_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(output.into_rgb32f()),
+        _ => output,
+    }
+}
+
+/// Whether the decoded image has 16 bits per channel.
+pub(crate) fn is_16_bit(image: &DynamicImage) -> bool {
+    matches!(image, DynamicImage::ImageLuma16(_) | DynamicImage::ImageLumaA16(_) | DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_))
+}
+
+/// Runs `apply` on the 16-bit RGB channels of an image, putting the original alpha back afterwards if it had any.
+pub(crate) fn map_16_bit(image: DynamicImage, apply: impl Fn(&RgbImage16) -> RgbImage16) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return DynamicImage::ImageRgb16(apply(&image.into_rgb16()));
+    }
+
+    let rgba = image.into_rgba16();
+    let rgb = apply(&DynamicImage::ImageRgba16(rgba.clone()).into_rgb16());
+    let mut output = rgba;
+    for (pixel, color) in output.pixels_mut().zip(rgb.pixels()) {
+        pixel.0[..3].copy_from_slice(&color.0);
+    }
+    DynamicImage::ImageRgba16(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A fresh directory under the system temp dir for one test's files.
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("kernel-rs-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn path_in(dir: &std::path::Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn missing_input_is_an_io_error() {
+        let dir = scratch_dir("missing-input");
+        let (input, output) = (path_in(&dir, "missing.png"), path_in(&dir, "out.png"));
+
+        assert!(matches!(blur_image(&input, &output, 3, BlurKind::Box), Err(Error::Io(err)) if err.kind() == io::ErrorKind::NotFound));
+        assert!(matches!(sharpen_image(&input, &output, 1.0), Err(Error::Io(err)) if err.kind() == io::ErrorKind::NotFound));
+        assert!(!std::path::Path::new(&output).exists());
+    }
+
+    #[test]
+    fn corrupt_input_is_a_decode_error() {
+        let dir = scratch_dir("corrupt-input");
+        let (input, output) = (path_in(&dir, "corrupt.png"), path_in(&dir, "out.png"));
+        // A valid PNG signature followed by garbage
+        std::fs::write(&input, b"\x89PNG\r\n\x1a\nnot really an image").unwrap();
+
+        assert!(matches!(blur_image(&input, &output, 3, BlurKind::Box), Err(Error::ImageDecode(_))));
+        assert!(matches!(sharpen_image(&input, &output, 1.0), Err(Error::ImageDecode(_))));
+        assert!(!std::path::Path::new(&output).exists());
+    }
+
+    #[test]
+    fn png_input_blurs_to_a_valid_png() {
+        let dir = scratch_dir("png-blur");
+        let (input, output) = (path_in(&dir, "gradient.png"), path_in(&dir, "gradient_blurred.png"));
+        save_image(DynamicImage::ImageRgb8(generate_test_image(12, 8, TestPattern::Gradient)), &input).unwrap();
+
+        blur_image(&input, &output, 3, BlurKind::Box).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), image::ImageFormat::Png);
+        assert_eq!(image::load_from_memory(&bytes).unwrap().dimensions(), (12, 8));
+    }
+
+    #[test]
+    fn blur_keeps_the_alpha_channel() {
+        let dir = scratch_dir("alpha-blur");
+        let (input, output) = (path_in(&dir, "translucent.png"), path_in(&dir, "translucent_blurred.png"));
+        let translucent = image::RgbaImage::from_fn(9, 9, |x, y| image::Rgba([(x * 28) as u8, (y * 28) as u8, 90, 128]));
+        save_image(DynamicImage::ImageRgba8(translucent), &input).unwrap();
+
+        blur_image(&input, &output, 5, BlurKind::Gaussian { sigma: 0.0 }).unwrap();
+
+        let blurred = image::open(&output).unwrap();
+        assert_eq!(blurred.color(), ColorType::Rgba8);
+        assert!(blurred.into_rgba8().pixels().all(|pixel| pixel[3] == 128));
+    }
+
+    #[test]
+    fn jpeg_quality_changes_the_file_size() {
+        let image = generate_test_image(64, 64, TestPattern::Checkerboard { cell: 3 });
+        let size_at = |quality: u8| {
+            let mut encoded = Vec::new();
+            write_jpeg(&image, &mut encoded, quality).unwrap();
+            encoded.len()
+        };
+
+        let (low, high) = (size_at(20), size_at(95));
+        assert!(low < high, "{} vs {} bytes", low, high);
+        // Out-of-range qualities are clamped rather than rejected
+        assert_eq!(size_at(0), size_at(1));
+        assert_eq!(size_at(255), size_at(100));
+    }
+
+    #[test]
+    fn grayscale_input_is_blurred_on_one_channel() {
+        let dir = scratch_dir("gray-blur");
+        let (input, output) = (path_in(&dir, "gray.png"), path_in(&dir, "gray_blurred.png"));
+        let gray = image::GrayImage::from_fn(256, 256, |x, y| image::Luma([((x * 7 + y * 13) % 256) as u8]));
+        save_image(DynamicImage::ImageLuma8(gray.clone()), &input).unwrap();
+
+        blur_image(&input, &output, 9, BlurKind::Gaussian { sigma: 2.0 }).unwrap();
+        let blurred = image::open(&output).unwrap();
+        assert_eq!(blurred.color(), ColorType::L8);
+
+        // The single-channel convolution, and within rounding of blurring the gray as RGB
+        let kernel = generate_gaussian_kernel(9, 2.0);
+        let blurred = blurred.into_luma8();
+        assert_eq!(blurred, apply_convolution_luma8(&gray, &kernel, BorderMode::Clamp, 0.0));
+        let rgb = to_luma8(&convolve(&DynamicImage::ImageLuma8(gray).into_rgb8(), &kernel));
+        for ((x, y, pixel), rgb) in blurred.enumerate_pixels().zip(rgb.pixels()) {
+            assert!(pixel[0].abs_diff(rgb[0]) <= 1, "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn format_override_ignores_the_extension() {
+        let dir = scratch_dir("format-override");
+        let path = path_in(&dir, "photo.jpg");
+        let image = DynamicImage::ImageRgb8(generate_test_image(8, 6, TestPattern::Gradient));
+
+        save_image_as(&image, &path, OutputFormat::Png).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), image::ImageFormat::Png);
+        // PNG is lossless, so the pixels come back exactly
+        assert_eq!(image::load_from_memory(&bytes).unwrap().into_rgb8(), image.into_rgb8());
+
+        let transparent = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+        let err = save_image_as(&transparent, &path_in(&dir, "alpha.png"), OutputFormat::Jpeg).unwrap_err();
+        assert!(err.to_string().starts_with("JPEG has no alpha channel"), "{}", err);
+    }
+
+    #[test]
+    fn reader_input_decodes_and_rejects_empty_or_invalid_data() {
+        let image = generate_test_image(8, 6, TestPattern::Gradient);
+        let mut encoded = Vec::new();
+        DynamicImage::ImageRgb8(image.clone()).write_to(&mut io::Cursor::new(&mut encoded), image::ImageFormat::Png).unwrap();
+        assert_eq!(load_image_from_reader(encoded.as_slice()).unwrap().into_rgb8(), image);
+
+        let empty = load_image_from_reader(io::empty()).unwrap_err();
+        assert!(matches!(&empty, Error::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof), "{}", empty);
+        let garbage = load_image_from_reader(&b"not an image"[..]).unwrap_err();
+        assert!(matches!(garbage, Error::UnsupportedFormat(_)), "{}", garbage);
+    }
+
+    #[test]
+    fn invalid_kernels_are_errors_not_panics() {
+        let dir = scratch_dir("invalid-kernel");
+        let input = path_in(&dir, "input.png");
+        save_image(DynamicImage::ImageRgb8(image::RgbImage::new(4, 4)), &input).unwrap();
+
+        let even = vec![vec![0.25; 2]; 2];
+        let err = kernel_image(&input, &path_in(&dir, "output.png"), &even).unwrap_err();
+        assert!(matches!(err, Error::InvalidKernel(KernelError::EvenDimension { width: 2, height: 2 })), "{}", err);
+        assert!(!std::path::Path::new(&path_in(&dir, "output.png")).exists());
+
+        let image = DynamicImage::ImageLuma8(image::GrayImage::new(4, 4));
+        let ragged = vec![vec![1.0; 3], vec![1.0]];
+        let err = apply_kernel(image, &ragged, ChannelMask::ALL, BorderMode::Clamp).unwrap_err();
+        assert!(matches!(err, Error::InvalidKernel(KernelError::RaggedRows { row: 2, .. })), "{}", err);
+    }
+}
@@ -0,0 +1,134 @@
+//! Leveled logging to stderr behind the `warn!`, `info!` and `debug!` macros.
+//!
+//! This stands in for the `log` crate with an `env_logger` backend: the crate keeps to `image` and `rayon`,
+//! and three levels, one atomic and `KERNEL_RS_LOG` (read in `main`) cover everything the binary reports.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much the crate reports on stderr, from least to most chatty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Only warnings, e.g. an even kernel size being rounded up.
+    Warn,
+    /// Warnings plus short status lines such as "Blurred image saved to ...". The default.
+    Info,
+    /// Everything, including image dimensions, kernels, thread counts and per-stage timings.
+    Debug,
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Sets the most detailed level that is still written to stderr.
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether messages at `level` are currently written.
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Writes one record to stderr if `level` is enabled; use the `warn!`, `info!` and `debug!` macros instead.
+/// Stdout is never touched, so `--stdout` output stays a clean image.
+pub fn write_log(level: LogLevel, message: fmt::Arguments) {
+    if !log_enabled(level) {
+        return;
+    }
+    #[cfg(test)]
+    tests::capture(level, &message);
+    match level {
+        LogLevel::Warn => eprintln!("Warning: {}", message),
+        LogLevel::Info => eprintln!("{}", message),
+        LogLevel::Debug => eprintln!("[debug] {}", message),
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    /// Parses `warn`, `info` or `debug` (case-insensitive).
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            other => Err(format!("unknown log level '{}', expected warn, info or debug", other)),
+        }
+    }
+}
+
+/// Logs a warning, e.g. `warn!("Kernel size {} is even", size)`.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::write_log($crate::LogLevel::Warn, format_args!($($arg)*))
+    };
+}
+
+/// Logs a status line shown by default.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::write_log($crate::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+/// Logs a diagnostic shown only with `--verbose`. The arguments are not formatted when debug logging is off.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log_enabled($crate::LogLevel::Debug) {
+            $crate::write_log($crate::LogLevel::Debug, format_args!($($arg)*))
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Filter, Pipeline};
+    use image::RgbImage;
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// Records written on this thread, so parallel tests don't see each other's messages.
+        static RECORDS: RefCell<Vec<(LogLevel, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Called by `write_log` for every record that passes the level filter.
+    pub(super) fn capture(level: LogLevel, message: &fmt::Arguments) {
+        RECORDS.with(|records| records.borrow_mut().push((level, message.to_string())));
+    }
+
+    /// Everything logged on this thread since the last call, for tests of other modules' messages.
+    pub(crate) fn take_records() -> Vec<(LogLevel, String)> {
+        RECORDS.with(|records| records.take())
+    }
+
+    #[test]
+    fn debug_records_are_emitted_only_at_debug_level() {
+        let image = RgbImage::new(4, 4);
+        let pipeline = Pipeline::from(Filter::Invert);
+
+        // Both halves share the global level, so they run in one test
+        set_log_level(LogLevel::Debug);
+        pipeline.apply(&image);
+        let records = take_records();
+        assert!(records.iter().any(|(level, message)| *level == LogLevel::Debug && message.starts_with("Stage inverted took")), "{:?}", records);
+
+        set_log_level(LogLevel::Info);
+        pipeline.apply(&image);
+        crate::info!("status");
+        assert_eq!(take_records(), vec![(LogLevel::Info, "status".to_string())]);
+    }
+
+    #[test]
+    fn levels_are_ordered_and_parsed() {
+        assert!(LogLevel::Warn < LogLevel::Info && LogLevel::Info < LogLevel::Debug);
+        assert_eq!("DEBUG".parse(), Ok(LogLevel::Debug));
+        assert_eq!(" warning ".parse(), Ok(LogLevel::Warn));
+        assert_eq!("loud".parse::<LogLevel>(), Err("unknown log level 'loud', expected warn, info or debug".to_string()));
+    }
+}
@@ -1,181 +1,1080 @@
-use std::io::{self, Write};
-use std::path::Path;
-use std::fs;
-use image::{RgbImage, Rgb, ImageBuffer};
-use rayon::prelude::*;
-
-fn generate_box_blur_kernel(size: usize) -> Vec<Vec<f32>> {
-    // Filled with 1/(n*n) to average (blur) neighboring pixels
-    // Kernel size must be odd to ensure the center pixel is included.
-    // Kernel is normalized to ensure output has the same brightness as the input.
-    let value = 1.0 / (size * size) as f32;
-    vec![vec![value; size]; size]
-}
-
-/// Applies an (n x n) convolution kernel to an RGB image using multi-threading.
-/// Each color channel (R, G, B) is processed independently.
-fn apply_convolution(image: &RgbImage, kernel: &Vec<Vec<f32>>) -> RgbImage {
-    let (width, height) = image.dimensions();
-    let kernel_size = kernel.len();
-    let half_k = kernel_size as i32 / 2;
-
-    // Create an empty output image with the same dimensions
-    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
-
-    // Uses Rayon to parallelize row processing
-    output
-        .enumerate_rows_mut()
-        .par_bridge() // Convert to parallel iterator
-        .for_each(|(_y, row)| {
-            for (x, _y, pixel) in row {
-                let mut sum_r = 0.0;
-                let mut sum_g = 0.0;
-                let mut sum_b = 0.0;
-
-                // Applies the kernel over the pixel neighborhood
-                for ky in 0..kernel_size {
-                    for kx in 0..kernel_size {
-                        let nx = (x as i32 + kx as i32 - half_k).clamp(0, (width - 1) as i32) as u32;
-                        let ny = (_y as i32 + ky as i32 - half_k).clamp(0, (height - 1) as i32) as u32;
-
-                        let neighbor_pixel = image.get_pixel(nx, ny);
-                        sum_r += neighbor_pixel[0] as f32 * kernel[ky][kx];
-                        sum_g += neighbor_pixel[1] as f32 * kernel[ky][kx];
-                        sum_b += neighbor_pixel[2] as f32 * kernel[ky][kx];
-                    }
-                }
+mod cli;
+mod glob;
+mod job;
+mod json;
+mod manifest;
+mod preset;
+
+use std::io::{self, IsTerminal, Write};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use std::{env, fs, panic, process, thread};
+use image::DynamicImage;
+use job::{load_input, save_output, Job, Mask, Operation, STDIN_PATH};
+use manifest::{Manifest, MANIFEST_FILE};
+use kernel_rs::{blur_image, debug, edge_detect_image, emboss_image, ensure_odd_size, filter_image, generate_test_image, info, load_kernel_from_file, merge_channels, motion_blur_image, normalize_kernel, psnr, render_kernel, render_preview, save_image, set_jpeg_quality, set_log_level, set_row_progress, set_schedule, set_sequential, set_soft_clip, set_tile_height, sharpen_image, split_channels, ssim, to_luma8, unsharp_amount, warn, write_image, write_png, BlurKind, BorderMode, ColorSpace, EdgeOperator, Error, Filter, LogLevel, OutputFormat, PreviewStyle, ProgressBar, TestPattern, DEFAULT_PREVIEW_WIDTH, DEFAULT_SEED};
+
+/// Extensions recognized as input images.
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "bmp", "tif", "tiff", "webp", "gif"];
+
+/// Upper bound on images decoded at the same time in batch mode, to keep memory use in check.
+const MAX_CONCURRENT_DECODES: usize = 4;
+
+
+/// Whether the path is a file with one of the supported image extensions (case-insensitive).
+fn is_supported_image(path: &Path) -> bool {
+    path.is_file() && path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// File name patterns (see `glob::matches`) for the outputs a run derives from its inputs with each of `suffixes`,
+/// e.g. `?*_blurred_5.*` for `cat_blurred_5.png`: any stem, then the suffix, then any extension.
+fn output_patterns(suffixes: &[&str]) -> Vec<String> {
+    suffixes.iter().map(|suffix| format!("?*{}.*", glob::escape(&format!("_{}", suffix)))).collect()
+}
+
+/// The `output_patterns` of what `job` writes when it names outputs after their inputs, laid out by its
+/// `--output-suffix` template if it has one (`{stem}-{filter}{ext}` gives `?*-blurred.*`).
+fn job_output_patterns(job: &Job) -> Vec<String> {
+    let Some(template) = &job.output_suffix else {
+        return output_patterns(&[&job.suffix()]);
+    };
+    // Expanded around stand-ins for the stem and extension, which then become wildcards
+    let (stem, extension) = ("\u{0}", "\u{1}");
+    let name = glob::escape(&expand_output_template(template, stem, &job.suffix(), extension));
+    vec![name.replace(stem, "?*").replace(extension, "*")]
+}
+
+/// Whether the file is named like an output of the current run (one of `outputs`, from `output_patterns`).
+/// Such files are skipped as inputs, so re-runs don't stack `_blurred_5_blurred_5`; inputs that merely contain
+/// a filter's word, like `beach_motion.jpg`, or outputs of other filters are still read.
+fn is_generated_output(path: &Path, outputs: &[String]) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| outputs.iter().any(|pattern| glob::matches(pattern, name)))
+}
+
+/// Folder the interactive menu, `--batch` and a missing `--input` look for images in.
+const INPUT_DIR: &str = "images";
+
+/// The first supported image in `images/` (or anywhere below it when `recursive`) by path order,
+/// leaving out previous `outputs` (see `is_generated_output`).
+fn find_image(recursive: bool, outputs: &[String]) -> Option<String> {
+    if !Path::new(INPUT_DIR).exists() {
+        eprintln!("Error: '{}/' folder does not exist. Please create it and add an image.", INPUT_DIR);
+        return None;
+    }
+
+    walk_images(Path::new(INPUT_DIR), recursive).into_iter().find(|path| !is_generated_output(path, outputs)).map(|path| {
+        path.to_string_lossy().into_owned()
+    }).or_else(|| {
+        eprintln!("Error: No images ({}) found in '{}/'. Please add an image and try again.", IMAGE_EXTENSIONS.join(", "), INPUT_DIR);
+        None
+    })
+}
 
-                // Clamp values and assign them to the output pixel
-                pixel.0[0] = sum_r.round().clamp(0.0, 255.0) as u8;
-                pixel.0[1] = sum_g.round().clamp(0.0, 255.0) as u8;
-                pixel.0[2] = sum_b.round().clamp(0.0, 255.0) as u8;
+/// Lists every supported image inside `images/` (and its subfolders when `recursive`), sorted by path,
+/// leaving out previous `outputs` (see `is_generated_output`).
+fn find_images(recursive: bool, outputs: &[String]) -> Vec<String> {
+    if !Path::new(INPUT_DIR).is_dir() {
+        eprintln!("Error: '{}/' folder does not exist. Please create it and add some images.", INPUT_DIR);
+        return Vec::new();
+    }
+
+    let (generated, images): (Vec<_>, Vec<_>) = walk_images(Path::new(INPUT_DIR), recursive)
+        .into_iter()
+        .partition(|path| is_generated_output(path, outputs));
+
+    if !generated.is_empty() {
+        info!("Skipping {} previously generated output(s) in '{}/'", generated.len(), INPUT_DIR);
+    }
+
+    images.into_iter().map(|path| path.to_string_lossy().into_owned()).collect()
+}
+
+/// The supported images a wildcard `--input` matches, leaving out previous `outputs` (see `is_generated_output`).
+/// Fails when nothing matches, since running on no files is almost certainly a typo.
+fn expand_pattern(pattern: &str, outputs: &[String]) -> Result<Vec<String>, Error> {
+    let (generated, images): (Vec<_>, Vec<_>) = glob::expand(pattern)
+        .into_iter()
+        .filter(|path| is_supported_image(path))
+        .partition(|path| is_generated_output(path, outputs));
+
+    if images.is_empty() {
+        return Err(Error::InvalidArgument(format!("no images ({}) match '{}'", IMAGE_EXTENSIONS.join(", "), pattern)));
+    }
+    if !generated.is_empty() {
+        info!("Skipping {} previously generated output(s) matching '{}'", generated.len(), pattern);
+    }
+
+    Ok(images.into_iter().map(|path| path.to_string_lossy().into_owned()).collect())
+}
+
+/// Collects the supported image files in `directory`, descending into subfolders when `recursive`, sorted by path.
+/// Symlinked folders are followed, but each real folder is only read once so link cycles can't loop forever.
+fn walk_images(directory: &Path, recursive: bool) -> Vec<PathBuf> {
+    let mut images = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![directory.to_path_buf()];
+
+    while let Some(directory) = pending.pop() {
+        // Unreadable folders are skipped rather than failing the whole run
+        let Ok(entries) = fs::read_dir(&directory) else { continue };
+        if !visited.insert(fs::canonicalize(&directory).unwrap_or_else(|_| directory.clone())) {
+            continue;
+        }
+
+        for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if is_supported_image(&path) {
+                images.push(path);
             }
-        });
+        }
+    }
 
-    output
+    images.sort();
+    images
 }
 
-/// Blurs an image using a dynamically generated box blur kernel with multi-threading.
-fn blur_image(input_path: &str, output_path: &str, blur_size: usize) {
-    // Ensure the kernel size is odd (required for centering)
-    let blur_size = if blur_size % 2 == 0 { blur_size + 1 } else { blur_size };
+/// Where the output of a discovered input goes: its folder relative to `images/`, inside `output_dir`.
+/// `images/cats/tabby.png` with `out` gives `out/cats`; inputs outside `images/` use `output_dir` as is.
+fn output_dir_for(output_dir: &str, image_path: &str) -> String {
+    let relative = Path::new(image_path).parent().and_then(|parent| parent.strip_prefix(INPUT_DIR).ok());
+    match relative {
+        Some(relative) => Path::new(output_dir).join(relative).to_string_lossy().into_owned(),
+        None => output_dir.to_string(),
+    }
+}
 
-    // Load the image and convert it to RGB format
-    let image = image::open(input_path)
-        .expect("Failed to open image")
-        .into_rgb8(); // Convert to RGB format
+/// Prints a prompt without a trailing newline and returns the trimmed line the user enters.
+fn prompt(message: &str) -> String {
+    print!("{}", message);
+    let _ = io::stdout().flush(); // Ensure is displayed immediately
 
-    // Generate the blur kernel dynamically
-    let kernel = generate_box_blur_kernel(blur_size);
+    // A closed or unreadable stdin reads as an empty answer, which picks the defaults
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_err() {
+        input.clear();
+    }
+    input.trim().to_string()
+}
 
-    // Apply the blur using convolution (multi-threaded)
-    let blurred_image = apply_convolution(&image, &kernel);
+/// Builds the output path `images/<stem>_<suffix>.<ext>` for the given input image.
+/// The extension follows `format` when given, otherwise the input's own extension so a PNG in yields a PNG out.
+/// WebP can be decoded but not encoded with the enabled `image` features, so WebP inputs fall back to PNG.
+fn output_path(image_path: &str, suffix: &str, format: Option<&str>) -> String {
+    output_path_in(INPUT_DIR, image_path, suffix, None, format)
+}
+
+/// Same as `output_path` but inside `directory` instead of `images/`, and named by the `--output-suffix`
+/// `template` if there is one (see `expand_output_template`).
+fn output_path_in(directory: &str, image_path: &str, suffix: &str, template: Option<&str>, format: Option<&str>) -> String {
+    let path = Path::new(image_path);
+    // Paths without a file name (e.g. `..`) still get a usable output name
+    let stem = path.file_stem().map_or("image".into(), |stem| stem.to_string_lossy());
+    let extension = match format {
+        Some(format) => format.to_string(),
+        None => match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("webp") => "png".to_string(),
+            Some(ext) => ext.to_string(),
+            None => "jpg".to_string(),
+        },
+    };
+    let file_name = match template {
+        Some(template) => expand_output_template(template, &stem, suffix, &extension),
+        None => format!("{}_{}.{}", stem, suffix, extension),
+    };
+    Path::new(directory).join(file_name).to_string_lossy().into_owned()
+}
 
-    // Save the blurred image
-    blurred_image.save(output_path)
-        .expect("Failed to save blurred image");
+/// Fills in an `--output-suffix` template: `{stem}` is the input's name without extension, `{ext}` the output
+/// extension with its dot, and `{filter}` and `{param}` split the default suffix at its trailing number, so
+/// `blurred_5` gives `blurred` and `5` (a suffix without one, like `sharpened`, leaves `{param}` empty).
+/// A template without `{ext}` gets the extension appended. The placeholders were checked when parsing.
+fn expand_output_template(template: &str, stem: &str, suffix: &str, extension: &str) -> String {
+    let (filter, param) = match suffix.rsplit_once('_') {
+        Some((filter, param)) if !param.is_empty() && param.bytes().all(|byte| byte.is_ascii_digit()) => (filter, param),
+        _ => (suffix, ""),
+    };
+    let extension = format!(".{}", extension);
+
+    // One pass over the template, so a `{` in the stem itself is never taken for a placeholder
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let (placeholder, after) = rest[start + 1..].split_once('}').expect("Template placeholders are checked by the parser");
+        name.push_str(&rest[..start]);
+        name.push_str(match placeholder {
+            "stem" => stem,
+            "filter" => filter,
+            "param" => param,
+            _ => &extension,
+        });
+        rest = after;
+    }
+    name.push_str(rest);
+
+    if template.contains("{ext}") { name } else { name + &extension }
+}
 
-    println!("Blurred image saved to '{}'", output_path);
+/// Whether `--output` names a directory (an existing one, or any path ending in a separator)
+/// rather than a file, in which case outputs keep their derived names inside it.
+fn is_output_directory(output: &str) -> bool {
+    output.ends_with('/') || output.ends_with(std::path::MAIN_SEPARATOR) || Path::new(output).is_dir()
 }
 
-fn generate_sharpen_kernel() -> Vec<Vec<f32>> {
-    // This kernel is used to enhance edges in an image.
-    // The center pixel is given a higher weight (5.0) to make it stand out more, while the neighboring pixels
-    // are given a negative weight (-1.0) to reduce their influence, effectively highlighting edges.
-    vec![
-        vec![0.0, -1.0,  0.0],
-        vec![-1.0, 5.0, -1.0],
-        vec![0.0, -1.0,  0.0],
-    ]
+/// Environment variable that sets the log level (warn, info or debug) when `--verbose` is not given.
+const LOG_ENV: &str = "KERNEL_RS_LOG";
+
+/// Sets the library's log level: debug with `--verbose`, otherwise `KERNEL_RS_LOG` or the default (info).
+fn configure_logging(verbose: bool) {
+    if verbose {
+        set_log_level(LogLevel::Debug);
+    } else if let Ok(value) = env::var(LOG_ENV) {
+        match value.parse() {
+            Ok(level) => set_log_level(level),
+            Err(err) => warn!("Ignoring {}: {}.", LOG_ENV, err),
+        }
+    }
 }
 
-/// Sharpens an image using a convolutional sharpening filter.
-fn sharpen_image(input_path: &str, output_path: &str) {
-    // Load the image and convert it to RGB format
-    let image = image::open(input_path)
-        .expect("Failed to open image")
-        .into_rgb8();
+/// Environment variable that caps the number of worker threads when `--threads` is not given.
+const THREADS_ENV: &str = "KERNEL_RS_THREADS";
 
-    // Generate the sharpening kernel
-    let kernel = generate_sharpen_kernel();
+/// Sets up the global Rayon pool with `threads` workers, falling back to `KERNEL_RS_THREADS`.
+/// 1 forces single-threaded execution and 0 (or neither being set) uses all cores.
+fn configure_threads(threads: Option<usize>) {
+    let threads = threads.or_else(|| {
+        let value = env::var(THREADS_ENV).ok()?;
+        match value.trim().parse() {
+            Ok(threads) => Some(threads),
+            Err(_) => {
+                warn!("Ignoring {}='{}', expected a whole number.", THREADS_ENV, value);
+                None
+            }
+        }
+    });
 
-    // Apply the sharpening filter using convolution
-    let sharpened_image = apply_convolution(&image, &kernel);
+    // Failing to size the pool isn't fatal; Rayon's default pool still works
+    if let Some(threads) = threads
+        && let Err(err) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
+    {
+        warn!("Could not set up {} worker threads ({}); using the default.", threads, err);
+    }
+}
 
-    // Save the sharpened image
-    sharpened_image.save(output_path)
-        .expect("Failed to save sharpened image");
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    println!("Sharpened image saved to '{}'", output_path);
+    // No arguments keeps the original interactive flow
+    if args.is_empty() {
+        configure_logging(false);
+        configure_threads(None);
+        set_row_progress(true);
+        interactive();
+    } else {
+        run_cli(args);
+    }
 }
 
+/// Runs a single filter, pipeline or custom kernel described by command-line flags.
+/// Usage errors exit with code 2 and processing failures with code 1.
+fn run_cli(args: Vec<String>) {
+    let args = match cli::parse_args(args) {
+        Ok(args) => args,
+        Err(err) => usage_error(&err),
+    };
 
-fn find_image() -> Option<String> {
-    let input_dir = "images/";
-    if !Path::new(input_dir).exists() {
-        println!("Error: '{}' folder does not exist. Please create it and add a .jpg file.", input_dir);
-        return None;
+    if args.help {
+        print!("{}", cli::usage());
+        return;
+    }
+    if args.list_filters {
+        print!("{}", cli::filter_list());
+        return;
+    }
+
+    if args.quiet && args.verbose {
+        usage_error("'--quiet' cannot be combined with '--verbose'");
+    }
+    configure_logging(args.verbose);
+
+    if args.single_thread && args.threads.is_some_and(|threads| threads != 1) {
+        usage_error("'--single-thread' cannot be combined with '--threads'");
+    }
+    configure_threads(if args.single_thread { Some(1) } else { args.threads });
+    debug!("Using {} worker thread(s)", rayon::current_num_threads());
+    set_sequential(args.single_thread);
+    set_soft_clip(args.soft_clip);
+
+    // Batch mode shows file progress instead, since several images convolve at once
+    set_row_progress(!args.quiet && !args.batch);
+    if let Some(rows) = args.tile_height {
+        set_tile_height(rows);
+    }
+    if let Some(schedule) = args.schedule {
+        set_schedule(schedule);
+    }
+    if let Some(quality) = args.quality {
+        set_jpeg_quality(quality);
+    }
+
+    if args.split_channels || args.merge_channels.is_some() {
+        run_channels(&args);
+        return;
+    }
+
+    if let Some([first, second]) = &args.compare {
+        run_compare(&args, first, second);
+        return;
+    }
+
+    if let Some((pattern, width, height)) = args.generate {
+        run_generate(&args, pattern, width, height);
+        return;
+    }
+
+    // '--luma-only' and '--channels' pick RGB or YCbCr parts of the result, which Lab filtering doesn't line up with
+    if args.color_space == Some(ColorSpace::Lab) && (args.kernel.is_some() || args.luma_only || args.channels.is_some()) {
+        usage_error("'--color-space lab' cannot be combined with '--kernel', '--luma-only' or '--channels'");
+    }
+
+    // Pixels past the edges read the background color instead of repeating the edge pixels
+    let border = args.border_color.map_or(BorderMode::Clamp, BorderMode::ConstantRgb);
+    let operation = match args.kernel() {
+        Ok(Some(kernel_path)) => match load_kernel_from_file(kernel_path) {
+            Ok(mut kernel) => {
+                if args.normalize {
+                    normalize_kernel(&mut kernel);
+                }
+                Operation::Kernel(kernel, args.channels.unwrap_or_default(), border, args.iterations.unwrap_or(1))
+            }
+            Err(err) => fail(&format!("Could not load kernel '{}'", kernel_path), &err),
+        },
+        Ok(None) => match args.pipeline() {
+            Ok(pipeline) => Operation::Pipeline(
+                pipeline
+                    .with_channels(args.channels.unwrap_or_default())
+                    .with_border(border)
+                    .with_color_space(args.color_space.unwrap_or_default())
+                    .with_iterations(args.iterations.unwrap_or(1)),
+            ),
+            Err(err) => usage_error(&err),
+        },
+        Err(err) => usage_error(&err),
+    };
+    let job = match args.resize() {
+        Ok(resize) => Job {
+            crop: args.crop,
+            flip: args.flip,
+            rotation: args.rotation,
+            max_dimension: args.max_dimension,
+            noise: args.noise,
+            seed: args.seed.unwrap_or(DEFAULT_SEED),
+            operation,
+            luma_only: args.luma_only,
+            mask: args.mask.as_deref().map(|path| Mask::load(path).unwrap_or_else(|err| fail(&format!("Could not load mask '{}'", path), &err))),
+            resize,
+            format: args.format.as_deref().and_then(OutputFormat::from_extension),
+            output_suffix: args.output_suffix.clone(),
+        },
+        Err(err) => usage_error(&err),
+    };
+
+    if args.luma_only && args.channels.is_some() {
+        usage_error("'--luma-only' cannot be combined with '--channels'");
+    }
+
+    if args.show_kernel {
+        run_show_kernel(&args, &job);
+        return;
+    }
+
+    if args.seed.is_some() && args.noise.is_none() {
+        usage_error("'--seed' only applies to randomized steps, which is just '--add-noise' for now");
+    }
+
+    // `--output some/dir/` keeps the derived names, just in another folder
+    let output_dir = args.output.as_deref().filter(|output| is_output_directory(output));
+    let output_file = args.output.as_deref().filter(|_| output_dir.is_none());
+
+    // A wildcard `--input` such as "photos/*.jpg" runs like `--batch` over the files it matches
+    let pattern = args.input.as_deref().filter(|input| glob::is_pattern(input));
+    let batch = args.batch || pattern.is_some();
+
+    if args.stdout && (batch || args.output.is_some()) {
+        usage_error("'--stdout' cannot be combined with '--batch', a wildcard '--input' or '--output'");
+    }
+
+    if args.preview && (batch || args.stdout) {
+        usage_error("'--preview' cannot be combined with '--batch', a wildcard '--input' or '--stdout'");
+    }
+    if args.preview_width.is_some() && !args.preview {
+        usage_error("'--preview-width' only applies to '--preview'");
+    }
+
+    if args.recursive && args.input.is_some() {
+        usage_error("'--recursive' cannot be combined with '--input'");
+    }
+
+    if args.skip_existing && !batch {
+        usage_error("'--skip-existing' only applies to '--batch' or a wildcard '--input'");
+    }
+    if args.resume && !batch {
+        usage_error("'--resume' only applies to '--batch' or a wildcard '--input'");
+    }
+
+    if args.in_place && (args.output.is_some() || args.stdout || args.format.is_some() || args.skip_existing) {
+        usage_error("'--in-place' writes back to the input; it cannot be combined with '--output', '--stdout', '--format' or '--skip-existing'");
+    }
+    if args.output_suffix.is_some() && (output_file.is_some() || args.stdout || args.in_place) {
+        usage_error("'--output-suffix' names derived outputs; it cannot be combined with an '--output' file, '--stdout' or '--in-place'");
+    }
+    if args.in_place && args.input.as_deref() == Some(STDIN_PATH) {
+        usage_error("'--in-place' needs an input file, not '--input -'");
     }
 
-    fs::read_dir(input_dir).ok()?.find_map(|entry| {
-        let path = entry.ok()?.path();
-        if path.is_file() && matches!(path.extension()?.to_str()?, "jpg" | "jpeg") {
-            Some(path.to_str()?.to_string())
+    if batch {
+        if args.batch && args.input.is_some() {
+            usage_error("'--batch' cannot be combined with '--input'; use a wildcard '--input' such as \"photos/*.jpg\" instead");
+        }
+        if output_file.is_some() || args.time {
+            usage_error("'--batch' and wildcard inputs cannot be combined with an '--output' file or '--time'; '--output' must be a folder");
+        }
+        if args.output_suffix.as_deref().is_some_and(|template| !template.contains("{stem}")) {
+            usage_error("'--output-suffix' needs {stem} with '--batch' or a wildcard '--input', or every input would get the same output name");
+        }
+        let outputs = job_output_patterns(&job);
+        let (images, source) = match pattern {
+            Some(pattern) => (expand_pattern(pattern, &outputs), pattern.to_string()),
+            None => (Ok(find_images(args.recursive, &outputs)), format!("{}/", INPUT_DIR)),
+        };
+        let destination = if args.in_place { Destination::InPlace } else { Destination::Folder(output_dir.unwrap_or(INPUT_DIR)) };
+        let manifest = batch_manifest(&job, args.resume);
+        let plan = images
+            .and_then(|images| plan_batch(&job, images, &source, destination, args.format.as_deref(), args.skip_existing, &manifest.finished()))
+            .unwrap_or_else(|err| fail("Nothing to process", &err));
+        if args.dry_run {
+            print_plan(&job, &plan);
         } else {
-            None
+            run_batch(&job, plan, &manifest, args.quiet);
         }
-    }).or_else(|| {
-        println!("Error: No .jpg files found in '{}'. Please add an image and try again.", input_dir);
-        None
-    })
+        return;
+    }
+
+    let discovered = args.input.is_none();
+    let image_path = match args.input.clone().or_else(|| find_image(args.recursive, &job_output_patterns(&job))) {
+        Some(file) => file,
+        None => process::exit(1),
+    };
+
+    // There is no file name to derive an output path from
+    if image_path == STDIN_PATH && !args.stdout && output_file.is_none() {
+        usage_error("'--input -' needs an '--output' file name or '--stdout'");
+    }
+
+    // `None` encodes straight to stdout; nothing else may be printed there or the image would be corrupted
+    let modified = (!args.stdout).then(|| match output_file {
+        Some(output) => output.to_string(),
+        None if args.in_place => image_path.clone(),
+        None => {
+            // Images found in a subfolder of `images/` keep that subfolder for their output
+            let directory = output_dir.unwrap_or(INPUT_DIR);
+            let directory = if discovered { output_dir_for(directory, &image_path) } else { directory.to_string() };
+            output_path_in(&directory, &image_path, &job.suffix(), job.output_suffix.as_deref(), args.format.as_deref())
+        }
+    });
+
+    let animation = modified.as_deref().is_some_and(|modified| job.is_animation(&image_path, modified));
+    if animation && (args.time || args.preview) {
+        usage_error("'--time' and '--preview' work on still images; GIF outputs are processed frame by frame");
+    }
+
+    if args.dry_run {
+        let encoder = job.format.unwrap_or(OutputFormat::Png).name();
+        let destination = modified.clone().unwrap_or_else(|| format!("stdout ({})", encoder));
+        print_plan(&job, &BatchPlan { tasks: vec![(image_path, destination)], skipped: Vec::new(), finished: Vec::new() });
+        return;
+    }
+
+    let result = if animation {
+        job.run(&image_path, modified.as_deref().expect("Animations have an output file"))
+    } else {
+        load_input(&image_path).and_then(|image| {
+            let processed = if args.time { apply_timed(&job, image)? } else { job.apply(image)? };
+            if args.preview {
+                print_preview(&processed, args.preview_width.unwrap_or(DEFAULT_PREVIEW_WIDTH));
+            }
+            match &modified {
+                Some(modified) => save_output(processed, &image_path, modified, job.format),
+                None => write_image(&processed, &mut io::stdout().lock(), job.format.unwrap_or(OutputFormat::Png)),
+            }
+        })
+    };
+
+    if let Err(err) = result {
+        fail(&format!("Could not process '{}'", image_path), &err);
+    }
+
+    if let Some(modified) = modified {
+        println!("Processing complete. Output saved as '{}'", modified);
+    }
 }
 
-fn main() {
+/// Handles `--split-channels` and `--merge-channels`, which write channel images instead of filtering.
+fn run_channels(args: &cli::Args) {
+    if args.split_channels && args.merge_channels.is_some() {
+        usage_error("'--split-channels' and '--merge-channels' are mutually exclusive");
+    }
+    if args.has_operation() || args.batch || args.stdout || args.crop.is_some() || args.resize.is_some() || args.noise.is_some() {
+        usage_error("'--split-channels' and '--merge-channels' cannot be combined with filters, '--batch', '--stdout' or other processing options");
+    }
+
+    let output_dir = args.output.as_deref().filter(|output| is_output_directory(output));
+    let format = args.format.as_deref();
+
+    if let Some([red, green, blue]) = &args.merge_channels {
+        if args.input.is_some() {
+            usage_error("'--merge-channels' takes its inputs as values; drop '--input'");
+        }
+        // `cat_r.png` merges into `cat_merged.png`
+        let destination = match args.output.as_deref().filter(|_| output_dir.is_none()) {
+            Some(output) => output.to_string(),
+            None => {
+                let red_path = Path::new(red);
+                let stem = red_path.file_stem().map_or("image".into(), |stem| stem.to_string_lossy());
+                let stem = stem.strip_suffix("_r").unwrap_or(&stem);
+                let base = match red_path.extension() {
+                    Some(extension) => red_path.with_file_name(format!("{}.{}", stem, extension.to_string_lossy())),
+                    None => red_path.with_file_name(stem),
+                };
+                output_path_in(output_dir.unwrap_or(INPUT_DIR), &base.to_string_lossy(), "merged", None, format)
+            }
+        };
+        if args.dry_run {
+            println!("Dry run: would merge R '{}', G '{}' and B '{}' into '{}'", red, green, blue, destination);
+            return;
+        }
+
+        let plane = |path: &str| load_input(path).map(|image| to_luma8(&image.into_rgb8()));
+        let result = plane(red)
+            .and_then(|r| Ok((r, plane(green)?, plane(blue)?)))
+            .and_then(|(r, g, b)| merge_channels(&r, &g, &b))
+            .and_then(|merged| save_image(DynamicImage::ImageRgb8(merged), &destination));
+        if let Err(err) = result {
+            fail("Could not merge channels", &err);
+        }
+        println!("Channels merged into '{}'", destination);
+        return;
+    }
+
+    if args.output.is_some() && output_dir.is_none() {
+        usage_error("'--split-channels' writes three files; give '--output' a directory (ending in /)");
+    }
+    let image_path = match args.input.clone().or_else(|| find_image(args.recursive, &output_patterns(&["r", "g", "b"]))) {
+        Some(file) => file,
+        None => process::exit(1),
+    };
+    let directory = output_dir.map_or_else(|| output_dir_for(INPUT_DIR, &image_path), str::to_string);
+    let destinations = ["r", "g", "b"].map(|channel| output_path_in(&directory, &image_path, channel, None, format));
+    if args.dry_run {
+        println!("Dry run: would split '{}' into '{}'", image_path, destinations.join("', '"));
+        return;
+    }
+
+    let result = load_input(&image_path).and_then(|image| {
+        for (channel, destination) in split_channels(&image.into_rgb8()).into_iter().zip(&destinations) {
+            save_image(DynamicImage::ImageRgb8(channel), destination)?;
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        fail(&format!("Could not split '{}'", image_path), &err);
+    }
+    println!("Channels saved as '{}'", destinations.join("', '"));
+}
+
+/// Side length in pixels `--show-kernel` scales small kernels up to, so each weight is a visible square.
+const KERNEL_VIEW_SIZE: u32 = 256;
+
+/// Handles `--show-kernel`, which saves a picture of the kernel instead of filtering.
+fn run_show_kernel(args: &cli::Args, job: &Job) {
+    if args.batch || args.input.is_some() || args.stdout || args.preview || args.time || args.iterations.is_some() {
+        usage_error("'--show-kernel' needs no image and cannot be combined with '--batch', '--input', '--stdout', '--preview', '--time' or '--iterations'");
+    }
+
+    let (name, kernel) = match &job.operation {
+        Operation::Kernel(kernel, ..) => {
+            let path = args.kernel.as_deref().unwrap_or("kernel");
+            let stem = Path::new(path).file_stem().map_or("kernel".into(), |stem| stem.to_string_lossy());
+            (stem.into_owned(), kernel.clone())
+        }
+        Operation::Pipeline(pipeline) => match &pipeline.stages[..] {
+            [filter] => match filter.kernel() {
+                Some(kernel) => (filter.suffix(), kernel),
+                None => usage_error(&format!("'{}' is not a single convolution, so it has no kernel to show", filter.suffix())),
+            },
+            _ => usage_error("'--show-kernel' shows one kernel; give a single '--filter' (without extra effects) or '--kernel'"),
+        },
+    };
+
+    let file_name = format!("{}_kernel.png", name);
+    let destination = match args.output.as_deref() {
+        Some(output) if is_output_directory(output) => Path::new(output).join(&file_name).to_string_lossy().into_owned(),
+        Some(output) => output.to_string(),
+        None => Path::new(INPUT_DIR).join(&file_name).to_string_lossy().into_owned(),
+    };
+    let (width, height) = (kernel.iter().map(Vec::len).max().unwrap_or(0), kernel.len());
+    if args.dry_run {
+        println!("Dry run: would save the {}x{} kernel as '{}'", width, height, destination);
+        return;
+    }
+
+    let cell = KERNEL_VIEW_SIZE / width.max(height).max(1) as u32;
+    if let Err(err) = save_image(DynamicImage::ImageLuma8(render_kernel(&kernel, cell)), &destination) {
+        fail("Could not save the kernel image", &err);
+    }
+    let weights = || kernel.iter().flatten().copied();
+    let (low, high) = (weights().fold(f32::INFINITY, f32::min), weights().fold(f32::NEG_INFINITY, f32::max));
+    println!("Kernel ({}x{}, weights {} to {}) saved as '{}'", width, height, low, high, destination);
+}
+
+/// Handles `--compare`, which prints how similar two images are instead of filtering.
+fn run_compare(args: &cli::Args, first: &str, second: &str) {
+    if args.has_operation() || args.batch || args.input.is_some() || args.output.is_some() || args.stdout || args.generate.is_some() {
+        usage_error("'--compare' takes its two images as values and cannot be combined with filters or other input and output options");
+    }
+    if args.dry_run {
+        println!("Dry run: would compare '{}' with '{}'", first, second);
+        return;
+    }
+
+    let result = load_input(first)
+        .and_then(|a| Ok((a.into_rgb8(), load_input(second)?.into_rgb8())))
+        .and_then(|(a, b)| Ok((psnr(&a, &b)?, ssim(&a, &b)?)));
+    match result {
+        Ok((psnr, ssim)) => {
+            println!("PSNR: {:.2} dB", psnr);
+            println!("SSIM: {:.4}", ssim);
+        }
+        Err(err) => fail(&format!("Could not compare '{}' with '{}'", first, second), &err),
+    }
+}
+
+/// Handles `--generate`, which writes a synthetic test image instead of filtering.
+fn run_generate(args: &cli::Args, pattern: TestPattern, width: u32, height: u32) {
+    if args.has_operation() || args.batch || args.input.is_some() || args.split_channels || args.merge_channels.is_some() {
+        usage_error("'--generate' writes a test image on its own; filter it in a second run with '--input'");
+    }
+    if args.stdout && args.output.is_some() {
+        usage_error("'--stdout' cannot be combined with '--output'");
+    }
+
+    let name = format!("{}_{}x{}.png", pattern.name(), width, height);
+    let destination = match args.output.as_deref() {
+        Some(output) if is_output_directory(output) => Path::new(output).join(&name).to_string_lossy().into_owned(),
+        Some(output) => output.to_string(),
+        None => Path::new(INPUT_DIR).join(&name).to_string_lossy().into_owned(),
+    };
+    if args.dry_run {
+        let target = if args.stdout { "stdout" } else { &destination };
+        println!("Dry run: would write a {}x{} {} image to '{}'", width, height, pattern.name(), target);
+        return;
+    }
+
+    let image = DynamicImage::ImageRgb8(generate_test_image(width, height, pattern));
+    if args.stdout {
+        if let Err(err) = write_png(&image, &mut io::stdout().lock()) {
+            fail("Could not write the test image to stdout", &err);
+        }
+        return;
+    }
+    if let Err(err) = save_image(image, &destination) {
+        fail("Could not save the test image", &err);
+    }
+    println!("Test image saved as '{}'", destination);
+}
+
+/// Prints a text preview of the result: colored blocks on a terminal, plain ASCII when piped or `NO_COLOR` is set.
+fn print_preview(image: &DynamicImage, width: u32) {
+    let style = if io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none() {
+        PreviewStyle::Color
+    } else {
+        PreviewStyle::Ascii
+    };
+    print!("{}", render_preview(&image.to_rgb8(), width, style));
+}
+
+/// Applies the job and reports on stderr how long the processing alone took (loading and saving excluded).
+fn apply_timed(job: &Job, image: DynamicImage) -> Result<DynamicImage, Error> {
+    let (width, height) = (image.width(), image.height());
+    let megapixels = width as f64 * height as f64 / 1_000_000.0;
+
+    let start = Instant::now();
+    let processed = job.apply(image)?;
+    let seconds = start.elapsed().as_secs_f64();
+
+    eprintln!(
+        "Processed {}x{} ({:.2} MP) in {:.3} s: {:.2} MP/s | kernel {} | {} threads",
+        width, height, megapixels, seconds, megapixels / seconds.max(f64::EPSILON),
+        job.kernel_description(), rayon::current_num_threads(),
+    );
+    Ok(processed)
+}
+
+/// The input/output pairs a batch run will process, and the ones it leaves out.
+struct BatchPlan {
+    tasks: Vec<(String, String)>,
+    /// Inputs whose output already exists, with that output, when `--skip-existing` is on.
+    skipped: Vec<(String, String)>,
+    /// Inputs an earlier run of the same job finished, with their output, when `--resume` is on.
+    finished: Vec<(String, String)>,
+}
+
+/// Where a batch run writes its results.
+#[derive(Clone, Copy)]
+enum Destination<'a> {
+    /// Derived names in this folder, mirroring subfolders of `images/`.
+    Folder(&'a str),
+    /// Over each input (`--in-place`).
+    InPlace,
+}
+
+/// Works out where the result of each batch input goes, e.g. from `find_images` or a wildcard `--input`.
+/// Both the real run and `--dry-run` use this, so the report always matches what would happen.
+/// Pairs in `finished` (from the manifest of an interrupted run) are left out. Fails when there are no images,
+/// naming the `source` they were looked for in (the folder or the wildcard pattern).
+fn plan_batch(job: &Job, images: Vec<String>, source: &str, destination: Destination, format: Option<&str>, skip_existing: bool, finished: &HashSet<(String, String)>) -> Result<BatchPlan, Error> {
+    if images.is_empty() {
+        return Err(Error::InvalidArgument(format!("no images found in '{}'", source)));
+    }
+
+    let suffix = job.suffix();
+    let (finished, remaining): (Vec<_>, Vec<_>) = images
+        .into_iter()
+        .map(|image_path| {
+            let modified = match destination {
+                Destination::InPlace => image_path.clone(),
+                Destination::Folder(output_dir) => output_path_in(&output_dir_for(output_dir, &image_path), &image_path, &suffix, job.output_suffix.as_deref(), format),
+            };
+            (image_path, modified)
+        })
+        .partition(|task| finished.contains(task));
+    let (skipped, tasks) = remaining.into_iter().partition(|(_, modified)| skip_existing && Path::new(modified).exists());
+
+    Ok(BatchPlan { tasks, skipped, finished })
+}
+
+/// The manifest a batch run records its finished files in: a fresh one, or with `resume` the one an
+/// interrupted run of the same job left behind. Exits with code 1 if that file can't be read.
+fn batch_manifest(job: &Job, resume: bool) -> Manifest {
+    let steps = job.describe().join("; ");
+    if !resume {
+        return Manifest::new(MANIFEST_FILE, &steps);
+    }
+    match Manifest::resume(MANIFEST_FILE, &steps) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            warn!("'{}' was written for different settings; nothing to resume, processing every image.", MANIFEST_FILE);
+            Manifest::new(MANIFEST_FILE, &steps)
+        }
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            process::exit(1);
+        }
+    }
+}
+
+/// Prints the steps of `job` and the planned input/output pairs without touching any image.
+fn print_plan(job: &Job, plan: &BatchPlan) {
+    println!("Dry run: no images will be read or written.");
+    println!("Steps:");
+    for (index, step) in job.describe().iter().enumerate() {
+        println!("  {}. {}", index + 1, step);
+    }
+    println!("Would process {} image(s):", plan.tasks.len());
+    for (image_path, modified) in &plan.tasks {
+        println!("  {} -> {}", image_path, modified);
+    }
+    for (image_path, modified) in &plan.skipped {
+        println!("  SKIP  {} ({} already exists)", image_path, modified);
+    }
+    for (image_path, modified) in &plan.finished {
+        println!("  DONE  {} -> {} (finished by an earlier run)", image_path, modified);
+    }
+}
+
+/// Runs the job on every task of the plan, working on at most `MAX_CONCURRENT_DECODES` files at once,
+/// then prints a per-file summary. Each finished file is recorded in `manifest` right away, so `--resume` can
+/// continue after an interruption. Shows overall file progress unless `quiet`. Exits with code 1 if any file failed.
+fn run_batch(job: &Job, plan: BatchPlan, manifest: &Manifest, quiet: bool) {
+    let BatchPlan { tasks: images, skipped, finished } = plan;
+
+    let progress = (!quiet).then(|| ProgressBar::new("Processing images", images.len()));
+
+    // A fixed set of file workers each takes the next task as soon as it is done with one, so a large image
+    // never holds up the others. They are plain threads rather than a small Rayon pool: each image's own
+    // passes then still spread over every thread of the global pool. No more workers than that pool has threads,
+    // so '--threads 1' still handles one file at a time.
+    let next = AtomicUsize::new(0);
+    let workers = MAX_CONCURRENT_DECODES.min(rayon::current_num_threads()).min(images.len());
+    let mut results: Vec<_> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((image_path, modified)) = images.get(index) else { break };
+                        let result = job.run(image_path, modified).map(|_| modified.clone());
+                        if result.is_ok()
+                            && let Err(err) = manifest.record(image_path, modified)
+                        {
+                            warn!("Could not update '{}': {}", MANIFEST_FILE, err);
+                        }
+                        if let Some(progress) = &progress {
+                            progress.inc(1);
+                        }
+                        done.push((index, image_path, result));
+                    }
+                    done
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_else(|payload| panic::resume_unwind(payload))).collect()
+    });
+    // Back in plan order for the summary
+    results.sort_by_key(|&(index, ..)| index);
+    let results: Vec<_> = results.into_iter().map(|(_, image_path, result)| (image_path, result)).collect();
+    if let Some(progress) = &progress {
+        progress.finish();
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    println!("Batch complete: {} succeeded, {} failed, {} skipped", results.len() - failed, failed, skipped.len() + finished.len());
+    for (image_path, modified) in &skipped {
+        println!("  SKIP  {} ({} already exists)", image_path, modified);
+    }
+    for (image_path, modified) in &finished {
+        println!("  DONE  {} -> {} (finished by an earlier run)", image_path, modified);
+    }
+    for (image_path, result) in &results {
+        match result {
+            Ok(modified) => println!("  OK    {} -> {}", image_path, modified),
+            Err(err) => println!("  FAIL  {}: {}", image_path, error_message(err)),
+        }
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+/// Prints `context` with a readable description of the error on stderr and exits with its `exit_code`.
+fn fail(context: &str, err: &Error) -> ! {
+    eprintln!("Error: {}: {}", context, error_message(err));
+    process::exit(exit_code(err));
+}
+
+/// The error's message, with a hint on what to do for the mistakes users make most.
+fn error_message(err: &Error) -> String {
+    match err {
+        Error::Io(io_err) if io_err.kind() == io::ErrorKind::NotFound => format!("{} (check the path)", err),
+        Error::UnsupportedFormat(_) => format!("{} (supported: {})", err, IMAGE_EXTENSIONS.join(", ")),
+        Error::ImageDecode(_) => format!("not a valid image or could not be encoded: {}", err),
+        _ => err.to_string(),
+    }
+}
+
+/// 2 for bad parameters, like the usage errors; 1 for files that can't be read, decoded or written.
+fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::InvalidArgument(_) => 2,
+        Error::Io(_) | Error::ImageDecode(_) | Error::UnsupportedFormat(_) | Error::InvalidKernel(_) => 1,
+    }
+}
+
+/// Prints the error followed by the usage text and exits with a nonzero code.
+fn usage_error(message: &str) -> ! {
+    eprintln!("Error: {}\n", message);
+    eprint!("{}", cli::usage());
+    process::exit(2);
+}
+
+/// Prompts for a filter and its parameters, then processes the first image in `images/`.
+fn interactive() {
     
-    let image_path = match find_image() {
+    // The filter is only chosen afterwards, so there are no outputs to recognize yet
+    let image_path = match find_image(false, &[]) {
         Some(file) => file,
         None => {
-            eprintln!("Error: No '.jpg' found in 'images/'. Exiting.");
+            eprintln!("Error: No image found in 'images/'. Exiting.");
             return;
         },
     };
 
-    println!("Please enter 1 for Blur or 2 for Sharpen.");
-    io::stdout().flush().unwrap(); // Ensure is displayed immediately
-
-    let mut choice = String::new();
-    io::stdin().read_line(&mut choice).expect("Failed to read input.");
-    let choice = choice.trim(); // Remove newline
+    println!("Please choose a filter:");
+    println!("  1. Blur");
+    println!("  2. Sharpen");
+    println!("  3. Edge Detection");
+    println!("  4. Motion Blur");
+    println!("  5. Emboss");
+    println!("  6. Grayscale");
+    println!("  7. Unsharp Mask");
+    println!("  8. Threshold");
+    println!("  9. Invert");
+    println!("  10. Sepia");
+    println!("  11. Histogram Equalization");
+    println!("  12. Laplacian Edges");
+    println!("  13. Cartoon");
+    let choice = prompt("Enter 1-13: ");
 
     let modified: String;
+    let result;
     
     if choice == "1" {
 
-        print!("Enter blur strength (odd value, i.e., 3, 5, 7): ");
-        io::stdout().flush().unwrap();
-
-        let mut blur_strength = String::new();
-        io::stdin().read_line(&mut blur_strength).expect("Failed to read input.");
-        
         // Convert to usize (default to 5 if invalid)
-        let blur_strength: usize = blur_strength.trim().parse().unwrap_or(5);
+        let blur_strength: usize = prompt("Enter blur strength (odd value, i.e., 3, 5, 7): ").parse().unwrap_or(5);
 
-        // Ensure blur strength is odd 
-        let blur_strength = if blur_strength % 2 == 0 { blur_strength + 1 } else { blur_strength };
+        // Ensure blur strength is odd, telling the user when it changes
+        let blur_strength = ensure_odd_size(blur_strength, "Blur size");
 
-        modified = format!("images/{}_blurred_{}.jpg", Path::new(&image_path).file_stem().expect("Failed to get file stem").to_str().expect("Failed to convert to str"), blur_strength);
+        let kind = if prompt("Enter 1 for Box blur or 2 for Gaussian blur: ") == "2" {
+            // Default to 0.0 (automatic sigma) if invalid
+            let sigma = prompt("Enter Gaussian sigma (0 derives it from the strength): ").parse().unwrap_or(0.0);
+            BlurKind::Gaussian { sigma }
+        } else {
+            BlurKind::Box
+        };
+
+        let suffix = match kind {
+            BlurKind::Box => "blurred",
+            BlurKind::Gaussian { .. } => "gaussian",
+        };
+
+        modified = output_path(&image_path, &format!("{}_{}", suffix, blur_strength), None);
 
         println!("Applying blur with strength {}...", blur_strength);
-        blur_image(&image_path, &modified, blur_strength);
+        result = blur_image(&image_path, &modified, blur_strength, kind);
 
     } else if choice == "2" {
-        modified = format!("images/{}_sharpened.jpg", Path::new(&image_path).file_stem().expect("Failed to get file stem").to_str().expect("Failed to convert to str"));
+        modified = output_path(&image_path, "sharpened", None);
 
-        println!("Sharpening the image...");
-        sharpen_image(&image_path, &modified);
+        // 1.0 is the classic 5/-1 kernel; smaller values are subtler
+        let strength: f32 = prompt("Enter sharpen strength (default 1.0, 0 leaves the image unchanged): ").parse().unwrap_or(1.0);
+
+        println!("Sharpening the image with strength {}...", strength);
+        result = sharpen_image(&image_path, &modified, strength);
     
+    } else if choice == "3" {
+        modified = output_path(&image_path, "edges", None);
+
+        // Luminance-only detection is faster but loses color information
+        let luma = prompt("Detect edges on grayscale luminance for speed? (y/N): ").eq_ignore_ascii_case("y");
+
+        println!("Detecting edges...");
+        result = if luma {
+            filter_image(&image_path, &modified, &Filter::Edges { operator: EdgeOperator::Sobel, luma })
+        } else {
+            edge_detect_image(&image_path, &modified)
+        };
+
+    } else if choice == "4" {
+
+        // Default to a 9 pixel horizontal streak if invalid
+        let length: usize = prompt("Enter motion blur length (odd value, i.e., 5, 9, 15): ").parse().unwrap_or(9);
+        let length = ensure_odd_size(length, "Motion blur length");
+        let angle: f32 = prompt("Enter motion angle in degrees (0 is horizontal): ").parse().unwrap_or(0.0);
+
+        modified = output_path(&image_path, &format!("motion_{}", length), None);
+
+        println!("Applying motion blur of length {} at {} degrees...", length, angle);
+        result = motion_blur_image(&image_path, &modified, length, angle);
+
+    } else if choice == "5" {
+        modified = output_path(&image_path, "embossed", None);
+
+        println!("Embossing the image...");
+        result = emboss_image(&image_path, &modified);
+
+    } else if choice == "6" {
+        modified = output_path(&image_path, "grayscale", None);
+
+        println!("Converting to grayscale...");
+        result = filter_image(&image_path, &modified, &Filter::Grayscale);
+
+    } else if choice == "7" {
+
+        // Default to a moderate 1 pixel radius at full strength if invalid
+        let sigma: f32 = prompt("Enter blur sigma (detail size in pixels, i.e., 1.0, 2.0): ").parse().unwrap_or(1.0);
+        let percent: f32 = prompt("Enter sharpening amount in percent (100 is standard, 0 leaves the image unchanged, i.e., 50, 150): ").parse().unwrap_or(100.0);
+
+        modified = output_path(&image_path, "unsharp", None);
+
+        println!("Applying unsharp mask with sigma {} and amount {}%...", sigma, percent);
+        result = filter_image(&image_path, &modified, &Filter::UnsharpMask { sigma, amount: unsharp_amount(percent) });
+
+    } else if choice == "8" {
+
+        // Default to mid-gray if invalid
+        let level: u8 = prompt("Enter threshold level (0-255, i.e., 128): ").parse().unwrap_or(128);
+
+        modified = output_path(&image_path, &format!("threshold_{}", level), None);
+
+        println!("Thresholding at level {}...", level);
+        result = filter_image(&image_path, &modified, &Filter::Threshold { level });
+
+    } else if choice == "9" {
+        modified = output_path(&image_path, "inverted", None);
+
+        println!("Inverting colors...");
+        result = filter_image(&image_path, &modified, &Filter::Invert);
+
+    } else if choice == "10" {
+        modified = output_path(&image_path, "sepia", None);
+
+        println!("Applying sepia tone...");
+        result = filter_image(&image_path, &modified, &Filter::Sepia);
+
+    } else if choice == "11" {
+        modified = output_path(&image_path, "equalized", None);
+
+        // Per-channel equalization stretches harder but can shift colors
+        let per_channel = prompt("Equalize each color channel separately? (y/N): ").eq_ignore_ascii_case("y");
+
+        println!("Equalizing the histogram...");
+        result = filter_image(&image_path, &modified, &Filter::Equalize { per_channel });
+
+    } else if choice == "12" {
+        modified = output_path(&image_path, "laplacian", None);
+
+        println!("Detecting edges with the Laplacian...");
+        result = filter_image(&image_path, &modified, &Filter::Laplacian);
+
+    } else if choice == "13" {
+        // Default to 8 color levels and medium outlines if invalid
+        let levels: u16 = prompt("Enter color levels per channel (i.e., 4, 8): ").parse().unwrap_or(8);
+        let edge_threshold: u8 = prompt("Enter edge threshold (0-255, lower draws more outlines, i.e., 64): ").parse().unwrap_or(64);
+        modified = output_path(&image_path, "cartoon", None);
+
+        println!("Cartoonifying with {} levels and edge threshold {}...", levels, edge_threshold);
+        result = filter_image(&image_path, &modified, &Filter::Cartoon { levels, edge_threshold });
+
     } else {
-        println!("Invalid choice! Please enter 1 for Blur or 2 for Sharpen.");
+        println!("Invalid choice! Please enter a number from the menu.");
+        return;
+    }
+
+    // Report load/save failures without aborting the process
+    if let Err(err) = result {
+        eprintln!("Error: Could not process '{}': {}", image_path, error_message(&err));
         return;
     }
 
@@ -183,4 +1082,283 @@ fn main() {
 
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kernel_rs::Pipeline;
+
+    /// A fresh directory under the system temp dir for one test's files.
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("kernel-rs-cli-{}-{}", process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn path_in(dir: &Path, name: &str) -> String {
+        dir.join(name).to_string_lossy().into_owned()
+    }
+
+    /// A job that only inverts, so its outputs are named `<stem>_inverted.<ext>`.
+    fn invert_job() -> Job {
+        Job {
+            crop: None,
+            flip: None,
+            rotation: None,
+            max_dimension: None,
+            noise: None,
+            seed: 0,
+            operation: Operation::Pipeline(Pipeline::from(Filter::Invert)),
+            luma_only: false,
+            mask: None,
+            resize: None,
+            format: None,
+            output_suffix: None,
+        }
+    }
+
+    #[test]
+    fn batch_skips_inputs_whose_output_exists() {
+        let dir = scratch_dir("skip-existing");
+        let (done, fresh) = (path_in(&dir, "done.png"), path_in(&dir, "fresh.png"));
+        fs::write(dir.join("done_inverted.png"), b"").unwrap();
+
+        let output_dir = dir.to_string_lossy().into_owned();
+        let images = vec![done.clone(), fresh.clone()];
+        let plan = plan_batch(&invert_job(), images, &output_dir, Destination::Folder(&output_dir), None, true, &HashSet::new()).unwrap();
+
+        assert_eq!(plan.skipped, vec![(done, path_in(&dir, "done_inverted.png"))]);
+        assert_eq!(plan.tasks, vec![(fresh, path_in(&dir, "fresh_inverted.png"))]);
+    }
+
+    #[test]
+    fn previous_outputs_are_not_picked_up_as_inputs() {
+        let blur = Job { operation: Operation::Pipeline(Pipeline::from(Filter::Blur { size: 5, kind: BlurKind::Box, linear: false })), ..invert_job() };
+        let outputs = job_output_patterns(&blur);
+        assert!(is_generated_output(Path::new("images/cat_blurred_5.png"), &outputs));
+        assert!(is_generated_output(Path::new("images/cat_blurred_5_blurred_5.jpg"), &outputs));
+        assert!(!is_generated_output(Path::new("images/cat.png"), &outputs));
+        // Only whole suffixes of this job count, so inputs that merely contain a filter's word are still read
+        for input in ["images/blurred_5.png", "images/cat_blurred_7.png", "images/beach_motion.jpg", "images/sunset_gamma.png"] {
+            assert!(!is_generated_output(Path::new(input), &outputs), "{}", input);
+        }
+
+        let channels = output_patterns(&["r", "g", "b"]);
+        assert!(is_generated_output(Path::new("images/cat_g.png"), &channels));
+        assert!(!is_generated_output(Path::new("images/cat_blurred_5.png"), &channels));
+    }
+
+    #[test]
+    fn batch_rerun_with_a_template_does_not_reprocess_its_outputs() {
+        let dir = scratch_dir("rerun-template");
+        image::RgbImage::new(4, 4).save(dir.join("cat.png")).unwrap();
+
+        let pattern = path_in(&dir, "*.png");
+        for _ in 0..2 {
+            run(&["--input", &pattern, "--filter", "blur", "--output-suffix", "{stem}-{filter}{ext}", "--output", &path_in(&dir, ""), "--quiet"]);
+        }
+        let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["cat-blurred.png", "cat.png"]);
+
+        let template = Job { output_suffix: Some("{filter}{param}_{stem}".to_string()), ..invert_job() };
+        let outputs = job_output_patterns(&template);
+        assert!(is_generated_output(Path::new("inverted_cat.jpg"), &outputs));
+        assert!(!is_generated_output(Path::new("cat_inverted.jpg"), &outputs));
+    }
+
+    #[test]
+    fn batch_processes_more_files_than_it_decodes_at_once() {
+        let dir = scratch_dir("many");
+        let count = MAX_CONCURRENT_DECODES * 2 + 1;
+        for index in 0..count {
+            image::RgbImage::from_pixel(4, 4, image::Rgb([index as u8; 3])).save(dir.join(format!("{}.png", index))).unwrap();
+        }
+
+        let output_dir = path_in(&dir, "out/");
+        run(&["--input", &path_in(&dir, "*.png"), "--filter", "invert", "--output", &output_dir, "--quiet"]);
+        for index in 0..count {
+            let output = image::open(dir.join(format!("out/{}_inverted.png", index))).unwrap().into_rgb8();
+            assert_eq!(output.get_pixel(0, 0).0, [255 - index as u8; 3], "image {}", index);
+        }
+    }
+
+    #[test]
+    fn batch_rerun_does_not_reprocess_its_outputs() {
+        let dir = scratch_dir("rerun");
+        image::RgbImage::new(4, 4).save(dir.join("cat.png")).unwrap();
 
+        let pattern = path_in(&dir, "*.png");
+        for _ in 0..2 {
+            run(&["--input", &pattern, "--filter", "invert", "--output", &path_in(&dir, ""), "--quiet"]);
+        }
+        let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["cat.png", "cat_inverted.png"]);
+    }
+
+    /// Runs the command line with `args`, as `main` would after the program name.
+    fn run(args: &[&str]) {
+        run_cli(args.iter().map(|arg| arg.to_string()).collect());
+    }
+
+    #[test]
+    fn dry_run_writes_no_output() {
+        let dir = scratch_dir("dry-run");
+        for name in ["first.png", "second.png"] {
+            image::RgbImage::new(4, 4).save(dir.join(name)).unwrap();
+        }
+
+        let (pattern, output_dir) = (path_in(&dir, "*.png"), path_in(&dir, "out/"));
+        run(&["--input", &pattern, "--output", &output_dir, "--filter", "blur", "--dry-run", "--quiet"]);
+        let single = path_in(&dir, "single.png");
+        run(&["--input", &path_in(&dir, "first.png"), "--output", &single, "--filter", "blur", "--dry-run"]);
+
+        assert!(!Path::new(&output_dir).exists());
+        assert!(!Path::new(&single).exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn explicit_output_file_may_point_anywhere() {
+        let dir = scratch_dir("output-file");
+        let input = path_in(&dir, "input.png");
+        image::RgbImage::new(4, 4).save(&input).unwrap();
+
+        // Missing parent folders are created
+        let output = path_in(&dir, "nested/deeper/result.png");
+        run(&["--input", &input, "--output", &output, "--filter", "blur", "--quiet"]);
+
+        assert_eq!(image::open(&output).unwrap().to_rgb8().dimensions(), (4, 4));
+    }
+
+    #[test]
+    fn explicit_output_directory_keeps_the_derived_name() {
+        let dir = scratch_dir("output-directory");
+        let input = path_in(&dir, "input.png");
+        image::RgbImage::new(4, 4).save(&input).unwrap();
+
+        run(&["--input", &input, "--output", &path_in(&dir, "results/"), "--filter", "blur", "--quiet"]);
+
+        assert!(dir.join("results").join("input_blurred_5.png").is_file());
+    }
+
+    #[test]
+    fn recursive_discovery_finds_nested_images() {
+        let dir = scratch_dir("recursive");
+        fs::create_dir_all(dir.join("trips/2024")).unwrap();
+        for name in ["top.png", "trips/beach.jpg", "trips/2024/snow.png"] {
+            image::RgbImage::new(2, 2).save(dir.join(name)).unwrap();
+        }
+        fs::write(dir.join("trips/notes.txt"), "not an image").unwrap();
+        // A link back up must not send the walk around in circles
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("trips/2024/loop")).unwrap();
+
+        let found = walk_images(&dir, true);
+        let expected: Vec<PathBuf> = ["top.png", "trips/2024/snow.png", "trips/beach.jpg"].iter().map(|name| dir.join(name)).collect();
+        assert_eq!(found, expected);
+        assert_eq!(walk_images(&dir, false), vec![dir.join("top.png")]);
+
+        // Outputs keep the subfolder the input was found in
+        assert_eq!(Path::new(&output_dir_for("out", "images/trips/2024/snow.png")), Path::new("out/trips/2024"));
+        assert_eq!(Path::new(&output_dir_for("out", "images/top.png")), Path::new("out"));
+    }
+
+    #[test]
+    fn errors_map_to_exit_codes_and_hints() {
+        let missing = Error::Io(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(exit_code(&missing), 1);
+        assert!(error_message(&missing).ends_with("(check the path)"));
+
+        let unsupported = Error::UnsupportedFormat(".xyz".to_string());
+        assert_eq!(exit_code(&unsupported), 1);
+        assert!(error_message(&unsupported).contains("supported: jpg"));
+
+        assert_eq!(exit_code(&Error::InvalidArgument("bad crop".to_string())), 2);
+    }
+
+    #[test]
+    fn wildcard_input_queues_every_match() {
+        let dir = scratch_dir("wildcard");
+        for name in ["first.png", "second.png", "notes.txt"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let images = expand_pattern(&path_in(&dir, "*"), &[]).unwrap();
+        assert_eq!(images, vec![path_in(&dir, "first.png"), path_in(&dir, "second.png")]);
+
+        let output_dir = dir.to_string_lossy().into_owned();
+        let plan = plan_batch(&invert_job(), images, &output_dir, Destination::Folder(&output_dir), None, false, &HashSet::new()).unwrap();
+        assert_eq!(plan.tasks.len(), 2);
+    }
+
+    #[test]
+    fn empty_batches_are_argument_errors() {
+        let dir = scratch_dir("no-match");
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        let err = expand_pattern(&path_in(&dir, "*"), &[]).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgument(_)), "{}", err);
+        assert_eq!(exit_code(&err), 2);
+        let output_dir = dir.to_string_lossy().into_owned();
+        let err = plan_batch(&invert_job(), Vec::new(), "photos/*.jpg", Destination::Folder(&output_dir), None, false, &HashSet::new()).err().unwrap();
+        assert!(matches!(err, Error::InvalidArgument(_)), "{}", err);
+        assert_eq!(err.to_string(), "no images found in 'photos/*.jpg'");
+    }
+
+    #[test]
+    fn in_place_replaces_the_input_with_a_valid_image() {
+        let dir = scratch_dir("in-place");
+        let input = path_in(&dir, "photo.png");
+        image::RgbImage::from_pixel(4, 4, image::Rgb([10, 20, 30])).save(&input).unwrap();
+        let before = fs::read(&input).unwrap();
+
+        run(&["--input", &input, "--in-place", "--filter", "invert", "--quiet"]);
+
+        assert_ne!(fs::read(&input).unwrap(), before);
+        let replaced = image::open(&input).unwrap().into_rgb8();
+        assert!(replaced.pixels().all(|pixel| pixel.0 == [245, 235, 225]));
+        // Nothing is left next to it: no derived output and no temporary file
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn resumed_batch_only_plans_the_unfinished_inputs() {
+        let dir = scratch_dir("resume");
+        let output_dir = dir.to_string_lossy().into_owned();
+        let (done, rest) = (path_in(&dir, "done.png"), path_in(&dir, "rest.png"));
+        let finished = HashSet::from([(done.clone(), path_in(&dir, "done_inverted.png"))]);
+
+        let plan = plan_batch(&invert_job(), vec![done.clone(), rest.clone()], &output_dir, Destination::Folder(&output_dir), None, false, &finished).unwrap();
+        assert_eq!(plan.finished, vec![(done, path_in(&dir, "done_inverted.png"))]);
+        assert_eq!(plan.tasks, vec![(rest, path_in(&dir, "rest_inverted.png"))]);
+    }
+
+    #[test]
+    fn same_seed_writes_identical_bytes() {
+        let dir = scratch_dir("seed");
+        let input = path_in(&dir, "gray.png");
+        image::RgbImage::from_pixel(32, 24, image::Rgb([128; 3])).save(&input).unwrap();
+
+        let noisy = |seed: &str, name: &str| {
+            let output = path_in(&dir, name);
+            run(&["--input", &input, "--output", &output, "--add-noise", "gaussian:20", "--seed", seed, "--filter", "blur", "--quiet"]);
+            fs::read(output).unwrap()
+        };
+        let first = noisy("7", "first.png");
+        assert_eq!(noisy("7", "second.png"), first);
+        assert_ne!(noisy("8", "other.png"), first);
+    }
+
+    #[test]
+    fn output_suffix_template_expands_for_a_blur_of_5() {
+        let suffix = Filter::Blur { size: 5, kind: BlurKind::Box, linear: false }.suffix();
+        assert_eq!(expand_output_template("{stem}-{filter}{ext}", "cat", &suffix, "png"), "cat-blurred.png");
+        assert_eq!(expand_output_template("{filter}{param}_{stem}", "cat", &suffix, "png"), "blurred5_cat.png");
+
+        let template = Some("{stem}-{filter}-{param}{ext}");
+        assert_eq!(output_path_in("out", "images/cat.jpg", &suffix, template, None), path_in(Path::new("out"), "cat-blurred-5.jpg"));
+        // Without a template, outputs keep the default names
+        assert_eq!(output_path_in("out", "images/cat.jpg", &suffix, None, None), path_in(Path::new("out"), "cat_blurred_5.jpg"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::json::{self, Json};
+
+/// Where a batch run records finished files, looked up in the current directory.
+pub const MANIFEST_FILE: &str = "kernel-rs-batch.json";
+
+/// The inputs a batch run has finished, kept in `kernel-rs-batch.json` so `--resume` can pick up where an
+/// interrupted run stopped, e.g.
+/// `{ "job": "Gaussian blur, size 5", "completed": [{ "input": "images/a.png", "output": "images/a_blurred_5.png" }] }`.
+/// The file is rewritten after every finished image, so it survives a crash or Ctrl-C at any point.
+pub struct Manifest {
+    path: String,
+    /// The job's steps, so a resume with different settings doesn't skip files that were made with the old ones.
+    job: String,
+    completed: Mutex<Vec<(String, String)>>,
+}
+
+impl Manifest {
+    /// An empty manifest for `job` at `path`; nothing is written until the first file finishes.
+    pub fn new(path: &str, job: &str) -> Manifest {
+        Manifest { path: path.to_string(), job: job.to_string(), completed: Mutex::new(Vec::new()) }
+    }
+
+    /// Reads the manifest an earlier run of the same `job` left at `path`. A missing file is an empty manifest;
+    /// one written for a different job is ignored with `Ok(None)` so the caller can say why nothing is skipped.
+    pub fn resume(path: &str, job: &str) -> Result<Option<Manifest>, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Some(Manifest::new(path, job))),
+            Err(err) => return Err(format!("Could not read '{}': {}", path, err)),
+        };
+        let invalid = |message: &str| format!("Invalid '{}': {}", path, message);
+
+        let Json::Object(root) = json::parse(&text).map_err(|err| invalid(&err))? else {
+            return Err(invalid("expected a JSON object at the top level"));
+        };
+        let mut recorded_job = None;
+        let mut completed = Vec::new();
+        for (key, value) in root {
+            match (key.as_str(), value) {
+                ("job", Json::String(value)) => recorded_job = Some(value),
+                ("completed", Json::Array(entries)) => {
+                    for entry in entries {
+                        completed.push(completed_entry(entry).ok_or_else(|| invalid("each completed entry needs an \"input\" and an \"output\" string"))?);
+                    }
+                }
+                ("job" | "completed", _) => return Err(invalid(&format!("unexpected value for \"{}\"", key))),
+                _ => {}
+            }
+        }
+
+        if recorded_job.as_deref() != Some(job) {
+            return Ok(None);
+        }
+        Ok(Some(Manifest { path: path.to_string(), job: job.to_string(), completed: Mutex::new(completed) }))
+    }
+
+    /// The input/output pairs recorded as finished whose output still exists.
+    pub fn finished(&self) -> HashSet<(String, String)> {
+        let completed = self.completed.lock().expect("Manifest lock poisoned");
+        completed.iter().filter(|(_, output)| Path::new(output).exists()).cloned().collect()
+    }
+
+    /// Records that `input` was written to `output` and saves the manifest. Safe to call from batch workers:
+    /// the lock is held while the file is written, so two updates never interleave.
+    pub fn record(&self, input: &str, output: &str) -> io::Result<()> {
+        let mut completed = self.completed.lock().expect("Manifest lock poisoned");
+        completed.push((input.to_string(), output.to_string()));
+        self.save(&completed)
+    }
+
+    /// Writes the whole manifest to a temporary file and renames it over the old one,
+    /// so a crash mid-write leaves the previous version rather than half a file.
+    fn save(&self, completed: &[(String, String)]) -> io::Result<()> {
+        let entries: Vec<String> = completed
+            .iter()
+            .map(|(input, output)| format!("    {{ \"input\": {}, \"output\": {} }}", json::quote(input), json::quote(output)))
+            .collect();
+        let text = format!("{{\n  \"job\": {},\n  \"completed\": [\n{}\n  ]\n}}\n", json::quote(&self.job), entries.join(",\n"));
+
+        let temporary = format!("{}.tmp", self.path);
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, &self.path)
+    }
+}
+
+fn completed_entry(entry: Json) -> Option<(String, String)> {
+    let Json::Object(fields) = entry else {
+        return None;
+    };
+    let field = |name: &str| {
+        fields.iter().find_map(|(key, value)| match value {
+            Json::String(value) if key == name => Some(value.clone()),
+            _ => None,
+        })
+    };
+    Some((field("input")?, field("output")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn resume_picks_up_the_files_a_partial_run_finished() {
+        let dir = env::temp_dir().join(format!("kernel-rs-{}-manifest", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path_in = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let manifest_path = path_in("batch.json");
+
+        // The first run finishes one of two images before it is interrupted
+        let first_run = Manifest::new(&manifest_path, "Invert colors");
+        fs::write(path_in("a_inverted.png"), b"").unwrap();
+        first_run.record(&path_in("a.png"), &path_in("a_inverted.png")).unwrap();
+        drop(first_run);
+
+        let resumed = Manifest::resume(&manifest_path, "Invert colors").unwrap().expect("same job");
+        assert_eq!(resumed.finished(), HashSet::from([(path_in("a.png"), path_in("a_inverted.png"))]));
+
+        // Each file is recorded as it finishes, keeping the earlier entries
+        fs::write(path_in("b_inverted.png"), b"").unwrap();
+        resumed.record(&path_in("b.png"), &path_in("b_inverted.png")).unwrap();
+        let resumed = Manifest::resume(&manifest_path, "Invert colors").unwrap().unwrap();
+        assert_eq!(resumed.finished().len(), 2);
+
+        // Deleted outputs are redone, and other settings start over
+        fs::remove_file(path_in("a_inverted.png")).unwrap();
+        assert_eq!(resumed.finished(), HashSet::from([(path_in("b.png"), path_in("b_inverted.png"))]));
+        assert!(Manifest::resume(&manifest_path, "Box blur, size 5").unwrap().is_none());
+    }
+
+    #[test]
+    fn missing_manifest_resumes_nothing_and_a_broken_one_is_an_error() {
+        let path = env::temp_dir().join(format!("kernel-rs-{}-manifest-broken.json", process::id()));
+        let path = path.to_string_lossy();
+        let _ = fs::remove_file(&*path);
+        assert!(Manifest::resume(&path, "Invert colors").unwrap().unwrap().finished().is_empty());
+
+        fs::write(&*path, r#"{ "job": "Invert colors", "completed": [{ "input": "a.png" }] }"#).unwrap();
+        assert!(Manifest::resume(&path, "Invert colors").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use image::{ImageBuffer, Pixel};
+use rayon::prelude::*;
+
+/// Seed used when the caller has no preference; any fixed value keeps runs reproducible.
+/// Randomized operations take the seed as an argument and never use thread-local or time-based state,
+/// so the same seed, input and settings give byte-identical output whatever the thread count.
+pub const DEFAULT_SEED: u64 = 0x5EED;
+
+/// Synthetic noise to add before filtering, e.g. to try out the denoising filters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Noise {
+    /// Zero-mean Gaussian noise with standard deviation `sigma` (on the 0–255 scale) on every channel.
+    Gaussian { sigma: f32 },
+    /// Each pixel turns black or white with the given `probability`.
+    SaltPepper { probability: f32 },
+}
+
+impl Noise {
+    /// Adds the noise to the color channels, leaving alpha (if any) untouched. The same seed always gives the same output.
+    pub fn apply<P>(&self, image: &ImageBuffer<P, Vec<u8>>, seed: u64) -> ImageBuffer<P, Vec<u8>>
+    where
+        P: Pixel<Subpixel = u8> + Send + Sync,
+    {
+        match *self {
+            Noise::Gaussian { sigma } => add_gaussian_noise(image, sigma, seed),
+            Noise::SaltPepper { probability } => add_salt_pepper_noise(image, probability, seed),
+        }
+    }
+}
+
+/// Adds zero-mean Gaussian noise with standard deviation `sigma` to each color channel and clamps to 0–255.
+pub fn add_gaussian_noise<P>(image: &ImageBuffer<P, Vec<u8>>, sigma: f32, seed: u64) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + Send + Sync,
+{
+    add_noise(image, seed, |rng, pixel| {
+        for channel in color_channels(pixel) {
+            *channel = (*channel as f32 + rng.next_gaussian() * sigma).round().clamp(0.0, 255.0) as u8;
+        }
+    })
+}
+
+/// Sets each pixel to black or white (50/50) with the given `probability`, like dead or stuck sensor pixels.
+pub fn add_salt_pepper_noise<P>(image: &ImageBuffer<P, Vec<u8>>, probability: f32, seed: u64) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + Send + Sync,
+{
+    add_noise(image, seed, |rng, pixel| {
+        if rng.next_f32() < probability {
+            let value = if rng.next_f32() < 0.5 { 0 } else { 255 };
+            color_channels(pixel).for_each(|channel| *channel = value);
+        }
+    })
+}
+
+/// Runs `noise` over every pixel in parallel. Each row gets its own generator, the stream of `seed` for the
+/// row index, so the result doesn't depend on how Rayon schedules the rows.
+fn add_noise<P, F>(image: &ImageBuffer<P, Vec<u8>>, seed: u64, noise: F) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + Send + Sync,
+    F: Fn(&mut SplitMix64, &mut P) + Sync,
+{
+    let mut output = image.clone();
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(y, row)| {
+            let mut rng = SplitMix64::for_stream(seed, y as u64);
+            for (_x, _y, pixel) in row {
+                noise(&mut rng, pixel);
+            }
+        });
+
+    output
+}
+
+/// The channels noise should touch: all but a trailing alpha channel.
+fn color_channels<P: Pixel<Subpixel = u8>>(pixel: &mut P) -> impl Iterator<Item = &mut u8> {
+    let count = match P::CHANNEL_COUNT {
+        2 | 4 => P::CHANNEL_COUNT - 1,
+        count => count,
+    };
+    pixel.channels_mut().iter_mut().take(count as usize)
+}
+
+/// Small, fast pseudo-random generator (SplitMix64). Not cryptographic, but well distributed and reproducible.
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    /// An independent generator for part `index` of a job seeded with `seed` (a row, tile or frame), so parallel
+    /// work draws the same numbers no matter which thread gets to it first.
+    pub(crate) fn for_stream(seed: u64, index: u64) -> Self {
+        SplitMix64::new(seed ^ (index + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fill an f32 mantissa exactly
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Standard normal sample via the Box-Muller transform.
+    pub(crate) fn next_gaussian(&mut self) -> f32 {
+        // 1 - u keeps the logarithm's argument in (0, 1]
+        let u1 = 1.0 - self.next_f32();
+        let u2 = self.next_f32();
+        (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    #[test]
+    fn a_fixed_seed_gives_identical_noise() {
+        let gray = RgbImage::from_pixel(40, 30, Rgb([128; 3]));
+        for noise in [Noise::Gaussian { sigma: 20.0 }, Noise::SaltPepper { probability: 0.1 }] {
+            let first = noise.apply(&gray, 42);
+            assert_eq!(noise.apply(&gray, 42), first, "{:?}", noise);
+            assert_ne!(noise.apply(&gray, 43), first, "{:?}", noise);
+            assert_ne!(first, gray, "{:?}", noise);
+        }
+    }
+
+    #[test]
+    fn noise_leaves_alpha_alone() {
+        let translucent = RgbaImage::from_pixel(16, 16, Rgba([128, 128, 128, 77]));
+        let noisy = add_gaussian_noise(&translucent, 30.0, DEFAULT_SEED);
+        assert!(noisy.pixels().all(|pixel| pixel[3] == 77));
+    }
+}
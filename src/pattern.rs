@@ -0,0 +1,86 @@
+use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+
+/// Checkerboard square size used when the caller doesn't pick one.
+pub const DEFAULT_CHECKER_CELL: u32 = 32;
+
+/// Synthetic images for trying out filters without shipping sample photos.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestPattern {
+    /// Every pixel has this color.
+    SolidColor([u8; 3]),
+    /// Alternating white and black `cell x cell` squares, white in the top-left corner.
+    Checkerboard { cell: u32 },
+    /// Red ramps from 0 on the left to 255 on the right, green from 0 at the top to 255 at the bottom; blue is 128.
+    Gradient,
+    /// Black except for one white pixel at the center (`width / 2`, `height / 2`), so a filter's output is its kernel.
+    Impulse,
+}
+
+impl TestPattern {
+    /// Short lowercase name, e.g. for default file names.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TestPattern::SolidColor(_) => "solid",
+            TestPattern::Checkerboard { .. } => "checkerboard",
+            TestPattern::Gradient => "gradient",
+            TestPattern::Impulse => "impulse",
+        }
+    }
+}
+
+/// Draws `pattern` into a new `width x height` image.
+pub fn generate_test_image(width: u32, height: u32, pattern: TestPattern) -> RgbImage {
+    // Ramps end exactly at 255 on the last column and row
+    let ramp = |position: u32, length: u32| (position as f32 * 255.0 / (length.max(2) - 1) as f32).round() as u8;
+    let cell = match pattern {
+        TestPattern::Checkerboard { cell } => cell.max(1),
+        _ => 1,
+    };
+
+    let mut output = RgbImage::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                *pixel = match pattern {
+                    TestPattern::SolidColor(color) => Rgb(color),
+                    TestPattern::Checkerboard { .. } if (x / cell + y / cell) % 2 == 0 => Rgb([255; 3]),
+                    TestPattern::Checkerboard { .. } => Rgb([0; 3]),
+                    TestPattern::Gradient => Rgb([ramp(x, width), ramp(y, height), 128]),
+                    TestPattern::Impulse if (x, y) == (width / 2, height / 2) => Rgb([255; 3]),
+                    TestPattern::Impulse => Rgb([0; 3]),
+                };
+            }
+        });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_pattern_has_the_expected_pixels() {
+        let solid = generate_test_image(5, 3, TestPattern::SolidColor([10, 20, 30]));
+        assert!(solid.pixels().all(|pixel| pixel.0 == [10, 20, 30]));
+
+        let checkerboard = generate_test_image(8, 8, TestPattern::Checkerboard { cell: 2 });
+        assert_eq!(checkerboard.get_pixel(0, 0).0, [255; 3]);
+        assert_eq!(checkerboard.get_pixel(1, 1).0, [255; 3]);
+        assert_eq!(checkerboard.get_pixel(2, 0).0, [0; 3]);
+        assert_eq!(checkerboard.get_pixel(0, 2).0, [0; 3]);
+        assert_eq!(checkerboard.get_pixel(3, 3).0, [255; 3]);
+
+        let gradient = generate_test_image(6, 4, TestPattern::Gradient);
+        assert_eq!(gradient.get_pixel(0, 0).0, [0, 0, 128]);
+        assert_eq!(gradient.get_pixel(5, 3).0, [255, 255, 128]);
+        assert_eq!(gradient.get_pixel(1, 0).0, [51, 0, 128]);
+
+        let impulse = generate_test_image(7, 5, TestPattern::Impulse);
+        assert_eq!(impulse.get_pixel(3, 2).0, [255; 3]);
+        assert_eq!(impulse.pixels().filter(|pixel| pixel.0 != [0; 3]).count(), 1);
+    }
+}
@@ -0,0 +1,286 @@
+use std::str::FromStr;
+use std::time::Instant;
+
+use image::{DynamicImage, GrayImage, ImageBuffer, Pixel, RgbImage, RgbaImage};
+
+use crate::color::{from_lab_f32, to_lab_f32, ColorSpace};
+use crate::convolution::{apply_to_luma, from_u16, gray_to_f32, gray_to_u8, to_f32, to_u16, to_u8, BorderMode, Channel, ChannelMask, RgbImage16};
+use crate::filter::{Filter, ParseFilterError};
+use crate::transform::{crop, pad};
+use crate::{is_16_bit, map_16_bit};
+
+/// An ordered chain of filters applied one after another, e.g. "blur then sharpen", without saving intermediates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pipeline {
+    pub stages: Vec<Filter>,
+    /// Color channels the pipeline may change; the rest keep their input values.
+    pub channels: ChannelMask,
+    /// How every stage reads pixels past the image edges. Stages clamp by default.
+    pub border: BorderMode,
+    /// Color space the stages run in; sRGB by default.
+    pub color_space: ColorSpace,
+    /// How many times the whole chain of stages runs, each pass on the previous one's output; 1 by default.
+    pub iterations: usize,
+}
+
+impl Pipeline {
+    pub fn new(stages: Vec<Filter>) -> Self {
+        Pipeline { stages, channels: ChannelMask::ALL, border: BorderMode::Clamp, color_space: ColorSpace::Rgb, iterations: 1 }
+    }
+
+    /// Restricts the pipeline to the given color channels.
+    pub fn with_channels(mut self, channels: ChannelMask) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Makes every stage read pixels past the edges according to `border`, e.g. a `ConstantRgb` background color.
+    pub fn with_border(mut self, border: BorderMode) -> Self {
+        self.border = border;
+        self
+    }
+
+    /// Runs every stage in `color_space`, e.g. `ColorSpace::Lab` to blur perceptually.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Runs the stages `iterations` times over, e.g. to smooth more with a repeated small blur.
+    /// The passes chain like stages do, so intermediates stay in f32. 0 is treated as 1.
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Every stage in the order it runs, repeated for each iteration.
+    fn passes(&self) -> impl Iterator<Item = &Filter> {
+        (0..self.iterations.max(1)).flat_map(|_| self.stages.iter())
+    }
+
+    /// Threads the image through every stage in order.
+    /// Intermediates stay in f32 so chained stages don't compound rounding and clipping; only the result is clamped.
+    pub fn apply(&self, image: &RgbImage) -> RgbImage {
+        let working = match self.color_space {
+            ColorSpace::Rgb => to_f32(image),
+            ColorSpace::Lab => to_lab_f32(image),
+        };
+        let result = self.passes().fold(working, |image, stage| self.run_stage(stage, &image, |image| stage.apply_f32(image)));
+        let result = match self.color_space {
+            ColorSpace::Rgb => to_u8(&result),
+            ColorSpace::Lab => from_lab_f32(&result),
+        };
+        self.channels.merge(image, &result)
+    }
+
+    /// Same as `apply`, keeping transparency the way `Filter::apply_rgba` does.
+    pub fn apply_rgba(&self, image: &RgbaImage) -> RgbaImage {
+        let result = self.passes().fold(image.clone(), |image, stage| self.run_stage(stage, &image, |image| stage.apply_rgba(image)));
+        self.channels.merge(image, &result)
+    }
+
+    /// Same as `apply` on a 16-bit image. Convolution-based stages keep the full precision;
+    /// the others (e.g. median, threshold) work on an 8-bit round trip of their input.
+    pub fn apply_u16(&self, image: &RgbImage16) -> RgbImage16 {
+        let result = self.passes().fold(from_u16(image), |image, stage| self.run_stage(stage, &image, |image| stage.apply_f32(image)));
+        self.channels.merge(image, &to_u16(&result))
+    }
+
+    /// Same as `apply` on a single-channel image, or `None` if a stage has no grayscale path
+    /// (see `Filter::apply_gray`) or the pipeline is restricted to some channels.
+    pub fn apply_gray(&self, image: &GrayImage) -> Option<GrayImage> {
+        // Checked up front so a late stage without a gray path doesn't waste the earlier ones
+        if self.channels != ChannelMask::ALL || !self.stages.iter().all(Filter::has_gray_path) {
+            return None;
+        }
+        let result = self.passes().fold(gray_to_f32(image), |image, stage| {
+            self.run_stage(stage, &image, |image| stage.apply_gray_f32(image).expect("Every stage has a gray path"))
+        });
+        Some(gray_to_u8(&result))
+    }
+
+    /// Same as `apply`, but only the luminance changes (for `--luma-only`): every stage runs on Y in f32 and the
+    /// result is clamped once (see `apply_to_luma`). `None` if a stage has no grayscale path, the pipeline is
+    /// restricted to some channels or runs in Lab; keep the luminance of the full result with `merge_luma` instead.
+    pub fn apply_luma(&self, image: &RgbImage) -> Option<RgbImage> {
+        if self.channels != ChannelMask::ALL || self.color_space != ColorSpace::Rgb || !self.stages.iter().all(Filter::has_gray_path) {
+            return None;
+        }
+        Some(apply_to_luma(image, |luma| {
+            self.passes().fold(luma, |image, stage| {
+                self.run_stage(stage, &image, |image| stage.apply_gray_f32(image).expect("Every stage has a gray path"))
+            })
+        }))
+    }
+
+    /// Runs the pipeline on a decoded image, taking the RGBA path when it has transparency.
+    /// 16-bit images stay 16-bit (alpha, if any, is kept as is), and 8-bit grayscale stays
+    /// grayscale when every stage can run on one channel.
+    /// In Lab every image is filtered as 8-bit color, and transparency is kept as it is.
+    pub fn apply_dynamic(&self, image: DynamicImage) -> DynamicImage {
+        if self.color_space == ColorSpace::Lab {
+            if !image.color().has_alpha() {
+                return DynamicImage::ImageRgb8(self.apply(&image.into_rgb8()));
+            }
+            let original = image.into_rgba8();
+            let color = DynamicImage::ImageRgba8(original.clone()).into_rgb8();
+            let mut result = DynamicImage::ImageRgb8(self.apply(&color)).into_rgba8();
+            result.pixels_mut().zip(original.pixels()).for_each(|(pixel, original)| pixel[3] = original[3]);
+            return DynamicImage::ImageRgba8(result);
+        }
+
+        if let DynamicImage::ImageLuma8(gray) = &image
+            && let Some(result) = self.apply_gray(gray)
+        {
+            DynamicImage::ImageLuma8(result)
+        } else if is_16_bit(&image) {
+            map_16_bit(image, |rgb| self.apply_u16(rgb))
+        } else if image.color().has_alpha() {
+            DynamicImage::ImageRgba8(self.apply_rgba(&image.into_rgba8()))
+        } else {
+            DynamicImage::ImageRgb8(self.apply(&image.into_rgb8()))
+        }
+    }
+
+    /// Runs one stage, logging how long it took at debug level. With a border other than `Clamp` the image is
+    /// first padded by the stage's `border_reach` (the filters themselves always clamp), then cropped back,
+    /// so every neighborhood that crosses the edge reads the border pixels instead.
+    fn run_stage<P>(&self, stage: &Filter, image: &ImageBuffer<P, Vec<P::Subpixel>>, run: impl FnOnce(&ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P: Pixel + 'static,
+        P::Subpixel: Channel,
+    {
+        let reach = stage.border_reach(image.dimensions());
+        let start = Instant::now();
+        let result = if self.border == BorderMode::Clamp || reach == 0 {
+            run(image)
+        } else {
+            let (width, height) = image.dimensions();
+            let filtered = run(&pad(image, reach, self.border));
+            crop(&filtered, reach, reach, width, height).expect("Filters keep the padded size")
+        };
+        crate::debug!("Stage {} took {:.1} ms", stage.suffix(), start.elapsed().as_secs_f64() * 1000.0);
+        result
+    }
+
+    /// The stage suffixes joined with `_`, e.g. `blurred_5_sharpened`, then `_x3` for three iterations
+    /// and `_lab` when run in Lab.
+    pub fn suffix(&self) -> String {
+        let suffix = self.stages.iter().map(Filter::suffix).collect::<Vec<_>>().join("_");
+        let suffix = if self.iterations > 1 { format!("{}_x{}", suffix, self.iterations) } else { suffix };
+        match self.color_space {
+            ColorSpace::Rgb => suffix,
+            ColorSpace::Lab => format!("{}_lab", suffix),
+        }
+    }
+}
+
+impl From<Filter> for Pipeline {
+    fn from(filter: Filter) -> Self {
+        Pipeline::new(vec![filter])
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = ParseFilterError;
+
+    /// Parses comma-separated filter specs, e.g. `blur:5,sharpen,grayscale`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let stages = spec
+            .split(',')
+            .filter(|stage| !stage.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Filter>, _>>()?;
+
+        if stages.is_empty() {
+            return Err(ParseFilterError::new("Pipeline must contain at least one filter"));
+        }
+
+        Ok(Pipeline::new(stages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use crate::kernel::BlurKind;
+    use crate::pattern::{generate_test_image, TestPattern};
+
+    /// Largest per-channel difference between two images of the same size.
+    fn max_difference(a: &RgbImage, b: &RgbImage) -> u8 {
+        a.as_raw().iter().zip(b.as_raw()).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn two_stage_pipeline_runs_end_to_end() {
+        let pipeline: Pipeline = "blur:3,invert".parse().unwrap();
+        assert_eq!(pipeline.stages, vec![Filter::Blur { size: 3, kind: BlurKind::Box, linear: false }, Filter::Invert]);
+        assert_eq!(pipeline.suffix(), "blurred_3_inverted");
+
+        let image = generate_test_image(20, 14, TestPattern::Checkerboard { cell: 2 });
+        let chained = pipeline.stages[1].apply(&pipeline.stages[0].apply(&image));
+        // The pipeline rounds once at the end instead of after the blur, so it may differ by one
+        assert!(max_difference(&pipeline.apply(&image), &chained) <= 1);
+    }
+
+    #[test]
+    fn empty_pipeline_is_rejected() {
+        assert!(" , ".parse::<Pipeline>().is_err());
+        assert!("blur:3,nonsense".parse::<Pipeline>().is_err());
+    }
+
+    #[test]
+    fn f32_intermediates_are_more_accurate_than_a_u8_round_trip() {
+        let image = generate_test_image(24, 16, TestPattern::Checkerboard { cell: 3 });
+        let pipeline: Pipeline = "gaussian:5:1.3,sharpen:1.5".parse().unwrap();
+
+        // Exact result: both stages in f32, clamped but not rounded
+        let exact = pipeline.stages.iter().fold(to_f32(&image), |image, stage| stage.apply_f32(&image));
+        let error = |result: &RgbImage| -> f32 {
+            result.as_raw().iter().zip(exact.as_raw()).map(|(&value, &exact)| (value as f32 - exact.clamp(0.0, 255.0)).abs()).sum()
+        };
+
+        let in_f32 = pipeline.apply(&image);
+        let round_tripped = pipeline.stages[1].apply(&pipeline.stages[0].apply(&image));
+        assert_ne!(in_f32, round_tripped);
+        assert!(error(&in_f32) < error(&round_tripped), "{} vs {}", error(&in_f32), error(&round_tripped));
+    }
+
+    #[test]
+    fn luma_only_sharpening_rings_less_at_color_edges() {
+        // Red against teal of about the same luminance: only the color changes across the edge
+        let (red, teal) = ([200, 60, 60], [40, 130, 110]);
+        let image = RgbImage::from_fn(16, 8, |x, _| Rgb(if x < 8 { red } else { teal }));
+        let pipeline = Pipeline::from(Filter::Sharpen { strength: 1.0, conservative: false });
+
+        // How far channels overshoot the range between the two colors
+        let ringing = |result: &RgbImage| -> u32 {
+            result.pixels().flat_map(|pixel| (0..3).map(move |c| {
+                let (low, high) = (red[c].min(teal[c]), red[c].max(teal[c]));
+                (low.saturating_sub(pixel[c]) + pixel[c].saturating_sub(high)) as u32
+            })).sum()
+        };
+
+        let luma_only = pipeline.apply_luma(&image).expect("Sharpen has a gray path");
+        let full = pipeline.apply(&image);
+        assert!(ringing(&luma_only) * 10 < ringing(&full), "{} vs {}", ringing(&luma_only), ringing(&full));
+    }
+
+    #[test]
+    fn two_box_blur_iterations_match_the_combined_kernel() {
+        let image = generate_test_image(24, 16, TestPattern::Checkerboard { cell: 3 });
+        let twice = Pipeline::from(Filter::Blur { size: 3, kind: BlurKind::Box, linear: false }).with_iterations(2).apply(&image);
+
+        // A 3x3 box convolved with itself is the 5x5 tent with weights (1 2 3 2 1)ᵀ(1 2 3 2 1) / 81
+        let tent = [1.0, 2.0, 3.0, 2.0, 1.0];
+        let combined: Vec<Vec<f32>> = tent.iter().map(|row| tent.iter().map(|column| row * column / 81.0).collect()).collect();
+        let once = crate::convolution::apply_convolution(&image, &combined, BorderMode::Clamp, 0.0);
+
+        // Clamping the intermediate differs from clamping the input, so the two pixel border is left out
+        for (x, y, pixel) in twice.enumerate_pixels().filter(|(x, y, _)| (2..22).contains(x) && (2..14).contains(y)) {
+            let expected = once.get_pixel(x, y);
+            assert!((0..3).all(|c| pixel[c].abs_diff(expected[c]) <= 1), "pixel ({}, {})", x, y);
+        }
+    }
+}
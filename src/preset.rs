@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::fs;
+
+use kernel_rs::{Filter, Pipeline};
+
+use crate::json::{self, Json};
+
+/// Config file `--preset` reads, looked up in the current directory.
+pub const CONFIG_FILE: &str = "kernel-rs.json";
+
+/// Named pipelines from `kernel-rs.json`, e.g.
+/// `{ "presets": { "portrait": ["bilateral:3:20", "sharpen:0.3"] } }`.
+/// Each stage is a spec string in the same format as `--pipeline` stages.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub presets: HashMap<String, Pipeline>,
+}
+
+impl Config {
+    /// Reads and parses the config file at `path`.
+    pub fn load(path: &str) -> Result<Config, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("Could not read '{}': {}", path, err))?;
+        Config::parse(&text).map_err(|err| format!("Invalid '{}': {}", path, err))
+    }
+
+    /// Parses the JSON text of a config file; unknown top-level keys are ignored.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let Json::Object(root) = json::parse(text)? else {
+            return Err("expected a JSON object at the top level".to_string());
+        };
+
+        let mut config = Config::default();
+        let Some((_, presets)) = root.into_iter().find(|(key, _)| key == "presets") else {
+            return Ok(config);
+        };
+        let Json::Object(presets) = presets else {
+            return Err("\"presets\" must be an object mapping names to lists of filters".to_string());
+        };
+
+        for (name, stages) in presets {
+            let Json::Array(stages) = stages else {
+                return Err(format!("preset \"{}\" must be a list of filter specs, e.g. [\"blur:5\", \"sharpen\"]", name));
+            };
+            let stages = stages
+                .into_iter()
+                .map(|stage| match stage {
+                    Json::String(spec) => spec.parse::<Filter>().map_err(|err| format!("preset \"{}\": {}", name, err)),
+                    _ => Err(format!("preset \"{}\": each filter must be a spec string such as \"sharpen:0.5\"", name)),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if stages.is_empty() {
+                return Err(format!("preset \"{}\" has no filters", name));
+            }
+            config.presets.insert(name, Pipeline::new(stages));
+        }
+
+        Ok(config)
+    }
+
+    /// The pipeline saved under `name`, or an error listing the presets that do exist.
+    pub fn preset(&self, name: &str) -> Result<&Pipeline, String> {
+        self.presets.get(name).ok_or_else(|| {
+            let mut names: Vec<&str> = self.presets.keys().map(String::as_str).collect();
+            names.sort();
+            if names.is_empty() {
+                format!("No preset named '{}': '{}' defines no presets", name, CONFIG_FILE)
+            } else {
+                format!("No preset named '{}' in '{}' (available: {})", name, CONFIG_FILE, names.join(", "))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Writes a config with `presets` (name and stage specs) as the JSON `kernel-rs.json` holds and returns its path.
+    fn write_config(test: &str, presets: &[(&str, &[&str])]) -> String {
+        let entries: Vec<String> = presets
+            .iter()
+            .map(|(name, stages)| {
+                let stages: Vec<String> = stages.iter().map(|stage| json::quote(stage)).collect();
+                format!("    {}: [{}]", json::quote(name), stages.join(", "))
+            })
+            .collect();
+        let path = env::temp_dir().join(format!("kernel-rs-{}-{}.json", process::id(), test));
+        fs::write(&path, format!("{{\n  \"presets\": {{\n{}\n  }}\n}}\n", entries.join(",\n"))).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn presets_round_trip_through_the_config_file() {
+        let presets: &[(&str, &[&str])] = &[("portrait", &["bilateral:3:20", "sharpen:0.3"]), ("soft", &["gaussian:7:1.5"])];
+        let config = Config::load(&write_config("round-trip", presets)).unwrap();
+
+        assert_eq!(config.presets.len(), presets.len());
+        for (name, stages) in presets {
+            let expected: Pipeline = stages.join(",").parse().unwrap();
+            assert_eq!(config.preset(name).unwrap(), &expected, "preset {}", name);
+        }
+    }
+
+    #[test]
+    fn missing_preset_lists_the_available_ones() {
+        let config = Config::parse(r#"{ "presets": { "soft": ["blur:3"], "bold": ["sharpen:2"] } }"#).unwrap();
+        assert_eq!(config.preset("portrait").unwrap_err(), "No preset named 'portrait' in 'kernel-rs.json' (available: bold, soft)");
+        assert!(Config::parse(r#"{ "presets": { "empty": [] } }"#).is_err());
+        assert!(Config::parse(r#"{ "presets": { "typo": ["blurr:3"] } }"#).is_err());
+    }
+}
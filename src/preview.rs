@@ -0,0 +1,91 @@
+use std::fmt::Write;
+
+use image::RgbImage;
+
+use crate::{luminance, resize, ResampleMode};
+
+/// Columns used by `render_preview` when no width is given; fits a standard 80-column terminal.
+pub const DEFAULT_PREVIEW_WIDTH: u32 = 80;
+
+/// Characters for grayscale previews, from darkest to brightest.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// How `render_preview` draws the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewStyle {
+    /// 24-bit ANSI colors with half blocks (`▀`), two pixel rows per line.
+    Color,
+    /// Plain characters by brightness, e.g. `@` for white; works in any terminal or file.
+    Ascii,
+}
+
+/// Renders a `width`-column text preview of the image, one `\n`-terminated line per row of cells.
+/// Terminal cells are about twice as tall as wide, so each line covers two pixel rows of the downsampled
+/// image (Color stacks them in one half block, Ascii averages them) and the aspect ratio is kept.
+pub fn render_preview(image: &RgbImage, width: u32, style: PreviewStyle) -> String {
+    let width = width.max(1);
+    let lines = preview_lines(image.width(), image.height(), width);
+
+    let mut preview = String::new();
+    match style {
+        PreviewStyle::Color => {
+            let small = resize(image, width, lines * 2, ResampleMode::Box);
+            for y in 0..lines {
+                for x in 0..width {
+                    let [tr, tg, tb] = small.get_pixel(x, 2 * y).0;
+                    let [br, bg, bb] = small.get_pixel(x, 2 * y + 1).0;
+                    // Foreground paints the top half, background the bottom half
+                    let _ = write!(preview, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m▀", tr, tg, tb, br, bg, bb);
+                }
+                preview.push_str("\x1b[0m\n");
+            }
+        }
+        PreviewStyle::Ascii => {
+            let small = resize(image, width, lines, ResampleMode::Box);
+            for row in small.rows() {
+                for pixel in row {
+                    let level = luminance(pixel) / 255.0 * (ASCII_RAMP.len() - 1) as f32;
+                    preview.push(ASCII_RAMP[(level.round() as usize).min(ASCII_RAMP.len() - 1)] as char);
+                }
+                preview.push('\n');
+            }
+        }
+    }
+
+    preview
+}
+
+/// Number of text lines a `width`-column preview of a `image_width x image_height` image takes.
+pub fn preview_lines(image_width: u32, image_height: u32, width: u32) -> u32 {
+    let lines = image_height as f64 * width.max(1) as f64 / image_width.max(1) as f64 / 2.0;
+    (lines.round() as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::{generate_test_image, TestPattern};
+
+    #[test]
+    fn preview_has_the_requested_width_and_keeps_the_aspect_ratio() {
+        let image = generate_test_image(200, 100, TestPattern::Gradient);
+
+        // 200x100 at 40 columns is 40x20 pixels, drawn in 10 lines of two pixel rows
+        let ascii = render_preview(&image, 40, PreviewStyle::Ascii);
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert!(lines.iter().all(|line| line.chars().count() == 40));
+
+        let color = render_preview(&image, 40, PreviewStyle::Color);
+        assert_eq!(color.lines().count(), 10);
+        assert!(color.lines().all(|line| line.matches('▀').count() == 40));
+    }
+
+    #[test]
+    fn ascii_preview_goes_from_dark_to_bright() {
+        let black = render_preview(&RgbImage::new(8, 8), 4, PreviewStyle::Ascii);
+        assert_eq!(black, "    \n    \n");
+        let white = render_preview(&RgbImage::from_pixel(8, 8, image::Rgb([255; 3])), 4, PreviewStyle::Ascii);
+        assert_eq!(white, "@@@@\n@@@@\n");
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Whether the convolution routines draw a per-row progress bar on stderr. Off by default for library use.
+static ROW_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Turns the per-row progress bar of `apply_convolution` and friends on or off.
+/// Nothing is drawn when stderr is not a terminal, so redirected output stays clean.
+pub fn set_row_progress(enabled: bool) {
+    ROW_PROGRESS.store(enabled, Ordering::Relaxed);
+}
+
+/// Width of the bar in characters, excluding the label and percentage.
+const BAR_WIDTH: usize = 30;
+
+/// A thread-safe text progress bar drawn on stderr, e.g. `Blurring [###########.......]  58%`.
+/// `inc` is a single atomic add; the bar is only redrawn when the whole percentage changes.
+pub struct ProgressBar {
+    label: String,
+    total: usize,
+    done: AtomicUsize,
+    drawn_percent: AtomicUsize,
+    visible: bool,
+}
+
+impl ProgressBar {
+    pub fn new(label: impl Into<String>, total: usize) -> Self {
+        ProgressBar {
+            label: label.into(),
+            total: total.max(1),
+            done: AtomicUsize::new(0),
+            drawn_percent: AtomicUsize::new(0),
+            visible: io::stderr().is_terminal(),
+        }
+    }
+
+    /// Records `count` more finished units, redrawing if the percentage moved.
+    pub fn inc(&self, count: usize) {
+        let done = self.done.fetch_add(count, Ordering::Relaxed) + count;
+        let percent = (done * 100 / self.total).min(100);
+
+        // Only the thread that moves the percentage forward redraws
+        if self.visible && self.drawn_percent.fetch_max(percent, Ordering::Relaxed) < percent {
+            self.draw(percent);
+        }
+    }
+
+    /// Draws the completed bar and moves to the next line.
+    pub fn finish(&self) {
+        if self.visible {
+            self.draw(100);
+            eprintln!();
+        }
+    }
+
+    fn draw(&self, percent: usize) {
+        let filled = percent * BAR_WIDTH / 100;
+        eprint!("\r{} [{}{}] {:>3}%", self.label, "#".repeat(filled), ".".repeat(BAR_WIDTH - filled), percent);
+        io::stderr().flush().ok();
+    }
+}
+
+/// Per-row progress for one convolution, or nothing at all when row progress is disabled.
+pub(crate) struct RowProgress(Option<ProgressBar>);
+
+impl RowProgress {
+    pub(crate) fn start(rows: u32) -> Self {
+        RowProgress(ROW_PROGRESS.load(Ordering::Relaxed).then(|| ProgressBar::new("Convolving", rows as usize)))
+    }
+
+    pub(crate) fn row_done(&self) {
+        self.inc(1);
+    }
+
+    /// Records `rows` finished rows at once, e.g. the rows that end inside a tile of pixels.
+    pub(crate) fn inc(&self, rows: usize) {
+        if let Some(bar) = &self.0
+            && rows > 0
+        {
+            bar.inc(rows);
+        }
+    }
+
+    pub(crate) fn finish(self) {
+        if let Some(bar) = &self.0 {
+            bar.finish();
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use image::{ImageBuffer, RgbImage};
+use rayon::prelude::*;
+
+/// Replaces each pixel with the per-channel median of its `(2 * radius + 1)` square neighborhood.
+/// Removes salt-and-pepper noise that linear blurs only smear. Edges are clamped like `apply_convolution`.
+///
+/// Uses a running histogram per channel (Huang's algorithm): sliding one pixel right removes the outgoing
+/// column and adds the incoming one, so the cost grows with `radius` rather than `radius²`.
+/// `examples/median_bench.rs` times it against sorting every window.
+pub fn median_filter(image: &RgbImage, radius: usize) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: RgbImage = ImageBuffer::new(width, height);
+    if width == 0 || height == 0 {
+        return output;
+    }
+
+    let radius = radius as i32;
+    // The window always has an odd size, so the value at rank `half` is the exact median
+    let half = ((2 * radius + 1) * (2 * radius + 1) / 2) as u32;
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(y, row)| {
+            let rows: Vec<u32> = (-radius..=radius).map(|dy| (y as i32 + dy).clamp(0, height as i32 - 1) as u32).collect();
+            let column = |x: i32| {
+                let x = x.clamp(0, width as i32 - 1) as u32;
+                rows.iter().map(move |&y| image.get_pixel(x, y).0)
+            };
+
+            let mut medians = [RunningMedian::new(half); 3];
+            for x in -radius..=radius {
+                for pixel in column(x) {
+                    medians.iter_mut().zip(pixel).for_each(|(median, value)| median.add(value));
+                }
+            }
+
+            for (x, _, pixel) in row {
+                for (channel, median) in pixel.0.iter_mut().zip(medians.iter_mut()) {
+                    *channel = median.median();
+                }
+
+                // Slide the window one pixel to the right
+                for (outgoing, incoming) in column(x as i32 - radius).zip(column(x as i32 + radius + 1)) {
+                    for (median, (outgoing, incoming)) in medians.iter_mut().zip(outgoing.into_iter().zip(incoming)) {
+                        median.remove(outgoing);
+                        median.add(incoming);
+                    }
+                }
+            }
+        });
+
+    output
+}
+
+/// Histogram of one channel's window values that tracks the median as values come and go.
+#[derive(Clone, Copy)]
+struct RunningMedian {
+    histogram: [u32; 256],
+    /// Rank of the median in the window, i.e. half the window size.
+    half: u32,
+    /// Current median estimate and how many window values are below it.
+    median: u8,
+    below: u32,
+}
+
+impl RunningMedian {
+    fn new(half: u32) -> Self {
+        RunningMedian { histogram: [0; 256], half, median: 0, below: 0 }
+    }
+
+    fn add(&mut self, value: u8) {
+        self.histogram[value as usize] += 1;
+        if value < self.median {
+            self.below += 1;
+        }
+    }
+
+    fn remove(&mut self, value: u8) {
+        self.histogram[value as usize] -= 1;
+        if value < self.median {
+            self.below -= 1;
+        }
+    }
+
+    /// Moves the estimate to the value at rank `half`; it only ever moves a few bins between neighboring pixels.
+    fn median(&mut self) -> u8 {
+        while self.below > self.half {
+            self.median -= 1;
+            self.below -= self.histogram[self.median as usize];
+        }
+        while self.below + self.histogram[self.median as usize] <= self.half {
+            self.below += self.histogram[self.median as usize];
+            self.median += 1;
+        }
+        self.median
+    }
+}
+
+/// Grows bright regions by taking the per-channel maximum over a `(2 * radius + 1)` square.
+pub fn dilate(image: &RgbImage, radius: usize) -> RgbImage {
+    box_max(image, radius)
+}
+
+/// Grows dark regions by taking the per-channel minimum over a `(2 * radius + 1)` square.
+pub fn erode(image: &RgbImage, radius: usize) -> RgbImage {
+    box_min(image, radius)
+}
+
+/// Per-channel maximum over a `(2 * radius + 1)` square, the box-filter name for `dilate`.
+pub fn box_max(image: &RgbImage, radius: usize) -> RgbImage {
+    extreme_filter(image, radius, u8::max)
+}
+
+/// Per-channel minimum over a `(2 * radius + 1)` square, the box-filter name for `erode`.
+pub fn box_min(image: &RgbImage, radius: usize) -> RgbImage {
+    extreme_filter(image, radius, u8::min)
+}
+
+/// Per-channel spread (maximum minus minimum) over a `(2 * radius + 1)` square: 0 on flat areas and bright
+/// along edges and texture, i.e. `box_max` minus `box_min` (the morphological gradient).
+pub fn box_range(image: &RgbImage, radius: usize) -> RgbImage {
+    let max = box_max(image, radius);
+    let min = box_min(image, radius);
+    let (width, height) = image.dimensions();
+    let range = max.as_raw().iter().zip(min.as_raw()).map(|(&max, &min)| max - min).collect();
+    ImageBuffer::from_raw(width, height, range).expect("buffer matches the image dimensions")
+}
+
+/// Reduces each channel of the clamped square neighborhood with `pick` (`u8::max` or `u8::min`).
+///
+/// The maximum of a square is the maximum of its rows' maxima, so this runs a horizontal pass and then a
+/// vertical one over its result: `2 * (2 * radius + 1)` reads per pixel instead of `(2 * radius + 1)²`.
+/// Clamped border samples repeat the edge pixel, which is already inside the window, so both passes simply
+/// stop at the image edge.
+fn extreme_filter<F>(image: &RgbImage, radius: usize, pick: F) -> RgbImage
+where
+    F: Fn(u8, u8) -> u8 + Sync,
+{
+    let (width, height) = image.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let row_len = width * 3;
+    if width == 0 || height == 0 {
+        return ImageBuffer::new(width as u32, height as u32);
+    }
+
+    let source = image.as_raw();
+    let mut horizontal = vec![0u8; source.len()];
+    horizontal.par_chunks_mut(row_len).zip(source.par_chunks(row_len)).for_each(|(out, row)| {
+        for x in 0..width {
+            let (start, end) = (x.saturating_sub(radius), (x + radius).min(width - 1));
+            for channel in 0..3 {
+                out[x * 3 + channel] = (start..=end).map(|nx| row[nx * 3 + channel]).reduce(&pick).unwrap_or(0);
+            }
+        }
+    });
+
+    let mut output = vec![0u8; source.len()];
+    output.par_chunks_mut(row_len).enumerate().for_each(|(y, out)| {
+        let (start, end) = (y.saturating_sub(radius), (y + radius).min(height - 1));
+        out.copy_from_slice(&horizontal[start * row_len..(start + 1) * row_len]);
+        for ny in start + 1..=end {
+            let row = &horizontal[ny * row_len..(ny + 1) * row_len];
+            for (value, &other) in out.iter_mut().zip(row) {
+                *value = pick(*value, other);
+            }
+        }
+    });
+
+    ImageBuffer::from_raw(width as u32, height as u32, output).expect("buffer matches the image dimensions")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    /// A 7x7 black image with a single bright pixel at (3, 3).
+    fn bright_dot() -> RgbImage {
+        RgbImage::from_fn(7, 7, |x, y| Rgb([if (x, y) == (3, 3) { 200 } else { 10 }; 3]))
+    }
+
+    /// A 6x4 image that is dark in columns 0–2 and bright in 3–5.
+    fn vertical_edge() -> RgbImage {
+        RgbImage::from_fn(6, 4, |x, _| Rgb([if x < 3 { 20 } else { 220 }; 3]))
+    }
+
+    #[test]
+    fn box_max_grows_bright_regions() {
+        let output = box_max(&bright_dot(), 1);
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let inside = x.abs_diff(3) <= 1 && y.abs_diff(3) <= 1;
+            assert_eq!(pixel.0, [if inside { 200 } else { 10 }; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn box_min_grows_dark_regions() {
+        let output = box_min(&vertical_edge(), 2);
+        for (x, y, pixel) in output.enumerate_pixels() {
+            assert_eq!(pixel.0, [if x < 5 { 20 } else { 220 }; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn box_range_highlights_edges() {
+        let output = box_range(&vertical_edge(), 1);
+        for (x, y, pixel) in output.enumerate_pixels() {
+            // Only the columns whose window straddles the edge see both sides
+            let expected = if x == 2 || x == 3 { 200 } else { 0 };
+            assert_eq!(pixel.0, [expected; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn box_range_matches_max_minus_min() {
+        let image = RgbImage::from_fn(9, 5, |x, y| Rgb([(x * 29 + y * 7) as u8, (x * y * 11) as u8, ((x ^ y) * 30) as u8]));
+        let (max, min, range) = (box_max(&image, 2), box_min(&image, 2), box_range(&image, 2));
+        for ((max, min), range) in max.pixels().zip(min.pixels()).zip(range.pixels()) {
+            for channel in 0..3 {
+                assert_eq!(range[channel], max[channel] - min[channel]);
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_filter_matches_a_brute_force_window() {
+        let image = RgbImage::from_fn(11, 6, |x, y| Rgb([(x * 37 + y * 91) as u8, (x * x + y) as u8, (y * 53) as u8]));
+        let output = box_max(&image, 3);
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let mut expected = [0u8; 3];
+            for dy in -3..=3 {
+                for dx in -3..=3 {
+                    let neighbor = crate::convolution::BorderMode::Clamp.sample(&image, x as i32 + dx, y as i32 + dy);
+                    for channel in 0..3 {
+                        expected[channel] = expected[channel].max(neighbor[channel]);
+                    }
+                }
+            }
+            assert_eq!(pixel.0, expected, "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn radius_zero_is_the_identity() {
+        let image = bright_dot();
+        assert_eq!(box_max(&image, 0), image);
+        assert_eq!(box_min(&image, 0), image);
+        assert_eq!(box_range(&image, 0).as_raw().iter().copied().max(), Some(0));
+    }
+
+    #[test]
+    fn empty_image_stays_empty() {
+        assert_eq!(box_max(&RgbImage::new(0, 0), 2).dimensions(), (0, 0));
+    }
+
+    #[test]
+    fn median_removes_salt_and_pepper_noise() {
+        let flat = RgbImage::from_pixel(32, 24, Rgb([128; 3]));
+        let noisy = crate::noise::add_salt_pepper_noise(&flat, 0.05, 7);
+        assert!(noisy.pixels().any(|pixel| pixel.0 == [0; 3]) && noisy.pixels().any(|pixel| pixel.0 == [255; 3]));
+
+        assert_eq!(median_filter(&noisy, 1), flat);
+    }
+
+    #[test]
+    fn single_pixels_grow_under_dilation_and_erosion() {
+        let dark_dot = RgbImage::from_fn(7, 7, |x, y| Rgb([if (x, y) == (3, 3) { 10 } else { 200 }; 3]));
+        let dilated = dilate(&bright_dot(), 2);
+        let eroded = erode(&dark_dot, 2);
+        for (x, y, pixel) in dilated.enumerate_pixels() {
+            let inside = x.abs_diff(3) <= 2 && y.abs_diff(3) <= 2;
+            assert_eq!(pixel.0, [if inside { 200 } else { 10 }; 3], "dilated pixel ({}, {})", x, y);
+            assert_eq!(eroded.get_pixel(x, y).0, [if inside { 10 } else { 200 }; 3], "eroded pixel ({}, {})", x, y);
+        }
+    }
+
+    /// The textbook median: collect and sort the clamped window of every pixel.
+    fn naive_median(image: &RgbImage, radius: i32) -> RgbImage {
+        RgbImage::from_fn(image.width(), image.height(), |x, y| {
+            let mut pixel = [0u8; 3];
+            for (channel, value) in pixel.iter_mut().enumerate() {
+                let mut window = Vec::new();
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        window.push(crate::convolution::BorderMode::Clamp.sample(image, x as i32 + dx, y as i32 + dy)[channel]);
+                    }
+                }
+                window.sort_unstable();
+                *value = window[window.len() / 2];
+            }
+            Rgb(pixel)
+        })
+    }
+
+    #[test]
+    fn running_histogram_median_matches_the_naive_median() {
+        let image = RgbImage::from_fn(23, 17, |x, y| Rgb([(x * 37 + y * 91) as u8, ((x * y) % 7 * 40) as u8, ((y * 53) ^ (x * 11)) as u8]));
+        for radius in [1, 2, 4] {
+            assert_eq!(median_filter(&image, radius), naive_median(&image, radius as i32), "radius {}", radius);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use image::{DynamicImage, ImageBuffer, Pixel};
+use rayon::prelude::*;
+
+/// How `resize` computes each output pixel from the source image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResampleMode {
+    /// Copies the closest source pixel; fast and blocky, exact for whole-number upscales.
+    Nearest,
+    /// Interpolates between the four surrounding source pixels.
+    Bilinear,
+    /// Averages every source pixel the output pixel covers, weighted by overlap. Best for thumbnails.
+    #[default]
+    Box,
+}
+
+/// Resamples an image to `new_width x new_height`. Works on RGB and RGBA alike (alpha is resampled too).
+/// Bilinear sampling clamps at the edges, so border pixels are not darkened.
+pub fn resize<P>(image: &ImageBuffer<P, Vec<u8>>, new_width: u32, new_height: u32, mode: ResampleMode) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + Send + Sync,
+{
+    let (width, height) = image.dimensions();
+    let scale_x = width as f32 / new_width as f32;
+    let scale_y = height as f32 / new_height as f32;
+
+    let mut output: ImageBuffer<P, Vec<u8>> = ImageBuffer::new(new_width, new_height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let sums = match mode {
+                    ResampleMode::Nearest => nearest(image, (x as f32 + 0.5) * scale_x, (y as f32 + 0.5) * scale_y),
+                    ResampleMode::Bilinear => bilinear(image, (x as f32 + 0.5) * scale_x - 0.5, (y as f32 + 0.5) * scale_y - 0.5),
+                    ResampleMode::Box => area_average(image, x as f32 * scale_x, y as f32 * scale_y, scale_x, scale_y),
+                };
+                for (channel, sum) in pixel.channels_mut().iter_mut().zip(sums) {
+                    *channel = sum.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+/// Output size for a `--resize` request where either dimension may be left out.
+/// A missing dimension follows the aspect ratio of the other one; neither drops below 1.
+pub fn fit_dimensions(width: u32, height: u32, target_width: Option<u32>, target_height: Option<u32>) -> (u32, u32) {
+    let scaled = |value: u32, numerator: u32, denominator: u32| {
+        ((value as f64 * numerator as f64 / denominator as f64).round() as u32).max(1)
+    };
+
+    match (target_width, target_height) {
+        (Some(target_width), Some(target_height)) => (target_width, target_height),
+        (Some(target_width), None) => (target_width, scaled(height, target_width, width)),
+        (None, Some(target_height)) => (scaled(width, target_height, height), target_height),
+        (None, None) => (width, height),
+    }
+}
+
+/// Size that fits within `max_dimension` on both sides with the same aspect ratio, or the size unchanged if it already fits.
+/// Neither side drops below 1.
+pub fn limit_dimensions(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+    if width <= max_dimension && height <= max_dimension {
+        return (width, height);
+    }
+    if width >= height {
+        fit_dimensions(width, height, Some(max_dimension), None)
+    } else {
+        fit_dimensions(width, height, None, Some(max_dimension))
+    }
+}
+
+/// `resize` for any decoded image. Grayscale and RGB(A) keep their channels; other formats,
+/// including 16-bit ones, are resampled as 8-bit RGB or RGBA.
+pub fn resize_dynamic(image: DynamicImage, new_width: u32, new_height: u32, mode: ResampleMode) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(image) => DynamicImage::ImageLuma8(resize(&image, new_width, new_height, mode)),
+        DynamicImage::ImageLumaA8(image) => DynamicImage::ImageLumaA8(resize(&image, new_width, new_height, mode)),
+        image if image.color().has_alpha() => DynamicImage::ImageRgba8(resize(&image.into_rgba8(), new_width, new_height, mode)),
+        image => DynamicImage::ImageRgb8(resize(&image.into_rgb8(), new_width, new_height, mode)),
+    }
+}
+
+/// Reads the source pixel containing the point (x, y).
+fn nearest<P: Pixel<Subpixel = u8>>(image: &ImageBuffer<P, Vec<u8>>, x: f32, y: f32) -> [f32; 4] {
+    let (width, height) = image.dimensions();
+    let pixel = image.get_pixel((x as u32).min(width - 1), (y as u32).min(height - 1));
+    to_sums(pixel)
+}
+
+/// Blends the four source pixels around the point (x, y) by their distance.
+fn bilinear<P: Pixel<Subpixel = u8>>(image: &ImageBuffer<P, Vec<u8>>, x: f32, y: f32) -> [f32; 4] {
+    let (width, height) = image.dimensions();
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let corners = [
+        (x0, y0, (1.0 - fx) * (1.0 - fy)),
+        (x1, y0, fx * (1.0 - fy)),
+        (x0, y1, (1.0 - fx) * fy),
+        (x1, y1, fx * fy),
+    ];
+
+    let mut sums = [0.0; 4];
+    for (cx, cy, weight) in corners {
+        for (sum, value) in sums.iter_mut().zip(to_sums(image.get_pixel(cx, cy))) {
+            *sum += weight * value;
+        }
+    }
+    sums
+}
+
+/// Averages the source area `[x, x + span_x) x [y, y + span_y)`, weighting partly covered pixels by overlap.
+fn area_average<P: Pixel<Subpixel = u8>>(image: &ImageBuffer<P, Vec<u8>>, x: f32, y: f32, span_x: f32, span_y: f32) -> [f32; 4] {
+    let (width, height) = image.dimensions();
+    let (x_end, y_end) = (x + span_x, y + span_y);
+
+    let mut sums = [0.0; 4];
+    let mut total_weight = 0.0;
+    for sy in (y.floor() as u32)..(y_end.ceil() as u32).min(height) {
+        let weight_y = (y_end.min(sy as f32 + 1.0) - y.max(sy as f32)).max(0.0);
+        for sx in (x.floor() as u32)..(x_end.ceil() as u32).min(width) {
+            let weight = weight_y * (x_end.min(sx as f32 + 1.0) - x.max(sx as f32)).max(0.0);
+            for (sum, value) in sums.iter_mut().zip(to_sums(image.get_pixel(sx, sy))) {
+                *sum += weight * value;
+            }
+            total_weight += weight;
+        }
+    }
+
+    if total_weight > 0.0 {
+        sums.map(|sum| sum / total_weight)
+    } else {
+        nearest(image, x, y)
+    }
+}
+
+/// Widens a pixel's channels to f32, padding unused slots (RGB has no fourth channel) with 0.
+fn to_sums<P: Pixel<Subpixel = u8>>(pixel: &P) -> [f32; 4] {
+    let mut sums = [0.0; 4];
+    for (sum, &channel) in sums.iter_mut().zip(pixel.channels()) {
+        *sum = channel as f32;
+    }
+    sums
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn downscaling_a_solid_image_keeps_its_color() {
+        let solid = RgbImage::from_pixel(17, 9, Rgb([200, 40, 90]));
+        for mode in [ResampleMode::Nearest, ResampleMode::Bilinear, ResampleMode::Box] {
+            assert_eq!(resize(&solid, 5, 3, mode), RgbImage::from_pixel(5, 3, Rgb([200, 40, 90])), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn nearest_upscale_doubles_pixel_blocks() {
+        let image = RgbImage::from_fn(3, 2, |x, y| Rgb([(x * 80) as u8, (y * 200) as u8, 7]));
+        let doubled = resize(&image, 6, 4, ResampleMode::Nearest);
+        for (x, y, pixel) in doubled.enumerate_pixels() {
+            assert_eq!(pixel, image.get_pixel(x / 2, y / 2), "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn missing_dimensions_follow_the_aspect_ratio() {
+        assert_eq!(fit_dimensions(400, 200, Some(100), None), (100, 50));
+        assert_eq!(fit_dimensions(400, 200, None, Some(50)), (100, 50));
+        assert_eq!(fit_dimensions(400, 200, Some(3), None), (3, 2));
+    }
+}
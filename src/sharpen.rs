@@ -0,0 +1,227 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::convolution::{convolve, convolve_float, to_f32, RgbImageF32};
+use crate::kernel::{clamp_kernel_size, generate_box_blur_kernel, generate_gaussian_kernel, kernel_size_for_sigma};
+
+/// Highest unsharp amount `unsharp_amount` accepts, in percent; more only clips every edge to black and white.
+pub const MAX_UNSHARP_PERCENT: f32 = 1000.0;
+
+/// Converts an unsharp amount given in percent, as photo editors show it, into the factor `unsharp_mask` takes:
+/// 100% is 1.0 and 150% is 1.5. Negative values count as 0 and values past `MAX_UNSHARP_PERCENT` are capped.
+pub fn unsharp_amount(percent: f32) -> f32 {
+    percent.clamp(0.0, MAX_UNSHARP_PERCENT) / 100.0
+}
+
+/// Sharpens by adding back the detail removed by a Gaussian blur: `original + amount * (original - blurred)`.
+/// `sigma` sets the size of the details that get boosted and `amount` how strongly; amount 0 returns the original.
+/// A non-positive sigma has nothing to blur away, so the image is returned unchanged.
+pub fn unsharp_mask(image: &RgbImage, sigma: f32, amount: f32) -> RgbImage {
+    if sigma <= 0.0 {
+        return image.clone();
+    }
+
+    // Cover ±3σ so the Gaussian tails are not cut off, but no more than the image needs
+    let (width, height) = image.dimensions();
+    let size = clamp_kernel_size(kernel_size_for_sigma(sigma), width, height);
+    let blurred = convolve(image, &generate_gaussian_kernel(size, sigma));
+
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let original = image.get_pixel(x, y);
+                let blurred = blurred.get_pixel(x, y);
+                for channel in 0..3 {
+                    let detail = original[channel] as f32 - blurred[channel] as f32;
+                    let sharpened = original[channel] as f32 + amount * detail;
+                    pixel.0[channel] = sharpened.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+/// Edge-aware sharpening: like `unsharp_mask`, but the smooth base is a guided filter of the image against itself
+/// (He et al.) instead of a Gaussian blur. The guided filter smooths flat and gently varying areas over a
+/// `(2 * radius + 1)` window but keeps strong edges, so the detail layer `original - base` holds texture and fine
+/// detail without the edge overshoot that causes unsharp halos. `epsilon` is on the 0–1 scale of the local
+/// variance: windows with variance well above it (real edges) are left alone, e.g. 0.01 treats changes under
+/// about 0.1 (25 levels) as detail. `amount` scales the detail that is added back; 0 returns the original.
+pub fn guided_sharpen(image: &RgbImage, radius: usize, epsilon: f32, amount: f32) -> RgbImage {
+    if radius == 0 || amount == 0.0 {
+        return image.clone();
+    }
+
+    let original = to_f32(image);
+    let base = guided_filter(&original, radius, epsilon.max(0.0) * 255.0 * 255.0);
+
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let original = original.get_pixel(x, y);
+                let base = base.get_pixel(x, y);
+                for channel in 0..3 {
+                    let detail = original[channel] - base[channel];
+                    pixel.0[channel] = (original[channel] + amount * detail).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+/// Self-guided filter of each channel: every window fits the linear model `q = a * I + b`, with
+/// `a = variance / (variance + epsilon)`, and each pixel averages the models of the windows covering it.
+/// `epsilon` is on the squared 0–255 scale of `image`.
+fn guided_filter(image: &RgbImageF32, radius: usize, epsilon: f32) -> RgbImageF32 {
+    let window = generate_box_blur_kernel(2 * radius + 1);
+    let map = |image: &RgbImageF32, f: &(dyn Fn(usize, f32) -> f32 + Sync)| -> RgbImageF32 {
+        let (width, height) = image.dimensions();
+        let values = image.as_raw().par_iter().enumerate().map(|(index, &value)| f(index, value)).collect();
+        ImageBuffer::from_raw(width, height, values).expect("Buffer size matches the image dimensions")
+    };
+
+    let mean = convolve_float(image, &window);
+    let mean_of_squares = convolve_float(&map(image, &|_, value| value * value), &window);
+    let slope = map(&mean, &|index, mean| {
+        let variance = (mean_of_squares.as_raw()[index] - mean * mean).max(0.0);
+        variance / (variance + epsilon)
+    });
+    let offset = map(&mean, &|index, mean| mean - slope.as_raw()[index] * mean);
+
+    let (mean_slope, mean_offset) = (convolve_float(&slope, &window), convolve_float(&offset, &window));
+    map(image, &|index, value| mean_slope.as_raw()[index] * value + mean_offset.as_raw()[index])
+}
+
+/// Sharpens like `Filter::Sharpen` but clamps each result to the darkest and brightest value of the pixel's
+/// 3x3 neighborhood (per channel), so edges get crisper without the bright and dark halos of over/undershoot.
+/// `strength` 0 returns the original; edge pixels reuse their nearest neighbors.
+pub fn sharpen_conservative(image: &RgbImage, strength: f32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let center = image.get_pixel(x, y);
+                let mut low = center.0;
+                let mut high = center.0;
+                let mut cross = [0.0f32; 3];
+
+                for dy in -1i32..=1 {
+                    for dx in -1i32..=1 {
+                        let nx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                        let ny = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                        let neighbor = image.get_pixel(nx, ny);
+                        for channel in 0..3 {
+                            low[channel] = low[channel].min(neighbor[channel]);
+                            high[channel] = high[channel].max(neighbor[channel]);
+                            // Same taps as the sharpen kernel: the four direct neighbors
+                            if dx.abs() + dy.abs() == 1 {
+                                cross[channel] += neighbor[channel] as f32;
+                            }
+                        }
+                    }
+                }
+
+                for channel in 0..3 {
+                    let value = center[channel] as f32;
+                    let sharpened = value + strength * (4.0 * value - cross[channel]);
+                    pixel.0[channel] = sharpened.round().clamp(low[channel] as f32, high[channel] as f32) as u8;
+                }
+            }
+        });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::pattern::{generate_test_image, TestPattern};
+
+    #[test]
+    fn unsharp_amount_zero_returns_the_original() {
+        let image = generate_test_image(16, 12, TestPattern::Checkerboard { cell: 3 });
+        assert_eq!(unsharp_mask(&image, 1.5, 0.0), image);
+        assert_eq!(unsharp_mask(&image, 0.0, 2.0), image);
+    }
+
+    #[test]
+    fn unsharp_mask_saturates_instead_of_wrapping() {
+        let image = generate_test_image(16, 12, TestPattern::Checkerboard { cell: 3 });
+        let sharpened = unsharp_mask(&image, 1.0, 5.0);
+        // Past the edges the boost overshoots 0 and 255, which must clamp rather than wrap around
+        assert!(sharpened.pixels().zip(image.pixels()).all(|(sharpened, original)| sharpened == original));
+    }
+
+    #[test]
+    fn unsharp_kernel_is_capped_to_the_image() {
+        // sigma 500 asks for a 3001-wide kernel; a 4x4 image needs at most 7
+        let image = generate_test_image(4, 4, TestPattern::Gradient);
+        assert_eq!(unsharp_mask(&image, 500.0, 1.0).dimensions(), (4, 4));
+    }
+
+    /// The darkest and brightest value of the 3x3 neighborhood of (x, y) in channel 0, clamped at the edges.
+    fn neighborhood_range(image: &RgbImage, x: u32, y: u32) -> (u8, u8) {
+        let mut range = (u8::MAX, u8::MIN);
+        for ny in y.saturating_sub(1)..=(y + 1).min(image.height() - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(image.width() - 1) {
+                let value = image.get_pixel(nx, ny)[0];
+                range = (range.0.min(value), range.1.max(value));
+            }
+        }
+        range
+    }
+
+    #[test]
+    fn conservative_sharpen_stays_within_the_neighborhood() {
+        // A soft step edge, with room above and below for plain sharpening to overshoot
+        let ramp = [60, 60, 60, 100, 150, 190, 190, 190];
+        let image = RgbImage::from_fn(8, 4, |x, _| Rgb([ramp[x as usize]; 3]));
+
+        let sharpened = sharpen_conservative(&image, 2.0);
+        assert_ne!(sharpened, image);
+        for (x, y, pixel) in sharpened.enumerate_pixels() {
+            let (low, high) = neighborhood_range(&image, x, y);
+            assert!((low..=high).contains(&pixel[0]), "pixel ({}, {})", x, y);
+        }
+
+        let plain = Filter::Sharpen { strength: 2.0, conservative: false }.apply(&image);
+        assert!(plain.pixels().any(|pixel| pixel[0] < 60 || pixel[0] > 190));
+    }
+
+    /// Mean over the image of the variance of channel 0 in each pixel's 3x3 neighborhood, a measure of local contrast.
+    fn mean_local_variance(image: &RgbImage) -> f64 {
+        let mut total = 0.0;
+        for (x, y, _) in image.enumerate_pixels() {
+            let window: Vec<f64> = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| (x as i32 + dx, y as i32 + dy)))
+                .map(|(nx, ny)| image.get_pixel(nx.clamp(0, image.width() as i32 - 1) as u32, ny.clamp(0, image.height() as i32 - 1) as u32)[0] as f64)
+                .collect();
+            let mean = window.iter().sum::<f64>() / 9.0;
+            total += window.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / 9.0;
+        }
+        total / (image.width() * image.height()) as f64
+    }
+
+    #[test]
+    fn guided_sharpen_boosts_local_contrast_with_the_amount() {
+        // Fine, low-contrast texture that the guided filter treats as detail
+        let image = RgbImage::from_fn(24, 16, |x, y| Rgb([(110 + (x * 7 + y * 3) % 5 * 6) as u8; 3]));
+        assert_eq!(guided_sharpen(&image, 2, 0.01, 0.0), image);
+
+        let variances: Vec<f64> = [0.0, 0.5, 1.0, 2.0].iter().map(|&amount| mean_local_variance(&guided_sharpen(&image, 2, 0.01, amount))).collect();
+        assert!(variances.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", variances);
+    }
+}
@@ -0,0 +1,74 @@
+use image::{ImageBuffer, Rgb, RgbImage};
+use rayon::prelude::*;
+
+use crate::blur::box_blur_sat;
+use crate::color::{luminance, to_grayscale};
+
+/// Binarizes an image: pixels whose luminance is at least `level` become white, the rest black.
+/// Handy for scanned documents with even lighting.
+pub fn threshold(image: &RgbImage, level: u8) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let white = luminance(image.get_pixel(x, y)) >= level as f32;
+                pixel.0 = [if white { 255 } else { 0 }; 3];
+            }
+        });
+
+    output
+}
+
+/// Binarizes against the local mean luminance of a `block_size` square instead of one global level,
+/// so shadows and uneven lighting don't swallow detail. A pixel is white when it is at least `mean - c`.
+pub fn adaptive_threshold(image: &RgbImage, block_size: usize, c: f32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let gray = to_grayscale(image);
+
+    // The summed-area box blur gives every local mean in constant time
+    let mean = box_blur_sat(&gray, block_size / 2);
+
+    let mut output: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    output
+        .enumerate_rows_mut()
+        .par_bridge()
+        .for_each(|(_y, row)| {
+            for (x, y, pixel) in row {
+                let white = gray.get_pixel(x, y)[0] as f32 >= mean.get_pixel(x, y)[0] as f32 - c;
+                pixel.0 = [if white { 255 } else { 0 }; 3];
+            }
+        });
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 64x8 horizontal gray ramp, 4 levels per column, with a dark vertical line at column 30.
+    fn gradient_with_line() -> RgbImage {
+        RgbImage::from_fn(64, 8, |x, _| Rgb([if x == 30 { 60 } else { (x * 4) as u8 }; 3]))
+    }
+
+    #[test]
+    fn threshold_splits_a_gradient_at_the_level() {
+        let ramp = RgbImage::from_fn(64, 8, |x, _| Rgb([(x * 4) as u8; 3]));
+        for (x, y, pixel) in threshold(&ramp, 128).enumerate_pixels() {
+            assert_eq!(pixel.0, [if x >= 32 { 255 } else { 0 }; 3], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn adaptive_threshold_follows_the_gradient() {
+        // The ramp sits at its local mean (within a few levels at the clamped dark end), so only the line darker
+        // than its surroundings turns black; a global level would cut the ramp in half instead
+        for (x, y, pixel) in adaptive_threshold(&gradient_with_line(), 15, 10.0).enumerate_pixels() {
+            assert_eq!(pixel.0, [if x == 30 { 0 } else { 255 }; 3], "pixel ({}, {})", x, y);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::fmt;
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel};
+
+use crate::convolution::{BorderMode, Channel};
+
+/// Error returned when a crop rectangle is empty or does not fit inside the image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CropError {
+    pub rect: (u32, u32, u32, u32),
+    pub image_size: (u32, u32),
+}
+
+impl fmt::Display for CropError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (x, y, width, height) = self.rect;
+        let (image_width, image_height) = self.image_size;
+        write!(f, "crop {}x{} at ({}, {}) does not fit inside the {}x{} image", width, height, x, y, image_width, image_height)
+    }
+}
+
+impl std::error::Error for CropError {}
+
+/// Copies the `width x height` region whose top-left corner is (x, y).
+/// Returns an error if the region is empty or extends past the image.
+pub fn crop<P: Pixel + 'static>(image: &ImageBuffer<P, Vec<P::Subpixel>>, x: u32, y: u32, width: u32, height: u32) -> Result<ImageBuffer<P, Vec<P::Subpixel>>, CropError> {
+    check_crop(image.dimensions(), x, y, width, height)?;
+    Ok(image.view(x, y, width, height).to_image())
+}
+
+/// Grows the image by `padding` pixels on every side, filling the new border according to `border`
+/// (e.g. `BorderMode::ConstantRgb` for a solid background color). `crop` with (padding, padding) undoes it.
+pub fn pad<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>, padding: u32, border: BorderMode) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel,
+    P::Subpixel: Channel,
+{
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_fn(width + 2 * padding, height + 2 * padding, |x, y| {
+        border.sample(image, x as i32 - padding as i32, y as i32 - padding as i32)
+    })
+}
+
+/// Same as `pad` for a decoded image of any color type.
+pub fn pad_dynamic(image: &DynamicImage, padding: u32, border: BorderMode) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(image) => DynamicImage::ImageLuma8(pad(image, padding, border)),
+        DynamicImage::ImageLumaA8(image) => DynamicImage::ImageLumaA8(pad(image, padding, border)),
+        DynamicImage::ImageRgb8(image) => DynamicImage::ImageRgb8(pad(image, padding, border)),
+        DynamicImage::ImageRgba8(image) => DynamicImage::ImageRgba8(pad(image, padding, border)),
+        DynamicImage::ImageLuma16(image) => DynamicImage::ImageLuma16(pad(image, padding, border)),
+        DynamicImage::ImageLumaA16(image) => DynamicImage::ImageLumaA16(pad(image, padding, border)),
+        DynamicImage::ImageRgb16(image) => DynamicImage::ImageRgb16(pad(image, padding, border)),
+        DynamicImage::ImageRgba16(image) => DynamicImage::ImageRgba16(pad(image, padding, border)),
+        DynamicImage::ImageRgb32F(image) => DynamicImage::ImageRgb32F(pad(image, padding, border)),
+        image => DynamicImage::ImageRgba32F(pad(&image.to_rgba32f(), padding, border)),
+    }
+}
+
+/// Same as `crop` for a decoded image of any color type.
+pub fn crop_dynamic(image: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> Result<DynamicImage, CropError> {
+    check_crop(image.dimensions(), x, y, width, height)?;
+    Ok(image.crop_imm(x, y, width, height))
+}
+
+/// Checks that the crop rectangle is non-empty and lies entirely within the image.
+fn check_crop(image_size: (u32, u32), x: u32, y: u32, width: u32, height: u32) -> Result<(), CropError> {
+    let (image_width, image_height) = image_size;
+    // u64 so huge offsets can't overflow past the check
+    let fits = width > 0
+        && height > 0
+        && x as u64 + width as u64 <= image_width as u64
+        && y as u64 + height as u64 <= image_height as u64;
+
+    if fits {
+        Ok(())
+    } else {
+        Err(CropError { rect: (x, y, width, height), image_size })
+    }
+}
+
+/// Mirror axis for `flip_dynamic`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flip {
+    /// Left and right swap places.
+    Horizontal,
+    /// Top and bottom swap places.
+    Vertical,
+}
+
+/// Clockwise quarter turns for `rotate_dynamic`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// The rotation for 90, 180 or 270 degrees clockwise; any other angle gives `None`.
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees {
+            90 => Some(Rotation::Rotate90),
+            180 => Some(Rotation::Rotate180),
+            270 => Some(Rotation::Rotate270),
+            _ => None,
+        }
+    }
+
+    /// The clockwise angle in degrees.
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::Rotate90 => 90,
+            Rotation::Rotate180 => 180,
+            Rotation::Rotate270 => 270,
+        }
+    }
+}
+
+/// Mirrors the image left to right.
+pub fn flip_horizontal<P: Pixel>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_fn(width, height, |x, y| *image.get_pixel(width - 1 - x, y))
+}
+
+/// Mirrors the image top to bottom.
+pub fn flip_vertical<P: Pixel>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_fn(width, height, |x, y| *image.get_pixel(x, height - 1 - y))
+}
+
+/// Turns the image a quarter clockwise; a `W x H` image becomes `H x W` and the top-left pixel ends up top-right.
+pub fn rotate_90<P: Pixel>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_fn(height, width, |x, y| *image.get_pixel(y, height - 1 - x))
+}
+
+/// Turns the image upside down, the same as flipping it both ways.
+pub fn rotate_180<P: Pixel>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_fn(width, height, |x, y| *image.get_pixel(width - 1 - x, height - 1 - y))
+}
+
+/// Turns the image a quarter counterclockwise; a `W x H` image becomes `H x W`.
+pub fn rotate_270<P: Pixel>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = image.dimensions();
+    ImageBuffer::from_fn(height, width, |x, y| *image.get_pixel(width - 1 - y, x))
+}
+
+/// Same as `flip_horizontal` or `flip_vertical` for a decoded image of any color type.
+pub fn flip_dynamic(image: &DynamicImage, flip: Flip) -> DynamicImage {
+    match flip {
+        Flip::Horizontal => image.fliph(),
+        Flip::Vertical => image.flipv(),
+    }
+}
+
+/// Same as `rotate_90`, `rotate_180` or `rotate_270` for a decoded image of any color type.
+pub fn rotate_dynamic(image: &DynamicImage, rotation: Rotation) -> DynamicImage {
+    match rotation {
+        Rotation::Rotate90 => image.rotate90(),
+        Rotation::Rotate180 => image.rotate180(),
+        Rotation::Rotate270 => image.rotate270(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// A 4x3 image whose pixels encode their own coordinates as (x, y, 0), so every position is distinct.
+    fn coordinates() -> RgbImage {
+        RgbImage::from_fn(4, 3, |x, y| Rgb([x as u8, y as u8, 0]))
+    }
+
+    #[test]
+    fn crop_copies_exactly_the_rectangle() {
+        let cropped = crop(&coordinates(), 1, 1, 2, 2).unwrap();
+        assert_eq!(cropped.into_raw(), vec![1, 1, 0, 2, 1, 0, 1, 2, 0, 2, 2, 0]);
+    }
+
+    #[test]
+    fn crop_rejects_empty_and_oversized_rectangles() {
+        let image = coordinates();
+        assert_eq!(crop(&image, 0, 0, 0, 2), Err(CropError { rect: (0, 0, 0, 2), image_size: (4, 3) }));
+        let err = crop(&image, 3, 0, 2, 1).unwrap_err();
+        assert_eq!(err.to_string(), "crop 2x1 at (3, 0) does not fit inside the 4x3 image");
+    }
+
+    /// Checks that `output` is `dimensions` in size and each pixel holds the input coordinates `source` gives for it.
+    fn assert_read_from(output: &RgbImage, dimensions: (u32, u32), source: impl Fn(u32, u32) -> (u32, u32)) {
+        assert_eq!(output.dimensions(), dimensions);
+        for (x, y, pixel) in output.enumerate_pixels() {
+            let (sx, sy) = source(x, y);
+            assert_eq!(pixel.0, [sx as u8, sy as u8, 0], "pixel ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn flips_and_rotations_move_each_pixel_to_its_place() {
+        let image = coordinates();
+        assert_read_from(&flip_horizontal(&image), (4, 3), |x, y| (3 - x, y));
+        assert_read_from(&flip_vertical(&image), (4, 3), |x, y| (x, 2 - y));
+        assert_read_from(&rotate_90(&image), (3, 4), |x, y| (y, 2 - x));
+        assert_read_from(&rotate_180(&image), (4, 3), |x, y| (3 - x, 2 - y));
+        assert_read_from(&rotate_270(&image), (3, 4), |x, y| (3 - y, x));
+
+        // A quarter clockwise puts the top-left pixel top-right
+        assert_eq!(rotate_90(&image).get_pixel(2, 0).0, [0, 0, 0]);
+        assert_eq!(rotate_90(&rotate_270(&image)), image);
+    }
+
+    #[test]
+    fn dynamic_transforms_match_the_buffer_versions() {
+        let image = coordinates();
+        let dynamic = DynamicImage::ImageRgb8(image.clone());
+        assert_eq!(flip_dynamic(&dynamic, Flip::Horizontal).into_rgb8(), flip_horizontal(&image));
+        assert_eq!(flip_dynamic(&dynamic, Flip::Vertical).into_rgb8(), flip_vertical(&image));
+        assert_eq!(rotate_dynamic(&dynamic, Rotation::Rotate90).into_rgb8(), rotate_90(&image));
+        assert_eq!(rotate_dynamic(&dynamic, Rotation::Rotate180).into_rgb8(), rotate_180(&image));
+        assert_eq!(rotate_dynamic(&dynamic, Rotation::Rotate270).into_rgb8(), rotate_270(&image));
+    }
+}